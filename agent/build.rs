@@ -0,0 +1,14 @@
+// Copies Cargo.lock into OUT_DIR for `info`, which names the dependency versions that were
+// built. The lockfile isn't committed, so without one an empty file is written and `info`
+// reports the versions as unknown.
+use std::path::PathBuf;
+use std::{env, fs};
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let lock_path = manifest_dir.join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+    let lock = fs::read_to_string(&lock_path).unwrap_or_default();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("Cargo.lock"), lock).unwrap();
+}
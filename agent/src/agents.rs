@@ -1,29 +1,202 @@
+use crate::conversations::StoredMessage;
+use crate::correlation;
+use crate::delivery;
+use crate::doctor_llm::DoctorLlm;
+use crate::event_log;
+use crate::followups;
+use crate::imaging;
+use crate::metrics::{self, TopicMetrics};
+use crate::patient::{self, PatientContext};
+use crate::registry::{self, Announcement};
+use crate::rhythm;
+use crate::runner::{self, AgentRunner, ClusterNode};
+use crate::telemetry;
+use crate::tool_docs;
+use crate::topics;
+use crate::usage;
 use async_trait::async_trait;
-use autoagents::core::actor::Topic;
-use autoagents::core::agent::memory::SlidingWindowMemory;
+use autoagents::core::agent::memory::{MemoryProvider, SlidingWindowMemory};
 use autoagents::core::agent::prebuilt::executor::{ReActAgentOutput, ReActExecutor};
 use autoagents::core::agent::task::Task;
-use autoagents::core::agent::{AgentBuilder, AgentDeriveT, AgentExecutor, Context, ExecutorConfig};
+use autoagents::core::agent::{
+    AgentBuilder, AgentConfig, AgentDeriveT, AgentExecutor, Context, ExecutorConfig,
+};
 use autoagents::core::environment::Environment;
 use autoagents::core::error::Error;
 use autoagents::core::protocol::{Event, TaskResult};
 use autoagents::core::runtime::{ClusterClientRuntime, ClusterHostRuntime};
 use autoagents::core::runtime::{Runtime, TypedRuntime};
 use autoagents::core::tool::{ToolCallError, ToolInputT, ToolRuntime, ToolT};
-use autoagents::llm::backends::openai::OpenAI;
-use autoagents::llm::chat::{ChatMessage, ChatRole, MessageType};
-use autoagents_derive::{ToolInput, agent, tool};
+use autoagents::llm::LLMProvider;
+use autoagents::llm::chat::{
+    ChatMessage, ChatRole, ImageMime, MessageType, StructuredOutputFormat,
+};
+use autoagents_derive::{ToolInput, agent};
 use colored::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::mpsc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{Semaphore, mpsc, oneshot};
 use tokio::time::{Duration, sleep};
 use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use tokio_util::sync::CancellationToken;
+
+// The doctor's tools implement ToolT through this macro rather than with #[tool] so their
+// descriptions can be replaced at startup with --tool-descriptions. It also lists their names
+// in DOCTOR_TOOLS, so the list can't drift from the tools.
+macro_rules! doctor_tools {
+    ($($tool:ident {
+        name: $name:literal,
+        input: $input:ty,
+        description: $description:expr,
+        $(unavailable if $unavailable:expr => $unavailable_description:expr,)?
+    })*) => {
+        pub const DOCTOR_TOOLS: &[&str] = &[$($name),*];
+
+        // Name and description of every doctor tool given `overrides`
+        #[cfg(test)]
+        fn doctor_tool_descriptions(
+            overrides: &tool_docs::Overrides,
+        ) -> Vec<(&'static str, &'static str)> {
+            vec![$(($name, $tool {}.description_from(overrides))),*]
+        }
+
+        $(impl $tool {
+            fn description_from(&self, overrides: &tool_docs::Overrides) -> &'static str {
+                $(if $unavailable {
+                    return $unavailable_description;
+                })?
+                overrides.description($name, $description)
+            }
+        }
+
+        impl ToolT for $tool {
+            fn name(&self) -> &'static str {
+                $name
+            }
+
+            // With the overrides loaded from --tool-descriptions
+            fn description(&self) -> &'static str {
+                self.description_from(&tool_docs::current())
+            }
+
+            fn args_schema(&self) -> Value {
+                tool_args_schema::<$input>()
+            }
+        })*
+    };
+}
+
+doctor_tools! {
+    PublishTopicToAnalysis {
+        name: "ecg_analysis_tool",
+        input: PublishTopicToAnalysisArgs,
+        description: "Use this tool to publish a topic to the analysis agent which can get the ecg data and analysis, Once the query is submitted, you can respond back to the user that the analysis will be coming shortly",
+    }
+    CameraAnalysisTool {
+        name: "camera_analysis",
+        input: CameraAnalysisArgs,
+        description: "Request camera to capture and analyze an image based on user query",
+        // Tells the LLM up front, so it doesn't plan around a camera check it can't make
+        unavailable if camera_disabled() => "Camera analysis is disabled by site policy. Do not call this tool; if the user asks for a visual check, tell them the camera is unavailable here.",
+    }
+    FetchPastReports {
+        name: "fetch_past_reports",
+        input: FetchPastReportsArgs,
+        description: "Fetch summaries of the most recent ECG analysis reports, optionally filtered by text they contain. Use this to answer questions about previous analyses.",
+    }
+    CompareAnalyses {
+        name: "compare_analyses",
+        input: CompareAnalysesArgs,
+        description: "Compare two ECG analysis reports and list new, resolved, worsened and unchanged findings. Show the returned comparison to the user as-is.",
+    }
+    ClassifyRhythm {
+        name: "classify_rhythm",
+        input: ClassifyRhythmArgs,
+        description: "Estimate heart rate and a coarse rhythm class (bradycardia/normal/tachycardia, regular/irregular) from a file of raw ECG samples. Runs locally and instantly; use it for a first look and combine it with the full analysis.",
+    }
+    SetPatientContext {
+        name: "set_patient_context",
+        input: SetPatientContextArgs,
+        description: "Set the active patient for this session. The patient context is included in every following ECG analysis request.",
+    }
+    ScheduleFollowup {
+        name: "schedule_followup",
+        input: ScheduleFollowupArgs,
+        description: "Schedule a follow-up ECG analysis: the query is submitted to the analysis agent after delay_secs seconds. Returns the follow-up id; tell the user when the follow-up will run and that it can be cancelled.",
+    }
+    CancelFollowup {
+        name: "cancel_followup",
+        input: CancelFollowupArgs,
+        description: "Cancel a follow-up scheduled with schedule_followup, by its id. Without an id, lists the pending follow-ups and cancels nothing.",
+    }
+}
+
+// JSON schema of a tool's arguments, as generated by #[derive(ToolInput)]
+fn tool_args_schema<T: ToolInputT>() -> Value {
+    serde_json::from_str(T::io_schema()).unwrap_or(Value::Null)
+}
+
+// What a remote-facing tool did with the request; serialized in snake_case for the LLM
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolStatus {
+    Submitted,   // Published to the cluster; the answer arrives as a later task
+    Declined,    // The user refused, nothing was published
+    Unavailable, // The service is offline or disabled, nothing was published
+}
+
+// Result returned by the tools that hand work to another node, so the doctor's reasoning,
+// the GUI and the logs all see the same machine-readable shape
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToolResult {
+    pub status: ToolStatus,
+    pub message: String, // Instructions for the LLM in plain language
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>, // e.g. the correlation id of the published task
+}
+
+impl ToolResult {
+    pub fn new(status: ToolStatus, message: impl Into<String>) -> Self {
+        ToolResult {
+            status,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn into_value(self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+
+    // Short text for a tool observation; results of other tools are shown as raw JSON
+    pub fn describe(result: &Value) -> String {
+        match serde_json::from_value::<ToolResult>(result.clone()) {
+            Ok(tool_result) => format!(
+                "[{}] {}",
+                serde_json::to_value(tool_result.status)
+                    .ok()
+                    .and_then(|status| status.as_str().map(str::to_string))
+                    .unwrap_or_default(),
+                tool_result.message
+            ),
+            Err(_) => result.to_string(),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, ToolInput, Debug)]
 pub struct PublishTopicToAnalysisArgs {
@@ -31,50 +204,91 @@ pub struct PublishTopicToAnalysisArgs {
     query: String,
 }
 
-#[tool(
-    name = "ecg_analysis_tool",
-    description = "Use this tool to publish a topic to the analysis agent which can get the ecg data and analysis, Once the query is submitted, you can respond back to the user that the analyssi will be coming shortly",
-    input = PublishTopicToAnalysisArgs,
-)]
+#[derive(Debug)]
 struct PublishTopicToAnalysis {}
 
 #[async_trait]
 impl ToolRuntime for PublishTopicToAnalysis {
     async fn execute(&self, context: &Context, args: Value) -> Result<Value, ToolCallError> {
         println!("🔧 Tool call to publish to analysis agent");
+        guard_tool_call("ecg_analysis_tool")?;
         let typed_args: PublishTopicToAnalysisArgs = serde_json::from_value(args)?;
-        let analysis_topic = Topic::<Task>::new("analysis_agent");
+
+        // Nothing would ever answer, so don't claim the request was submitted
+        if !registry::is_online("analysis") {
+            println!(
+                "⚠️ No analysis agent is online, not publishing: {}",
+                typed_args.query
+            );
+            telemetry::lifecycle("publish_skipped", topics::ANALYSIS_REQUESTS, None, None);
+            notify_gui(
+                "⚠️ The ECG analysis service is offline - no analysis agent is connected, so no report will arrive for this request."
+                    .to_string(),
+            );
+            return Ok(ToolResult::new(
+                ToolStatus::Unavailable,
+                "The analysis service is OFFLINE: no analysis agent is connected to the cluster and the request was NOT submitted. Do not wait for a report and do not retry. Tell the user the ECG analysis service is currently offline and to try again once it is running.",
+            )
+            .into_value());
+        }
+
+        let analysis_topic = topics::topic(topics::ANALYSIS_REQUESTS);
+        let correlation_id = correlation::new_id();
 
         println!(
-            "🚀 Publishing query to analysis_agent topic: {}",
-            typed_args.query
+            "🚀 [{}] Publishing query to analysis_agent topic: {}",
+            correlation_id, typed_args.query
         );
 
-        let task = Task::new(typed_args.query.clone());
+        // Include the active patient so the analysis is grounded in who it concerns
+        let task = Task::new(correlation::tag(
+            correlation_id,
+            &patient::attach(&typed_args.query),
+        ));
         println!("📦 Created task for publishing: {:?}", task);
 
+        // Kept until the report arrives, so it can be sent again after a reconnect
+        delivery::track(correlation_id, &task.prompt);
         println!("🔧 About to publish via context.publish() to cluster...");
         match context.publish(analysis_topic.clone(), task).await {
             Ok(_) => {
                 println!(
-                    "✅ Successfully published query to analysis agent on topic: {:?}",
-                    analysis_topic
+                    "✅ [{}] Successfully published query to analysis agent on topic: {:?}",
+                    correlation_id, analysis_topic
+                );
+                telemetry::lifecycle(
+                    "publish_succeeded",
+                    topics::ANALYSIS_REQUESTS,
+                    Some(correlation_id),
+                    None,
                 );
                 println!("📡 Message should now be distributed to remote cluster nodes");
+                await_result(correlation_id);
 
                 // Add a small delay to ensure the message is sent
                 tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
-                Ok(serde_json::to_value(format!(
-                    "Analysis request submitted: '{}'. The analysis will be processed shortly.",
-                    typed_args.query
-                ))
-                .unwrap())
+                Ok(ToolResult::new(
+                    ToolStatus::Submitted,
+                    format!(
+                        "Analysis request submitted: '{}'. The analysis will be processed shortly.",
+                        typed_args.query
+                    ),
+                )
+                .with_data(serde_json::json!({ "correlation_id": correlation_id.to_string() }))
+                .into_value())
             }
             Err(e) => {
+                delivery::resolve(correlation_id);
                 eprintln!(
-                    "❌ Failed to publish to analysis agent on topic {:?}: {}",
-                    analysis_topic, e
+                    "❌ [{}] Failed to publish to analysis agent on topic {:?}: {}",
+                    correlation_id, analysis_topic, e
+                );
+                telemetry::lifecycle(
+                    "publish_failed",
+                    topics::ANALYSIS_REQUESTS,
+                    Some(correlation_id),
+                    None,
                 );
                 Err(ToolCallError::from(
                     Box::new(e) as Box<dyn std::error::Error + Send + Sync>
@@ -84,6 +298,254 @@ impl ToolRuntime for PublishTopicToAnalysis {
     }
 }
 
+// Tool for doctor to set which patient the session is about
+#[derive(Serialize, Deserialize, ToolInput, Debug)]
+pub struct SetPatientContextArgs {
+    #[input(description = "Identifier of the patient being discussed")]
+    patient_id: String,
+    #[input(description = "Patient age in years, if known")]
+    age: Option<u32>,
+    #[input(description = "Relevant clinical notes about the patient, if any")]
+    notes: Option<String>,
+}
+
+#[derive(Debug)]
+struct SetPatientContext {}
+
+#[async_trait]
+impl ToolRuntime for SetPatientContext {
+    async fn execute(&self, _context: &Context, args: Value) -> Result<Value, ToolCallError> {
+        guard_tool_call("set_patient_context")?;
+        let typed_args: SetPatientContextArgs = serde_json::from_value(args)?;
+        let context = PatientContext {
+            patient_id: typed_args.patient_id,
+            age: typed_args.age,
+            notes: typed_args.notes,
+        };
+        println!("🧑‍⚕️ Active patient set: {}", context.summary());
+        let summary = context.summary();
+        patient::set_active(context);
+
+        Ok(serde_json::to_value(format!("Active patient context set: {}", summary)).unwrap())
+    }
+}
+
+// Tool for doctor to have an ECG re-analyzed later, e.g. while monitoring a patient
+#[derive(Serialize, Deserialize, ToolInput, Debug)]
+pub struct ScheduleFollowupArgs {
+    #[input(
+        description = "The query to submit to the ECG analysis agent when the delay has passed"
+    )]
+    query: String,
+    #[input(description = "Seconds to wait before submitting the query")]
+    delay_secs: u64,
+}
+
+#[derive(Debug)]
+struct ScheduleFollowup {}
+
+#[async_trait]
+impl ToolRuntime for ScheduleFollowup {
+    async fn execute(&self, _context: &Context, args: Value) -> Result<Value, ToolCallError> {
+        guard_tool_call("schedule_followup")?;
+        let typed_args: ScheduleFollowupArgs = serde_json::from_value(args)?;
+        let Some(runtime) = FOLLOWUP_RUNTIME.lock().unwrap().clone() else {
+            return Ok(serde_json::to_value(
+                "Follow-ups can't be scheduled right now: the doctor node is not running.",
+            )
+            .unwrap());
+        };
+        let delay = Duration::from_secs(typed_args.delay_secs);
+        let (follow_up, token) = match followups::add(&typed_args.query, delay) {
+            Ok(scheduled) => scheduled,
+            Err(e) => {
+                return Ok(
+                    serde_json::to_value(format!("Follow-up not scheduled: {}.", e)).unwrap(),
+                );
+            }
+        };
+        println!("⏰ Scheduled follow-up {}", follow_up.describe());
+        arm_followup(runtime, &follow_up, token);
+
+        Ok(serde_json::to_value(format!(
+            "Follow-up #{} scheduled: '{}' will be sent to the analysis agent in {} seconds. It can be cancelled with cancel_followup and id {}.",
+            follow_up.id, follow_up.query, typed_args.delay_secs, follow_up.id
+        ))
+        .unwrap())
+    }
+}
+
+#[derive(Serialize, Deserialize, ToolInput, Debug)]
+pub struct CancelFollowupArgs {
+    #[input(
+        description = "Id of the follow-up to cancel; leave it out to list the pending follow-ups instead"
+    )]
+    id: Option<u64>,
+}
+
+#[derive(Debug)]
+struct CancelFollowup {}
+
+#[async_trait]
+impl ToolRuntime for CancelFollowup {
+    async fn execute(&self, _context: &Context, args: Value) -> Result<Value, ToolCallError> {
+        guard_tool_call("cancel_followup")?;
+        let typed_args: CancelFollowupArgs = serde_json::from_value(args)?;
+        let result = match typed_args.id {
+            Some(id) => match followups::cancel(id) {
+                Some(follow_up) => {
+                    println!("🗑️ Cancelled follow-up #{}", id);
+                    format!("Follow-up #{} cancelled: '{}'.", id, follow_up.query)
+                }
+                None => format!(
+                    "There is no pending follow-up #{}; it already ran or was cancelled.",
+                    id
+                ),
+            },
+            None => {
+                let pending = followups::pending();
+                if pending.is_empty() {
+                    "No follow-ups are scheduled.".to_string()
+                } else {
+                    let lines: Vec<String> = pending.iter().map(|f| f.describe()).collect();
+                    format!("Pending follow-ups:\n{}", lines.join("\n"))
+                }
+            }
+        };
+        Ok(serde_json::to_value(result).unwrap())
+    }
+}
+
+// Runtime used to publish follow-ups once they are due; tools only borrow their context
+static FOLLOWUP_RUNTIME: Mutex<Option<Arc<ClusterClientRuntime>>> = Mutex::new(None);
+
+// Start the follow-up's timer on the doctor runtime. Detached from the current query, so it
+// outlives the answer and a Stop; it dies with the runtime, so a restarted doctor arms the
+// pending follow-ups again. Only one timer can fire a job, since firing takes it off the book.
+fn arm_followup(
+    runtime: Arc<ClusterClientRuntime>,
+    follow_up: &followups::FollowUp,
+    token: CancellationToken,
+) {
+    let id = follow_up.id;
+    let delay = follow_up
+        .due_at
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = sleep(delay) => {}
+        }
+        let Some(follow_up) = followups::fired(id) else {
+            return;
+        };
+        publish_followup(&runtime, follow_up).await;
+    });
+}
+
+// Submit a due follow-up to the analysis agent and tell the user it went out
+async fn publish_followup(runtime: &ClusterClientRuntime, follow_up: followups::FollowUp) {
+    if !registry::is_online("analysis") {
+        eprintln!(
+            "⚠️ No analysis agent is online, follow-up #{} not sent",
+            follow_up.id
+        );
+        notify_gui(format!(
+            "⚠️ Follow-up #{} could not run: the ECG analysis service is offline.",
+            follow_up.id
+        ));
+        return;
+    }
+
+    let correlation_id = correlation::new_id();
+    println!(
+        "⏰ [{}] Follow-up #{} due, publishing: {}",
+        correlation_id, follow_up.id, follow_up.query
+    );
+    let task = Task::new(correlation::tag(
+        correlation_id,
+        &patient::attach(&follow_up.query),
+    ));
+    let analysis_topic = topics::topic(topics::ANALYSIS_REQUESTS);
+    delivery::track(correlation_id, &task.prompt);
+    match runtime.publish(&analysis_topic, task).await {
+        Ok(_) => {
+            telemetry::lifecycle(
+                "followup_published",
+                topics::ANALYSIS_REQUESTS,
+                Some(correlation_id),
+                None,
+            );
+            notify_gui(format!(
+                "⏰ Follow-up #{} sent for analysis: {}",
+                follow_up.id, follow_up.query
+            ));
+        }
+        Err(e) => {
+            delivery::resolve(correlation_id);
+            eprintln!("❌ Failed to publish follow-up #{}: {}", follow_up.id, e);
+            telemetry::lifecycle(
+                "followup_failed",
+                topics::ANALYSIS_REQUESTS,
+                Some(correlation_id),
+                None,
+            );
+            notify_gui(format!(
+                "❌ Follow-up #{} could not be sent for analysis.",
+                follow_up.id
+            ));
+        }
+    }
+}
+
+// A pending camera capture waiting for the user's approval in the GUI
+pub struct CameraConfirmRequest {
+    pub query: String,
+    pub reply: oneshot::Sender<bool>,
+}
+
+// Set with --confirm-camera; the GUI's dialog is then reached through the camera_confirm topic
+static CONFIRM_CAMERA: AtomicBool = AtomicBool::new(false);
+// Reply channels of the captures waiting on the dialog, by the id their topic message carries
+static CAMERA_CONFIRM_REPLIES: Mutex<BTreeMap<correlation::CorrelationId, oneshot::Sender<bool>>> =
+    Mutex::new(BTreeMap::new());
+const CAMERA_CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Ask the user to approve a capture; returns true when no confirmation is required
+async fn confirm_camera_capture(context: &Context, query: &str) -> bool {
+    if !CONFIRM_CAMERA.load(Ordering::Relaxed) {
+        return true;
+    }
+
+    let id = correlation::new_id();
+    let (reply_tx, reply_rx) = oneshot::channel();
+    CAMERA_CONFIRM_REPLIES.lock().unwrap().insert(id, reply_tx);
+    let request = Task::new(correlation::tag(id, query));
+    if let Err(e) = context
+        .publish(topics::topic(topics::CAMERA_CONFIRM), request)
+        .await
+    {
+        CAMERA_CONFIRM_REPLIES.lock().unwrap().remove(&id);
+        eprintln!(
+            "❌ Camera confirmation dialog unavailable, declining capture: {}",
+            e
+        );
+        return false;
+    }
+
+    println!("⏳ Waiting for user to approve camera capture...");
+    let approved = match tokio::time::timeout(CAMERA_CONFIRM_TIMEOUT, reply_rx).await {
+        Ok(Ok(approved)) => approved,
+        _ => {
+            println!("⌛ No camera confirmation received, declining capture");
+            false
+        }
+    };
+    CAMERA_CONFIRM_REPLIES.lock().unwrap().remove(&id);
+    approved
+}
+
 // Tool for doctor to request camera analysis
 #[derive(Serialize, Deserialize, ToolInput, Debug)]
 pub struct CameraAnalysisArgs {
@@ -91,45 +553,176 @@ pub struct CameraAnalysisArgs {
     query: String,
 }
 
-#[tool(
-    name = "camera_analysis",
-    description = "Request camera to capture and analyze an image based on user query",
-    input = CameraAnalysisArgs,
-)]
+// Set by --disable-camera at sites that forbid any camera use
+static CAMERA_DISABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn disable_camera() {
+    CAMERA_DISABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn camera_disabled() -> bool {
+    CAMERA_DISABLED.load(Ordering::Relaxed)
+}
+
+// #[agent] fixes the DoctorAgent's prompt at compile time, so with the camera disabled its
+// camera instructions are swapped for a note on the way to the LLM
+pub fn without_camera_instructions(prompt: &str) -> String {
+    let lines: Vec<&str> = prompt
+        .lines()
+        .filter(|line| !line.to_lowercase().contains("camera"))
+        .collect();
+    format!(
+        "{}\n\nCamera analysis is disabled by site policy: never plan a camera check, and tell the user the camera is unavailable here if they ask for one.",
+        lines.join("\n")
+    )
+}
+
+#[derive(Debug)]
 struct CameraAnalysisTool {}
 
+// Camera requests whose question is remembered; older ones are forgotten, their results then
+// just aren't shown as a reply
+const MAX_PENDING_CAMERA_REQUESTS: usize = 64;
+
+// User question behind each camera request, by correlation id, so the result can be shown in
+// the GUI as the reply to it
+static CAMERA_REQUEST_QUERIES: Mutex<VecDeque<(correlation::CorrelationId, String)>> =
+    Mutex::new(VecDeque::new());
+
+// The user question the doctor is currently answering
+static ACTIVE_QUERY: Mutex<Option<String>> = Mutex::new(None);
+
+// Analysis and camera requests the active query still waits for. While one is outstanding the
+// doctor's answer is only the "request submitted" step; the final answer comes after the result.
+static AWAITED_RESULTS: Mutex<Vec<correlation::CorrelationId>> = Mutex::new(Vec::new());
+
+fn await_result(id: correlation::CorrelationId) {
+    AWAITED_RESULTS.lock().unwrap().push(id);
+}
+
+fn result_arrived(id: correlation::CorrelationId) {
+    AWAITED_RESULTS
+        .lock()
+        .unwrap()
+        .retain(|awaited| *awaited != id);
+}
+
+fn awaiting_results() -> bool {
+    !AWAITED_RESULTS.lock().unwrap().is_empty()
+}
+
+fn remember_camera_request(id: correlation::CorrelationId, query: String) {
+    let mut pending = CAMERA_REQUEST_QUERIES.lock().unwrap();
+    if pending.len() >= MAX_PENDING_CAMERA_REQUESTS {
+        pending.pop_front();
+    }
+    pending.push_back((id, query));
+}
+
+// The question a camera result answers; each result is matched once
+fn take_camera_request(id: correlation::CorrelationId) -> Option<String> {
+    let mut pending = CAMERA_REQUEST_QUERIES.lock().unwrap();
+    let index = pending
+        .iter()
+        .position(|(pending_id, _)| *pending_id == id)?;
+    pending.remove(index).map(|(_, query)| query)
+}
+
 #[async_trait]
 impl ToolRuntime for CameraAnalysisTool {
     async fn execute(&self, context: &Context, args: Value) -> Result<Value, ToolCallError> {
         println!("📷 Tool call to request camera analysis");
+        guard_tool_call("camera_analysis")?;
         let typed_args: CameraAnalysisArgs = serde_json::from_value(args)?;
-        let camera_topic = Topic::<Task>::new("camera_requests");
+
+        if camera_disabled() {
+            println!("🚫 Camera analysis refused: the camera is disabled by policy");
+            return Ok(ToolResult::new(
+                ToolStatus::Unavailable,
+                "Camera analysis is disabled by policy at this site, so no image was taken. Do not retry; tell the user visual checks are unavailable.",
+            )
+            .into_value());
+        }
+
+        if !confirm_camera_capture(context, &typed_args.query).await {
+            println!("🚫 Camera capture declined by user");
+            return Ok(ToolResult::new(
+                ToolStatus::Declined,
+                "The user declined the camera capture. Do not retry; tell the user no image was taken.",
+            )
+            .into_value());
+        }
+
+        // Still publish: the camera may have joined before this node and not re-announced yet
+        let camera_registered = registry::has_capability("camera");
+        if !camera_registered {
+            println!("⚠️ No camera agent has announced itself; the request may go unanswered");
+        }
+
+        let camera_topic = topics::topic(topics::CAMERA_REQUESTS);
+        let correlation_id = correlation::new_id();
 
         println!(
-            "🚀 Publishing camera analysis request: {}",
-            typed_args.query
+            "🚀 [{}] Publishing camera analysis request: {}",
+            correlation_id, typed_args.query
         );
 
-        let task = Task::new(typed_args.query.clone());
+        // The patient context names the saved capture files
+        let task = Task::new(correlation::tag(
+            correlation_id,
+            &patient::attach(&typed_args.query),
+        ));
         println!("📦 Created camera analysis task: {:?}", task);
 
         println!("🔧 About to publish via context.publish() to cluster...");
         match context.publish(camera_topic.clone(), task).await {
             Ok(_) => {
                 println!(
-                    "✅ Successfully published camera analysis request to topic: {:?}",
-                    camera_topic
+                    "✅ [{}] Successfully published camera analysis request to topic: {:?}",
+                    correlation_id, camera_topic
                 );
-                Ok(serde_json::to_value(format!(
-                    "Camera analysis request submitted: {}",
-                    typed_args.query
-                ))
-                .unwrap())
+                telemetry::lifecycle(
+                    "publish_succeeded",
+                    topics::CAMERA_REQUESTS,
+                    Some(correlation_id),
+                    None,
+                );
+                // The user's own words read better in the GUI than the tool query
+                let question = ACTIVE_QUERY
+                    .lock()
+                    .unwrap()
+                    .clone()
+                    .unwrap_or_else(|| typed_args.query.clone());
+                remember_camera_request(correlation_id, question);
+                await_result(correlation_id);
+                let note = if camera_registered {
+                    ""
+                } else {
+                    " Note: no camera agent is registered in the cluster, so tell the user the analysis may not arrive."
+                };
+                Ok(ToolResult::new(
+                    ToolStatus::Submitted,
+                    format!(
+                        "Camera analysis request submitted: {}.{}",
+                        typed_args.query, note
+                    ),
+                )
+                .with_data(serde_json::json!({
+                    "correlation_id": correlation_id.to_string(),
+                    "camera_registered": camera_registered,
+                }))
+                .into_value())
             }
             Err(e) => {
                 eprintln!(
-                    "❌ Failed to publish camera analysis request on topic {:?}: {}",
-                    camera_topic, e
+                    "❌ [{}] Failed to publish camera analysis request on topic {:?}: {}",
+                    correlation_id, camera_topic, e
+                );
+                telemetry::lifecycle(
+                    "publish_failed",
+                    topics::CAMERA_REQUESTS,
+                    Some(correlation_id),
+                    None,
                 );
                 Err(ToolCallError::from(
                     Box::new(e) as Box<dyn std::error::Error + Send + Sync>
@@ -139,589 +732,2829 @@ impl ToolRuntime for CameraAnalysisTool {
     }
 }
 
-// Camera agent for image analysis
-#[agent(
-    name = "camera_agent",
-    description = "You are a Camera Analysis Agent that can capture and analyze images. When you receive a query, you should:
-    2. Analyze the image based on the specific query received
-
-    Always be thorough in your visual analysis and provide clear response."
-)]
-#[derive(Clone)]
-pub struct CameraAgent {}
-
-// Custom executor implementation for camera agent
-#[async_trait]
-impl AgentExecutor for CameraAgent {
-    type Output = String;
-    type Error = Error;
-
-    fn config(&self) -> ExecutorConfig {
-        ExecutorConfig::default()
-    }
+// Directory where AnalysisAgent stores every generated report and the doctor's report tools
+// read them, from --reports-dir. With the nodes on different machines it has to be shared
+// storage mounted on both.
+pub const DEFAULT_REPORTS_DIR: &str = "analysis_reports";
+static REPORTS_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
 
-    async fn execute(&self, task: &Task, context: Arc<Context>) -> Result<String, Error> {
-        let query = task.prompt.clone();
+pub fn set_reports_dir(dir: PathBuf) {
+    *REPORTS_DIR.lock().unwrap() = Some(dir);
+}
 
-        println!("📷 CameraAgent received query: {}", query);
+fn reports_dir() -> PathBuf {
+    REPORTS_DIR
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_REPORTS_DIR))
+}
+// Number of characters of each report returned as its summary
+const REPORT_SUMMARY_CHARS: usize = 600;
 
-        // Create images directory if it doesn't exist
-        let images_dir = "captured_images";
-        if !std::path::Path::new(images_dir).exists() {
-            std::fs::create_dir(images_dir).unwrap_or_else(|e| {
-                eprintln!("Failed to create images directory: {}", e);
-            });
-        }
+// Tool for doctor to look up previously generated analysis reports
+#[derive(Serialize, Deserialize, ToolInput, Debug)]
+pub struct FetchPastReportsArgs {
+    #[input(description = "Maximum number of most recent reports to return")]
+    limit: usize,
+    #[input(description = "Optional text that the report must contain (case-insensitive)")]
+    contains: Option<String>,
+}
 
-        // Generate unique filename with timestamp
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let output_path = format!("{}/medical_image_{}.jpg", images_dir, timestamp);
+#[derive(Debug)]
+struct FetchPastReports {}
 
-        println!("📷 Attempting to capture image...");
+#[async_trait]
+impl ToolRuntime for FetchPastReports {
+    async fn execute(&self, _context: &Context, args: Value) -> Result<Value, ToolCallError> {
+        println!("📚 Tool call to fetch past analysis reports");
+        guard_tool_call("fetch_past_reports")?;
+        let typed_args: FetchPastReportsArgs = serde_json::from_value(args)?;
+        let filter = typed_args.contains.map(|c| c.to_lowercase());
 
-        let mut capture_success = false;
+        let reports_dir = reports_dir();
+        let mut reports = match fs::read_dir(&reports_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let modified = entry.metadata().ok()?.modified().ok()?;
+                    Some((modified, entry.path()))
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                println!(
+                    "📚 No reports directory at {} (set --reports-dir to the analysis node's shared directory): {}",
+                    reports_dir.display(),
+                    e
+                );
+                return Ok(serde_json::to_value("No past analysis reports were found.").unwrap());
+            }
+        };
 
-        // Try imagesnap first (most reliable on macOS)
-        let imagesnap_result = Command::new("imagesnap")
-            .arg("-q") // Quiet mode
-            .arg(&output_path)
-            .output();
+        // Most recent first
+        reports.sort_by(|a, b| b.0.cmp(&a.0));
 
-        match imagesnap_result {
-            Ok(result) => {
-                if result.status.success() && fs::metadata(&output_path).is_ok() {
-                    println!("✅ Captured image with ImageSnap");
-                    capture_success = true;
-                } else {
-                    println!("❌ ImageSnap failed, trying FFmpeg...");
-                }
-            }
-            Err(_) => {
-                println!("❌ ImageSnap not available, trying FFmpeg...");
-            }
-        }
-
-        // Fallback: Try using ffmpeg if imagesnap failed
-        if !capture_success {
-            let ffmpeg_result = Command::new("ffmpeg")
-                .args(&[
-                    "-f",
-                    "avfoundation",
-                    "-video_size",
-                    "640x480",
-                    "-framerate",
-                    "30",
-                    "-i",
-                    "0", // Default camera
-                    "-vframes",
-                    "1",  // Capture only 1 frame
-                    "-y", // Overwrite output file
-                    &output_path,
-                ])
-                .output();
-
-            match ffmpeg_result {
-                Ok(result) => {
-                    if result.status.success() && fs::metadata(&output_path).is_ok() {
-                        println!("✅ Captured image with FFmpeg");
-                        capture_success = true;
-                    } else {
-                        println!("❌ FFmpeg failed");
-                    }
-                }
-                Err(_) => {
-                    println!("❌ FFmpeg not available");
+        let summaries = reports
+            .into_iter()
+            .filter_map(|(_, path)| {
+                let content = read_report(&path).ok()?;
+                if filter
+                    .as_ref()
+                    .is_some_and(|f| !content.to_lowercase().contains(f.as_str()))
+                {
+                    return None;
                 }
-            }
-        }
+                let name = path.file_name()?.to_string_lossy().to_string();
+                let summary: String = content.chars().take(REPORT_SUMMARY_CHARS).collect();
+                Some(serde_json::json!({ "report": name, "summary": summary }))
+            })
+            .take(typed_args.limit)
+            .collect::<Vec<_>>();
 
-        if !capture_success {
-            // Return error result if capture failed
-            return Ok("Camera capture failed - no image analysis available".to_string());
-        }
+        println!("📚 Returning {} past report(s)", summaries.len());
 
-        // Read the captured image into a buffer
-        let image_buffer = match fs::read(&output_path) {
-            Ok(buffer) => {
-                println!("📖 Image loaded successfully ({} KB)", buffer.len() / 1024);
-                buffer
-            }
-            Err(e) => {
-                println!("❌ Failed to read image file: {}", e);
-                return Ok("Image file could not be read".to_string());
-            }
-        };
+        if summaries.is_empty() {
+            return Ok(serde_json::to_value("No matching analysis reports were found.").unwrap());
+        }
+        Ok(Value::Array(summaries))
+    }
+}
 
-        println!("🤖 Sending image to AI for analysis...");
+// Tool for doctor to get a quick rhythm estimate from raw samples without spending an LLM call
+#[derive(Serialize, Deserialize, ToolInput, Debug)]
+pub struct ClassifyRhythmArgs {
+    #[input(
+        description = "Path to a file of ECG samples: numbers separated by whitespace, commas or newlines"
+    )]
+    path: String,
+    #[input(description = "Sampling rate of the samples in Hz (defaults to 250)")]
+    sample_rate_hz: Option<f64>,
+}
 
-        // Create chat messages for LLM
-        let messages = vec![
-            ChatMessage {
-                role: ChatRole::System,
-                message_type: MessageType::Text,
-                content: self.description().to_string(),
-            },
-            ChatMessage {
-                role: ChatRole::User,
-                message_type: MessageType::Image((
-                    autoagents::llm::chat::ImageMime::JPEG,
-                    image_buffer,
-                )),
-                content: format!(
-                    "Please analyze this medical image and respond to this query: {}. Provide detailed findings.",
-                    query
-                ),
-            },
-        ];
+#[derive(Debug)]
+struct ClassifyRhythm {}
 
-        // Call LLM directly with chat messages
-        match context.llm().chat(&messages, None, None).await {
-            Ok(response) => {
-                println!("✅ AI analysis completed");
-                let response_text = response.to_string();
-                println!("📋 Camera Analysis Result: {}", response_text);
-
-                // Publish the camera analysis result back to the doctor
-                let camera_response_topic = Topic::<Task>::new("camera_response");
-                let response_task =
-                    Task::new(format!("### Camera Analysis Result\n{}", response_text));
-
-                match context
-                    .publish(camera_response_topic.clone(), response_task)
-                    .await
-                {
-                    Ok(_) => {
-                        println!(
-                            "✅ Successfully published camera analysis to doctor topic: {:?}",
-                            camera_response_topic
-                        );
-                    }
-                    Err(e) => {
-                        eprintln!("❌ Failed to publish camera analysis to doctor: {}", e);
-                    }
-                }
+#[async_trait]
+impl ToolRuntime for ClassifyRhythm {
+    async fn execute(&self, _context: &Context, args: Value) -> Result<Value, ToolCallError> {
+        guard_tool_call("classify_rhythm")?;
+        let typed_args: ClassifyRhythmArgs = serde_json::from_value(args)?;
+        let sample_rate = typed_args
+            .sample_rate_hz
+            .unwrap_or(rhythm::DEFAULT_SAMPLE_RATE_HZ);
+        println!(
+            "💓 Tool call to classify rhythm from {} at {} Hz",
+            typed_args.path, sample_rate
+        );
 
-                Ok(response_text)
+        let result = fs::read_to_string(&typed_args.path)
+            .map_err(|e| format!("could not read {}: {}", typed_args.path, e))
+            .and_then(|content| rhythm::parse_samples(&content))
+            .and_then(|samples| rhythm::classify(&samples, sample_rate));
+        let answer = match result {
+            Ok(summary) => {
+                println!("💓 {}", summary.describe());
+                format!("## Rhythm Estimate\n{}", summary.describe())
             }
             Err(e) => {
-                println!("❌ LLM analysis failed: {}", e);
-                let error_msg = format!("AI analysis failed: {}", e);
+                println!("💓 Rhythm classification failed: {}", e);
+                format!("The rhythm could not be classified: {}", e)
+            }
+        };
+        Ok(serde_json::to_value(answer).unwrap())
+    }
+}
 
-                // Publish the error back to the doctor as well
-                let camera_response_topic = Topic::<Task>::new("camera_response");
-                let error_task = Task::new(format!("### Camera Analysis Error\n{}", error_msg));
+// Tool for doctor to compare two analysis reports, e.g. today's ECG against the last one
+#[derive(Serialize, Deserialize, ToolInput, Debug)]
+pub struct CompareAnalysesArgs {
+    #[input(
+        description = "The earlier analysis report: its full text, or a report file name returned by fetch_past_reports"
+    )]
+    report_a: String,
+    #[input(
+        description = "The later analysis report: its full text, or a report file name returned by fetch_past_reports"
+    )]
+    report_b: String,
+}
 
-                let _ = context.publish(camera_response_topic, error_task).await;
+#[derive(Debug)]
+struct CompareAnalyses {}
 
-                Ok(error_msg)
-            }
+// A report argument names a file in the reports directory or is the report text itself.
+// Only bare file names are looked up so the tool can't be pointed at arbitrary paths.
+fn resolve_report(value: &str) -> String {
+    let value = value.trim();
+    let is_file_name = std::path::Path::new(value)
+        .file_name()
+        .is_some_and(|name| name == value);
+    if is_file_name {
+        if let Ok(content) = read_report(&reports_dir().join(value)) {
+            return content;
         }
     }
+    value.to_string()
 }
 
-#[agent(
-    name = "doctor_agent",
-    description = "You are an expert ECG Doctor Agent using the ReAct (Reasoning + Acting) execution pattern. Your primary role is to help answer user queries about ECG analysis through systematic reasoning and tool usage.
+fn build_comparison_messages(report_a: &str, report_b: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: ChatRole::System,
+            message_type: MessageType::Text,
+            content: "You compare two ECG analysis reports for a clinician. Report A is the earlier one and report B the later one. Only use what the reports state.".to_string(),
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            message_type: MessageType::Text,
+            content: format!(
+                "Compare these reports and answer with exactly these markdown sections, each a bullet list (write 'None' if empty):
+### New findings
+### Resolved findings
+### Worsened findings
+### Improved or unchanged findings
+### Summary
 
-    ## Core Capabilities
-    You can:
+Report A:
+{}
+
+Report B:
+{}",
+                report_a, report_b
+            ),
+        },
+    ]
+}
+
+#[async_trait]
+impl ToolRuntime for CompareAnalyses {
+    async fn execute(&self, context: &Context, args: Value) -> Result<Value, ToolCallError> {
+        println!("🔀 Tool call to compare two analysis reports");
+        guard_tool_call("compare_analyses")?;
+        let typed_args: CompareAnalysesArgs = serde_json::from_value(args)?;
+        let report_a = resolve_report(&typed_args.report_a);
+        let report_b = resolve_report(&typed_args.report_b);
+
+        let messages = build_comparison_messages(&report_a, &report_b);
+        // The doctor's LLM counts this call in the usage summary itself
+        match context.llm().chat(&messages, None, None).await {
+            Ok(response) => {
+                let comparison = response.text().unwrap_or_default();
+                println!("🔀 Comparison ready ({} chars)", comparison.len());
+                Ok(serde_json::to_value(format!("## ECG Comparison\n{}", comparison)).unwrap())
+            }
+            Err(e) => {
+                eprintln!("❌ Report comparison failed: {}", e);
+                Err(ToolCallError::from(
+                    Box::new(e) as Box<dyn std::error::Error + Send + Sync>
+                ))
+            }
+        }
+    }
+}
+
+// Runs the vision LLM on images uploaded from the GUI - the camera path without the capture
+#[agent(
+    name = "uploaded_image_agent",
+    description = "You are a Medical Image Analysis Agent. You receive an image provided by the doctor (such as an ECG printout or a device screen) together with a query. Analyze the image based on the specific query received and provide a clear, thorough response."
+)]
+#[derive(Clone)]
+pub struct UploadedImageAgent {
+    settings: CameraSettings,
+}
+
+#[async_trait]
+impl AgentExecutor for UploadedImageAgent {
+    type Output = String;
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, context: Arc<Context>) -> Result<String, Error> {
+        let (correlation_id, payload) = correlation::split(&task.prompt);
+        let started = Instant::now();
+        telemetry::lifecycle(
+            "task_received",
+            topics::UPLOADED_IMAGE,
+            correlation_id,
+            None,
+        );
+
+        let upload = match UploadedImage::decode(payload) {
+            Ok(upload) => upload,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                return Ok(e);
+            }
+        };
+        println!(
+            "🖼️ [{}] Received uploaded image {} ({} KB) for query: {}",
+            correlation::display(correlation_id),
+            upload.file_name,
+            upload.bytes.len() / 1024,
+            upload.query
+        );
+
+        // Uploads can be any format, but the vision messages are always sent as JPEG
+        let image_buffer = imaging::to_jpeg(&upload.bytes, self.settings.max_image_dim)
+            .and_then(|buffer| imaging::fit_within_bytes(buffer, self.settings.max_image_bytes));
+        let image_buffer = match image_buffer {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                let error_msg = format!(
+                    "Uploaded image {} could not be prepared for analysis: {}",
+                    upload.file_name, e
+                );
+                let error_task = Task::new(correlation::tag_if_present(
+                    correlation_id,
+                    &format!("### Camera Analysis Error\n{}", error_msg),
+                ));
+                let _ = context
+                    .publish(topics::topic(topics::CAMERA_RESPONSE), error_task)
+                    .await;
+                return Ok(error_msg);
+            }
+        };
+
+        println!("🤖 Sending uploaded image to AI for analysis...");
+        let messages = build_camera_messages(self.description(), &upload.query, image_buffer);
+        analyze_and_publish(
+            &context,
+            &messages,
+            correlation_id,
+            topics::UPLOADED_IMAGE,
+            self.settings.result_topic,
+            started,
+            None,
+        )
+        .await
+    }
+}
+
+// Camera agent for image analysis
+#[agent(
+    name = "camera_agent",
+    description = "You are a Camera Analysis Agent that can capture and analyze images. When you receive a query, you should:
+    2. Analyze the image based on the specific query received
+
+    Always be thorough in your visual analysis and provide clear response."
+)]
+#[derive(Clone)]
+pub struct CameraAgent {
+    settings: CameraSettings,
+}
+
+// Capture attempts before giving up on a corrupt image
+const CAPTURE_ATTEMPTS: u32 = 2;
+
+// Tunables for the camera agent, taken from the Camera command flags
+#[derive(Debug, Clone)]
+pub struct CameraSettings {
+    pub max_image_dim: u32,
+    pub max_image_bytes: usize, // Bigger images are shrunk further or rejected before the LLM call
+    pub text_fallback: bool,    // Answer without an image when capture fails
+    pub capture_timeout: Duration,
+    pub capture_resolution: String, // "WxH" requested from ffmpeg; downscaled to max_image_dim afterwards
+    pub result_topic: &'static str, // camera_response (straight to the doctor) or analysis_agent
+}
+
+// Heading of a camera result sent to the analysis agent instead of the doctor
+const CAMERA_OBSERVATION_HEADING: &str = "### Camera Observation";
+
+// The task text for a finished camera analysis. Results bound for the analysis agent are
+// marked as an observation so it merges them with the ECG findings into one report.
+fn camera_result_prompt(result_topic: &str, response_text: &str) -> String {
+    if result_topic == topics::ANALYSIS_REQUESTS {
+        format!(
+            "{}\nVisual findings from the patient room camera. Combine them with the ECG analysis into a single report.\n\n{}",
+            CAMERA_OBSERVATION_HEADING, response_text
+        )
+    } else {
+        format!("### Camera Analysis Result\n{}", response_text)
+    }
+}
+
+// Build the vision chat for a captured image. The messages are provider agnostic: each
+// backend encodes the image bytes as base64 along with the media type from the ImageMime.
+fn build_camera_messages(
+    description: &str,
+    query: &str,
+    image_buffer: Vec<u8>,
+) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: ChatRole::System,
+            message_type: MessageType::Text,
+            content: description.to_string(),
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            message_type: MessageType::Image((ImageMime::JPEG, image_buffer)),
+            content: format!(
+                "Please analyze this medical image and respond to this query: {}. Provide detailed findings.",
+                query
+            ),
+        },
+    ]
+}
+
+// Text-only chat used when no image could be captured, so the LLM can still answer with a caveat
+fn build_text_fallback_messages(description: &str, query: &str) -> Vec<ChatMessage> {
+    vec![
+        ChatMessage {
+            role: ChatRole::System,
+            message_type: MessageType::Text,
+            content: description.to_string(),
+        },
+        ChatMessage {
+            role: ChatRole::User,
+            message_type: MessageType::Text,
+            content: format!(
+                "Camera capture was unavailable, so no image could be taken. Respond to this query as best you can without an image, and state clearly that no visual examination was possible: {}",
+                query
+            ),
+        },
+    ]
+}
+
+// Longest query slug in a capture file name
+const CAPTURE_SLUG_CHARS: usize = 40;
+
+// Lowercase ASCII letters and digits, with every other run of characters turned into one '-'
+fn file_slug(text: &str, max_chars: usize) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= max_chars {
+            break;
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+// cap_<patient>_<query slug>_<unix secs>, so captured_images/ tells which patient and
+// question each image belongs to
+fn capture_file_stem(patient_id: Option<&str>, query: &str, timestamp: u64) -> String {
+    let patient = patient_id
+        .map(|id| file_slug(id, CAPTURE_SLUG_CHARS))
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| "nopatient".to_string());
+    let query = file_slug(query, CAPTURE_SLUG_CHARS);
+    let query = if query.is_empty() { "query" } else { &query };
+    format!("cap_{}_{}_{}", patient, query, timestamp)
+}
+
+// Written next to each capture as <stem>.json for later audit
+#[derive(Serialize, Debug)]
+struct CaptureSidecar<'a> {
+    image: &'a str,
+    query: &'a str,
+    patient: Option<&'a str>, // Full patient context line, when the doctor set one
+    timestamp: u64,
+    correlation_id: Option<String>,
+    result: Option<&'a str>, // The analysis, or the error the doctor was shown
+}
+
+fn write_capture_sidecar(image_path: &str, sidecar: &CaptureSidecar) {
+    let path = Path::new(image_path).with_extension("json");
+    let written = serde_json::to_string_pretty(sidecar)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        println!(
+            "⚠️ Could not write capture record {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// Result of a single capture attempt
+#[derive(Debug, PartialEq)]
+enum CaptureOutcome {
+    Captured,
+    Failed,
+    TimedOut,
+    PermissionDenied, // The OS refused camera access to this process
+}
+
+// Phrases imagesnap and ffmpeg (AVFoundation) print when macOS hasn't granted camera access
+const PERMISSION_DENIED_MARKERS: &[&str] = &[
+    "not authorized",
+    "not permitted",
+    "permission denied",
+    "access denied",
+    "authorization status",
+];
+
+fn is_permission_denied(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr).to_lowercase();
+    PERMISSION_DENIED_MARKERS
+        .iter()
+        .any(|marker| stderr.contains(marker))
+}
+
+// Run a capture command, killing it if the camera doesn't answer in time (e.g. the device is
+// held by another process). Returns None on timeout.
+async fn run_capture_command(
+    mut command: Command,
+    limit: Duration,
+) -> Option<std::io::Result<Output>> {
+    command.kill_on_drop(true);
+    tokio::time::timeout(limit, command.output()).await.ok()
+}
+
+// Grab a single frame into `output_path`, trying imagesnap first and then ffmpeg
+async fn capture_frame(output_path: &str, resolution: &str, limit: Duration) -> CaptureOutcome {
+    // Try imagesnap first (most reliable on macOS). It has no size option and always captures
+    // at the camera's native resolution; the frame is downscaled before it reaches the LLM.
+    let mut imagesnap = Command::new("imagesnap");
+    imagesnap.arg("-q").arg(output_path); // Quiet mode
+    match run_capture_command(imagesnap, limit).await {
+        None => {
+            println!("⏰ ImageSnap timed out after {:?}", limit);
+            return CaptureOutcome::TimedOut;
+        }
+        Some(Ok(result)) => {
+            if result.status.success() && fs::metadata(output_path).is_ok() {
+                println!("✅ Captured image with ImageSnap");
+                return CaptureOutcome::Captured;
+            }
+            // FFmpeg runs under the same app permissions, so there's no point trying it
+            if is_permission_denied(&result.stderr) {
+                println!("🚫 ImageSnap was denied camera access");
+                return CaptureOutcome::PermissionDenied;
+            }
+            println!("❌ ImageSnap failed, trying FFmpeg...");
+        }
+        Some(Err(_)) => {
+            println!("❌ ImageSnap not available, trying FFmpeg...");
+        }
+    }
+
+    // Fallback: Try using ffmpeg if imagesnap failed
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg.args([
+        "-f",
+        "avfoundation",
+        "-video_size",
+        resolution,
+        "-framerate",
+        "30",
+        "-i",
+        "0", // Default camera
+        "-vframes",
+        "1",  // Capture only 1 frame
+        "-y", // Overwrite output file
+        output_path,
+    ]);
+    match run_capture_command(ffmpeg, limit).await {
+        None => {
+            println!("⏰ FFmpeg timed out after {:?}", limit);
+            CaptureOutcome::TimedOut
+        }
+        Some(Ok(result)) if result.status.success() && fs::metadata(output_path).is_ok() => {
+            println!("✅ Captured image with FFmpeg");
+            CaptureOutcome::Captured
+        }
+        Some(Ok(result)) if is_permission_denied(&result.stderr) => {
+            println!("🚫 FFmpeg was denied camera access");
+            CaptureOutcome::PermissionDenied
+        }
+        Some(Ok(_)) => {
+            println!("❌ FFmpeg failed");
+            CaptureOutcome::Failed
+        }
+        Some(Err(_)) => {
+            println!("❌ FFmpeg not available");
+            CaptureOutcome::Failed
+        }
+    }
+}
+
+// Earlier camera turns from the agent's sliding-window memory. Only text is kept there: each
+// turn names the image file it was about, and the images themselves stay on disk.
+async fn camera_history(context: &Context) -> Vec<ChatMessage> {
+    let Some(memory) = context.memory() else {
+        return Vec::new();
+    };
+    let history = memory.read().await.recall("", None).await;
+    history.unwrap_or_else(|e| {
+        println!("⚠️ Could not read camera memory: {}", e);
+        Vec::new()
+    })
+}
+
+async fn remember_camera_turn(context: &Context, turn: &str, response_text: &str) {
+    let Some(memory) = context.memory() else {
+        return;
+    };
+    let mut memory = memory.write().await;
+    for message in [
+        ChatMessage {
+            role: ChatRole::User,
+            message_type: MessageType::Text,
+            content: turn.to_string(),
+        },
+        ChatMessage {
+            role: ChatRole::Assistant,
+            message_type: MessageType::Text,
+            content: response_text.to_string(),
+        },
+    ] {
+        if let Err(e) = memory.remember(&message).await {
+            println!("⚠️ Could not update camera memory: {}", e);
+        }
+    }
+}
+
+// Put earlier turns between the system prompt and the new query, so "compare to the last
+// photo" has something to refer to
+fn with_history(mut messages: Vec<ChatMessage>, history: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let at = usize::from(matches!(
+        messages.first().map(|m| &m.role),
+        Some(ChatRole::System)
+    ));
+    messages.splice(at..at, history);
+    messages
+}
+
+// Run the vision chat and publish the result to `result_topic` (the error always goes to the
+// doctor on camera_response). Shared by live captures and uploaded images; `memory_turn` is
+// what to remember of the query when the analysis succeeds.
+async fn analyze_and_publish(
+    context: &Arc<Context>,
+    messages: &[ChatMessage],
+    correlation_id: Option<correlation::CorrelationId>,
+    source_topic: &str,
+    result_topic: &'static str,
+    started: Instant,
+    memory_turn: Option<String>,
+) -> Result<String, Error> {
+    // Call LLM directly with chat messages
+    match chat_with_retry(context.llm(), messages, None, "camera").await {
+        Ok(response_text) => {
+            println!("✅ AI analysis completed");
+            if let Some(turn) = memory_turn {
+                remember_camera_turn(context, &turn, &response_text).await;
+            }
+            telemetry::lifecycle(
+                "analysis_complete",
+                source_topic,
+                correlation_id,
+                Some(started.elapsed().as_millis()),
+            );
+            println!("📋 Camera Analysis Result: {}", response_text);
+
+            // Publish the camera analysis result to the doctor, or to the analysis agent
+            let camera_response_topic = topics::topic(result_topic);
+            let response_task = Task::new(correlation::tag_if_present(
+                correlation_id,
+                &camera_result_prompt(result_topic, &response_text),
+            ));
+
+            match context
+                .publish(camera_response_topic.clone(), response_task)
+                .await
+            {
+                Ok(_) => {
+                    println!(
+                        "✅ [{}] Successfully published camera analysis to topic: {:?}",
+                        correlation::display(correlation_id),
+                        camera_response_topic
+                    );
+                    telemetry::lifecycle("publish_succeeded", result_topic, correlation_id, None);
+                }
+                Err(e) => {
+                    eprintln!(
+                        "❌ Failed to publish camera analysis to {}: {}",
+                        result_topic, e
+                    );
+                    telemetry::lifecycle("publish_failed", result_topic, correlation_id, None);
+                }
+            }
+
+            Ok(response_text)
+        }
+        Err(e) => {
+            println!("❌ LLM analysis failed: {}", e);
+            let error_msg = format!("AI analysis failed: {}", e);
+
+            // Publish the error back to the doctor as well
+            let camera_response_topic = topics::topic(topics::CAMERA_RESPONSE);
+            let error_task = Task::new(correlation::tag_if_present(
+                correlation_id,
+                &format!("### Camera Analysis Error\n{}", error_msg),
+            ));
+
+            let _ = context.publish(camera_response_topic, error_task).await;
+
+            Ok(error_msg)
+        }
+    }
+}
+
+// An image the doctor uploaded from the GUI, sent to the camera node on the uploaded_image
+// topic. Tasks only carry text, so the bytes travel base64 encoded inside a JSON prompt.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UploadedImage {
+    pub query: String,
+    pub file_name: String,
+    #[serde(with = "base64_bytes")]
+    pub bytes: Vec<u8>,
+}
+
+impl UploadedImage {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn decode(prompt: &str) -> Result<Self, String> {
+        serde_json::from_str(prompt).map_err(|e| format!("invalid uploaded image payload: {}", e))
+    }
+}
+
+// PNG plot of the ECG samples behind an analysis (--render-waveform), for the doctor GUI
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WaveformImage {
+    pub query: String,
+    #[serde(with = "base64_bytes")]
+    pub png: Vec<u8>,
+}
+
+impl WaveformImage {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn decode(prompt: &str) -> Result<Self, String> {
+        serde_json::from_str(prompt).map_err(|e| format!("invalid waveform payload: {}", e))
+    }
+}
+
+mod base64_bytes {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+// Custom executor implementation for camera agent
+#[async_trait]
+impl AgentExecutor for CameraAgent {
+    type Output = String;
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, context: Arc<Context>) -> Result<String, Error> {
+        let (correlation_id, payload) = correlation::split(&task.prompt);
+        let (patient_context, query) = patient::detach(payload);
+        let query = query.to_string();
+
+        println!(
+            "📷 [{}] CameraAgent received query: {}",
+            correlation::display(correlation_id),
+            query
+        );
+        telemetry::lifecycle(
+            "task_received",
+            topics::CAMERA_REQUESTS,
+            correlation_id,
+            None,
+        );
+        let started = Instant::now();
+
+        // Create images directory if it doesn't exist
+        let images_dir = "captured_images";
+        if !std::path::Path::new(images_dir).exists() {
+            std::fs::create_dir(images_dir).unwrap_or_else(|e| {
+                eprintln!("Failed to create images directory: {}", e);
+            });
+        }
+
+        // Name the file after the patient, the query and the time
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let output_path = format!(
+            "{}/{}.jpg",
+            images_dir,
+            capture_file_stem(
+                patient_context.and_then(patient::id_from_summary),
+                &query,
+                timestamp
+            )
+        );
+
+        println!("📷 Attempting to capture image...");
+
+        // An interrupted capture can leave a truncated file behind, so validate the image and retry once.
+        // The first corrupt capture is what gets reported, even when the retry then fails.
+        let mut capture_success = false;
+        let mut capture_error = None;
+        for attempt in 1..=CAPTURE_ATTEMPTS {
+            match capture_frame(
+                &output_path,
+                &self.settings.capture_resolution,
+                self.settings.capture_timeout,
+            )
+            .await
+            {
+                CaptureOutcome::Captured => {}
+                CaptureOutcome::Failed => break,
+                CaptureOutcome::PermissionDenied => {
+                    // Not worth a text fallback: the doctor needs to fix the setting and retry
+                    let error_msg = "Camera access denied — grant permission to this terminal in System Settings > Privacy & Security > Camera, then restart the camera agent".to_string();
+                    let error_task = Task::new(correlation::tag_if_present(
+                        correlation_id,
+                        &format!("### Camera Analysis Error\n{}", error_msg),
+                    ));
+                    let _ = context
+                        .publish(topics::topic(topics::CAMERA_RESPONSE), error_task)
+                        .await;
+                    return Ok(error_msg);
+                }
+                CaptureOutcome::TimedOut => {
+                    let error_msg = format!(
+                        "Camera busy or timed out after {}s - no image could be captured",
+                        self.settings.capture_timeout.as_secs()
+                    );
+                    let error_task = Task::new(correlation::tag_if_present(
+                        correlation_id,
+                        &format!("### Camera Analysis Error\n{}", error_msg),
+                    ));
+                    let _ = context
+                        .publish(topics::topic(topics::CAMERA_RESPONSE), error_task)
+                        .await;
+                    return Ok(error_msg);
+                }
+            }
+            match imaging::validate_image_file(&output_path) {
+                Ok((width, height)) => {
+                    println!("✅ Captured image is valid ({}x{})", width, height);
+                    capture_success = true;
+                    capture_error = None;
+                    break;
+                }
+                Err(e) => {
+                    println!(
+                        "❌ Captured image is corrupt (attempt {}/{}): {}",
+                        attempt, CAPTURE_ATTEMPTS, e
+                    );
+                    let _ = fs::remove_file(&output_path);
+                    capture_error.get_or_insert(e);
+                }
+            }
+        }
+
+        // Without the text fallback the doctor is told, like for any other failed capture
+        if let Some(e) = capture_error.filter(|_| !self.settings.text_fallback) {
+            let error_msg = format!(
+                "Camera capture produced a corrupt image after {} attempts: {}",
+                CAPTURE_ATTEMPTS, e
+            );
+            let error_task = Task::new(correlation::tag_if_present(
+                correlation_id,
+                &format!("### Camera Analysis Error\n{}", error_msg),
+            ));
+            let _ = context
+                .publish(topics::topic(topics::CAMERA_RESPONSE), error_task)
+                .await;
+            return Ok(error_msg);
+        }
+
+        let history = camera_history(&context).await;
+        let file_name = Path::new(&output_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (messages, memory_turn) = if capture_success {
+            // Read the captured image into a buffer
+            let image_buffer = match fs::read(&output_path) {
+                Ok(buffer) => {
+                    println!("📖 Image loaded successfully ({} KB)", buffer.len() / 1024);
+                    buffer
+                }
+                Err(e) => {
+                    println!("❌ Failed to read image file: {}", e);
+                    return Ok("Image file could not be read".to_string());
+                }
+            };
+
+            // Keep the LLM payload bounded regardless of the capture size
+            let image_buffer = imaging::downscale_jpeg(image_buffer, self.settings.max_image_dim);
+            let image_buffer = match imaging::fit_within_bytes(
+                image_buffer,
+                self.settings.max_image_bytes,
+            ) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    println!("❌ Captured image is too large to send: {}", e);
+                    let error_msg = format!(
+                        "The captured image is too large to send for analysis ({}). Try a lower --capture-resolution or --max-image-dim.",
+                        e
+                    );
+                    let error_task = Task::new(correlation::tag_if_present(
+                        correlation_id,
+                        &format!("### Camera Analysis Error\n{}", error_msg),
+                    ));
+                    let _ = context
+                        .publish(topics::topic(topics::CAMERA_RESPONSE), error_task)
+                        .await;
+                    return Ok(error_msg);
+                }
+            };
+
+            println!("🤖 Sending image to AI for analysis...");
+
+            // Create chat messages for LLM - the selected backend handles base64 encoding and media type
+            (
+                build_camera_messages(self.description(), &query, image_buffer),
+                format!("[Captured image {}] {}", file_name, query),
+            )
+        } else if self.settings.text_fallback {
+            println!("📝 No camera available, answering from the query alone...");
+            (
+                build_text_fallback_messages(self.description(), &query),
+                format!("[No image captured] {}", query),
+            )
+        } else {
+            let error_msg = "Camera capture failed - no image analysis available".to_string();
+            let error_task = Task::new(correlation::tag_if_present(
+                correlation_id,
+                &format!("### Camera Analysis Error\n{}", error_msg),
+            ));
+            let _ = context
+                .publish(topics::topic(topics::CAMERA_RESPONSE), error_task)
+                .await;
+            return Ok(error_msg);
+        };
+
+        let result = analyze_and_publish(
+            &context,
+            &with_history(messages, history),
+            correlation_id,
+            topics::CAMERA_REQUESTS,
+            self.settings.result_topic,
+            started,
+            Some(memory_turn),
+        )
+        .await?;
+
+        if capture_success {
+            write_capture_sidecar(
+                &output_path,
+                &CaptureSidecar {
+                    image: &file_name,
+                    query: &query,
+                    patient: patient_context,
+                    timestamp,
+                    correlation_id: correlation_id.map(|id| id.to_string()),
+                    result: Some(&result),
+                },
+            );
+        }
+        Ok(result)
+    }
+}
+
+#[agent(
+    name = "doctor_agent",
+    description = "You are an expert ECG Doctor Agent using the ReAct (Reasoning + Acting) execution pattern. Your primary role is to help answer user queries about ECG analysis through systematic reasoning and tool usage.
+
+    ## Core Capabilities
+    You can:
     - Ask Analysis Agent to analyze ECG data using the ecg_analysis tool
     - Interpret analysis results and provide medical recommendations
     - You can check the patient room using the camerate tool to answer questions about the asked query
+    - Look up previous analysis reports using the fetch_past_reports tool when asked about earlier results
+    - Compare two analysis reports with the compare_analyses tool when asked whether an ECG changed
+    - Get an instant local heart rate and rhythm estimate from an ECG sample file with the classify_rhythm tool
+    - Set the active patient with the set_patient_context tool when the user says which patient they are asking about
+    - Schedule a later re-analysis with the schedule_followup tool when the user wants an ECG checked again in some minutes, and cancel or list scheduled follow-ups with the cancel_followup tool
     - Respond directly to users with analysis findings
 
     ## CRITICAL LOOP PREVENTION LOGIC
-    **IMPORTANT**: If you receive a message that:
-    - Starts with '###' or contains 'Analysis Report'
+    **IMPORTANT**: Results from other agents begin with a tag like '[correlation_id=...]'; ignore the tag when reading them. If you receive a message that:
+    - Starts with '###' (right after that tag) or contains 'Analysis Report'
     - Contains 'Key Insights', 'Strategic Recommendations', or 'Actionable Next Steps'
     - Appears to be analysis results from another agent
 
-    Then you should:
-    1. **DO NOT** use the ecg_analysis_tool again
-    2. **DIRECTLY RESPOND** to the user with the analysis results
-    3. **PROVIDE** your medical interpretation of the findings
-    4. **FORMAT** the response for the patient in a clear, understandable manner
+    Then you should:
+    1. **DO NOT** use the ecg_analysis_tool again
+    2. **DIRECTLY RESPOND** to the user with the analysis results
+    3. **PROVIDE** your medical interpretation of the findings
+    4. **FORMAT** the response for the patient in a clear, understandable manner
+
+    ## ReAct Execution Pattern
+    As a ReAct agent, you follow this pattern for NEW user queries:
+    1. **Thought**: Analyze what needs to be done and plan your approach
+    2. **Action**: Use appropriate tools to gather information (ONLY for new user queries)
+    3. **Observation**: Process the results from your tools
+    4. **Repeat**: Continue until task is complete
+
+    For ANALYSIS RESPONSES: Skip tools, respond directly to user.
+
+    Remember: Distinguish between new user queries (use tools) and analysis responses (respond directly).",
+    tools = [
+        PublishTopicToAnalysis,
+        CameraAnalysisTool,
+        FetchPastReports,
+        CompareAnalyses,
+        ClassifyRhythm,
+        SetPatientContext,
+        ScheduleFollowup,
+        CancelFollowup
+    ],
+)]
+#[derive(Clone)]
+pub struct DoctorAgent {
+    max_turns: usize, // ReAct iterations allowed per query, from --max-turns
+}
+
+#[agent(
+    name = "analysis_agent",
+    description = "You are an analysis agent that receives a query related to the ecg reading and you must provide a recommendation based on the data. When the query is a camera observation, combine the visual findings with the ECG data into one report.",
+    tools = [],
+)]
+pub struct AnalysisAgent {
+    emit_raw_json: bool, // Also publish the report as JSON for downstream integrations
+    concurrency: Option<Arc<Semaphore>>, // Set when more than one analysis may run at once
+    concurrency_limit: usize,
+    task_timeout: Duration, // An analysis still running after this is aborted
+}
+
+// Used where no --task-timeout-secs is given (one-shot analyses, the all-in-one demo)
+pub const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(300);
+
+impl AnalysisAgent {
+    fn new(emit_raw_json: bool, concurrency: usize, task_timeout: Duration) -> Self {
+        Self {
+            emit_raw_json,
+            concurrency: (concurrency > 1).then(|| Arc::new(Semaphore::new(concurrency))),
+            concurrency_limit: concurrency,
+            task_timeout,
+        }
+    }
+}
+
+impl ReActExecutor for DoctorAgent {
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig {
+            max_turns: self.max_turns,
+        }
+    }
+}
+
+// Lightweight agent on the doctor node that records heartbeats in the registry, which the GUI
+// status panel and the doctor's tools read
+#[agent(
+    name = "roster_agent",
+    description = "Records which cluster agents are online from their heartbeats.",
+    tools = [],
+)]
+#[derive(Clone)]
+pub struct RosterAgent {}
+
+#[async_trait]
+impl AgentExecutor for RosterAgent {
+    type Output = ();
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, context: Arc<Context>) -> Result<(), Error> {
+        // Heartbeats never reach the LLM, they only feed the liveness check
+        let came_online = registry::record_heartbeat(&task.prompt);
+
+        // The first analysis heartbeat, or the first after a gap, means requests published
+        // meanwhile may have been lost
+        if came_online && task.prompt == "analysis" {
+            redeliver_analysis_requests(&context).await;
+        }
+        Ok(())
+    }
+}
+
+// Publish the analysis requests that never got a report again. The analysis node skips the
+// ones it already has, so a request that wasn't actually lost is not analyzed twice.
+async fn redeliver_analysis_requests(context: &Context) {
+    let (again, dropped) = delivery::redeliver();
+    for request in dropped {
+        let (_, query) = correlation::split(&request.prompt);
+        let (_, query) = patient::detach(query);
+        eprintln!(
+            "❌ Giving up on analysis request after {} attempt(s): {}",
+            request.attempts, query
+        );
+        notify_gui(format!(
+            "⚠️ No analysis report arrived for '{}'. Please ask again.",
+            query
+        ));
+    }
+    if again.is_empty() {
+        return;
+    }
+    println!(
+        "🔁 Analysis agent connected, re-publishing {} unanswered request(s)",
+        again.len()
+    );
+    for prompt in again {
+        let correlation_id = correlation::split(&prompt).0;
+        match context
+            .publish(
+                topics::topic(topics::ANALYSIS_REQUESTS),
+                Task::new(prompt.clone()),
+            )
+            .await
+        {
+            Ok(_) => telemetry::lifecycle(
+                "publish_redelivered",
+                topics::ANALYSIS_REQUESTS,
+                correlation_id,
+                None,
+            ),
+            Err(e) => eprintln!(
+                "❌ [{}] Failed to re-publish analysis request: {}",
+                correlation::display(correlation_id),
+                e
+            ),
+        }
+    }
+}
+
+// Passes the doctor's "started on this query" receipts to the GUI
+#[agent(
+    name = "ack_agent",
+    description = "Forwards receipts for user queries the doctor started handling.",
+    tools = [],
+)]
+#[derive(Clone)]
+pub struct AckAgent {
+    ack_tx: mpsc::UnboundedSender<String>,
+}
+
+#[async_trait]
+impl AgentExecutor for AckAgent {
+    type Output = ();
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, _context: Arc<Context>) -> Result<(), Error> {
+        // Receipts never reach the LLM either
+        let _ = self.ack_tx.send(task.prompt.clone());
+        Ok(())
+    }
+}
+
+// Hears from the analysis node that a re-sent request is still running, so the re-send
+// doesn't count as a failed delivery attempt
+#[agent(
+    name = "progress_agent",
+    description = "Records analysis requests the analysis agent is still working on.",
+    tools = [],
+)]
+#[derive(Clone)]
+pub struct ProgressAgent {}
+
+#[async_trait]
+impl AgentExecutor for ProgressAgent {
+    type Output = ();
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, _context: Arc<Context>) -> Result<(), Error> {
+        if let Some(id) = correlation::split(&task.prompt).0 {
+            println!("⏳ [{}] Analysis still running", id);
+            delivery::still_running(id);
+        }
+        Ok(())
+    }
+}
+
+// Shows the camera_confirm requests of CameraAnalysisTool in the GUI's dialog, with the reply
+// channel the tool is waiting on (--confirm-camera)
+#[agent(
+    name = "camera_confirm_agent",
+    description = "Asks the user to approve camera captures.",
+    tools = [],
+)]
+#[derive(Clone)]
+pub struct CameraConfirmAgent {
+    confirm_tx: mpsc::UnboundedSender<CameraConfirmRequest>,
+}
+
+#[async_trait]
+impl AgentExecutor for CameraConfirmAgent {
+    type Output = ();
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, _context: Arc<Context>) -> Result<(), Error> {
+        let (Some(id), query) = correlation::split(&task.prompt) else {
+            return Ok(());
+        };
+        // Gone when the tool already gave up waiting
+        let Some(reply) = CAMERA_CONFIRM_REPLIES.lock().unwrap().remove(&id) else {
+            return Ok(());
+        };
+        let request = CameraConfirmRequest {
+            query: query.to_string(),
+            reply,
+        };
+        if self.confirm_tx.send(request).is_err() {
+            // Dropping the reply channel declines the capture
+            eprintln!("❌ Camera confirmation dialog unavailable, declining capture");
+        }
+        Ok(())
+    }
+}
+
+// Passes ECG waveform plots from the analysis node to the GUI
+#[agent(
+    name = "waveform_agent",
+    description = "Forwards ECG waveform plots rendered by the analysis agent.",
+    tools = [],
+)]
+#[derive(Clone)]
+pub struct WaveformAgent {
+    response_tx: mpsc::UnboundedSender<AgentResponse>, // The gate, like the agent's own answers
+}
+
+#[async_trait]
+impl AgentExecutor for WaveformAgent {
+    type Output = ();
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, _context: Arc<Context>) -> Result<(), Error> {
+        // The LLM can't use the image, so it goes to the GUI through the response gate, which
+        // drops it when the request it belongs to was cancelled
+        let (correlation_id, payload) = correlation::split(&task.prompt);
+        match WaveformImage::decode(payload) {
+            Ok(waveform) => {
+                println!(
+                    "📈 [{}] Forwarding ECG waveform for: {}",
+                    correlation::display(correlation_id),
+                    waveform.query
+                );
+                let response = AgentResponse {
+                    image: Some(waveform.png),
+                    ..AgentResponse::notice(format!("📈 ECG waveform for '{}'", waveform.query))
+                };
+                let _ = self.response_tx.send(response);
+            }
+            Err(e) => eprintln!("❌ Ignoring waveform: {}", e),
+        }
+        Ok(())
+    }
+}
+
+// Records announcements from the other nodes so the doctor's tools know what is available
+#[agent(
+    name = "registry_agent",
+    description = "Records the capabilities cluster agents announce when they join.",
+    tools = [],
+)]
+#[derive(Clone)]
+pub struct RegistryAgent {}
+
+#[async_trait]
+impl AgentExecutor for RegistryAgent {
+    type Output = ();
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig::default()
+    }
+
+    async fn execute(&self, task: &Task, _context: Arc<Context>) -> Result<(), Error> {
+        // Like heartbeats, announcements never reach the LLM
+        registry::record_payload(&task.prompt);
+        Ok(())
+    }
+}
+
+// Nodes re-announce at this interval so nodes that join later still learn about them
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Publish this node's announcement on startup and then periodically
+pub(crate) fn spawn_announcer(runtime: Arc<ClusterClientRuntime>, announcement: Announcement) {
+    tokio::spawn(async move {
+        let announce_topic = topics::topic(topics::AGENT_ANNOUNCE);
+        println!("🧭 Announcing {}", announcement.summary());
+        loop {
+            if let Err(e) = runtime
+                .publish(&announce_topic, Task::new(announcement.encode()))
+                .await
+            {
+                eprintln!("❌ Failed to announce {}: {}", announcement.name, e);
+            }
+            sleep(ANNOUNCE_INTERVAL).await;
+        }
+    });
+}
+
+// Periodically announce this node's agent kind so the doctor GUI can show it as online
+pub(crate) fn spawn_heartbeat(runtime: Arc<ClusterClientRuntime>, agent_kind: &'static str) {
+    tokio::spawn(async move {
+        let heartbeat_topic = topics::topic(topics::HEARTBEAT);
+        loop {
+            if let Err(e) = runtime
+                .publish(&heartbeat_topic, Task::new(agent_kind.to_string()))
+                .await
+            {
+                eprintln!("❌ Failed to publish {} heartbeat: {}", agent_kind, e);
+            }
+            sleep(registry::HEARTBEAT_INTERVAL).await;
+        }
+    });
+}
+
+#[async_trait]
+impl AgentExecutor for AnalysisAgent {
+    type Output = String;
+    type Error = Error;
+
+    fn config(&self) -> ExecutorConfig {
+        ExecutorConfig { max_turns: 10 }
+    }
+
+    async fn execute(
+        &self,
+        task: &Task,
+        context: Arc<Context>,
+    ) -> Result<Self::Output, Self::Error> {
+        println!("🧠 [AnalysisAgent] *** EXECUTE METHOD CALLED ***");
+        let (correlation_id, query) = correlation::split(&task.prompt);
+        println!(
+            "🧠 [AnalysisAgent] [{}] Received research data for analysis: {}",
+            correlation::display(correlation_id),
+            query
+        );
+        println!("🧠 [AnalysisAgent] Task details: {:?}", task);
+        telemetry::lifecycle(
+            "task_received",
+            topics::ANALYSIS_REQUESTS,
+            correlation_id,
+            None,
+        );
+        let started = Instant::now();
+
+        // Skip self-test messages to avoid infinite loop
+        if query == "SELF_TEST" {
+            println!("🧠 [AnalysisAgent] Skipping SELF_TEST message");
+            return Ok("Self-test completed successfully".to_string());
+        }
+
+        // A request the doctor sent again after a reconnect
+        match correlation_id.map(delivery::begin) {
+            Some(delivery::Seen::InProgress) => {
+                println!(
+                    "🧠 [AnalysisAgent] [{}] Already analyzing this request, telling the doctor it isn't lost",
+                    correlation::display(correlation_id)
+                );
+                context
+                    .publish(
+                        topics::topic(topics::ANALYSIS_PROGRESS),
+                        Task::new(correlation::tag_if_present(correlation_id, "in progress")),
+                    )
+                    .await?;
+                return Ok(format!("Analysis already in progress: {}", query));
+            }
+            Some(delivery::Seen::Answered(report)) => {
+                println!(
+                    "🧠 [AnalysisAgent] [{}] Already analyzed, sending the report again",
+                    correlation::display(correlation_id)
+                );
+                context
+                    .publish(
+                        topics::topic(topics::ANALYSIS_RESPONSE),
+                        Task::new(correlation::tag_if_present(correlation_id, &report)),
+                    )
+                    .await?;
+                return Ok(report);
+            }
+            Some(delivery::Seen::New) | None => {}
+        }
+
+        let task_timeout = self.task_timeout;
+        let Some(slots) = self.concurrency.clone() else {
+            return analyze_with_watchdog(
+                context,
+                self.emit_raw_json,
+                correlation_id,
+                query,
+                started,
+                task_timeout,
+            )
+            .await;
+        };
+
+        // Run in the background so the agent can accept the next task; the semaphore caps how
+        // many analyses are in flight. Each analysis builds its own chat from its task alone,
+        // so one patient's context never reaches another's.
+        println!(
+            "🧠 [AnalysisAgent] [{}] Scheduling analysis ({} of {} slots free)",
+            correlation::display(correlation_id),
+            slots.available_permits(),
+            self.concurrency_limit
+        );
+        let emit_raw_json = self.emit_raw_json;
+        let query = query.to_string();
+        let accepted = format!("Analysis scheduled: {}", query);
+        tokio::spawn(async move {
+            let Ok(_permit) = slots.acquire_owned().await else {
+                return;
+            };
+            if let Err(e) = analyze_with_watchdog(
+                context,
+                emit_raw_json,
+                correlation_id,
+                &query,
+                started,
+                task_timeout,
+            )
+            .await
+            {
+                eprintln!("❌ [AnalysisAgent] Background analysis failed: {}", e);
+            }
+        });
+        Ok(accepted)
+    }
+}
+
+// Abort an analysis that runs past `task_timeout` (e.g. a hung LLM call) and tell the doctor,
+// so the agent moves on to the next task instead of waiting forever
+async fn analyze_with_watchdog(
+    context: Arc<Context>,
+    emit_raw_json: bool,
+    correlation_id: Option<correlation::CorrelationId>,
+    query: &str,
+    started: Instant,
+    task_timeout: Duration,
+) -> Result<String, Error> {
+    let analysis = analyze_and_report(
+        context.clone(),
+        emit_raw_json,
+        correlation_id,
+        query,
+        started,
+    );
+    let result = tokio::time::timeout(task_timeout, analysis).await;
+    if let Some(id) = correlation_id {
+        match &result {
+            Ok(Ok(report)) => delivery::finish(id, report),
+            _ => delivery::abandon(id),
+        }
+    }
+    match result {
+        Ok(result) => result,
+        Err(_) => {
+            eprintln!(
+                "⏰ [AnalysisAgent] [{}] Analysis timed out after {}s, aborted: {}",
+                correlation::display(correlation_id),
+                task_timeout.as_secs(),
+                query
+            );
+            telemetry::lifecycle(
+                "analysis_timed_out",
+                topics::ANALYSIS_REQUESTS,
+                correlation_id,
+                Some(started.elapsed().as_millis()),
+            );
+            let notice = timeout_report(query, task_timeout);
+            let _ = context
+                .publish(
+                    topics::topic(topics::ANALYSIS_RESPONSE),
+                    Task::new(correlation::tag_if_present(correlation_id, &notice)),
+                )
+                .await;
+            Err(Error::CustomError(format!(
+                "analysis timed out after {}s",
+                task_timeout.as_secs()
+            )))
+        }
+    }
+}
+
+fn timeout_report(query: &str, task_timeout: Duration) -> String {
+    format!(
+        "### ECG Analysis Error\nThe analysis for '{}' was stopped after {} seconds without a result. Please try again.",
+        query,
+        task_timeout.as_secs()
+    )
+}
+
+// Run one analysis, save it and publish the report (or an error report) to the doctor
+async fn analyze_and_report(
+    context: Arc<Context>,
+    emit_raw_json: bool,
+    correlation_id: Option<correlation::CorrelationId>,
+    query: &str,
+    started: Instant,
+) -> Result<String, Error> {
+    let analysis_result = match analyze_ecg_query(
+        context.llm().clone(),
+        &context.config().description,
+        query,
+        context.config().output_schema.clone(),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            // Tell the doctor explicitly instead of leaving the request unanswered
+            eprintln!("❌ [AnalysisAgent] Analysis failed: {}", e);
+            let error_report = format!(
+                "### ECG Analysis Error\nThe analysis for '{}' could not be completed: {}",
+                query, e
+            );
+            let _ = context
+                .publish(
+                    topics::topic(topics::ANALYSIS_RESPONSE),
+                    Task::new(correlation::tag_if_present(correlation_id, &error_report)),
+                )
+                .await;
+            return Err(e);
+        }
+    };
+
+    println!("📈 [AnalysisAgent] Analysis completed!");
+    telemetry::lifecycle(
+        "analysis_complete",
+        topics::ANALYSIS_REQUESTS,
+        correlation_id,
+        Some(started.elapsed().as_millis()),
+    );
+    println!("\n{}", "=".repeat(80));
+    println!("🎯 FINAL ANALYSIS REPORT:");
+    println!("{}", "=".repeat(80));
+    println!("{}", analysis_result);
+    println!("{}\n", "=".repeat(80));
+
+    // Keep a copy of the report so the doctor can look it up later
+    let report_path = match save_report(&analysis_result) {
+        Ok(path) => Some(path),
+        Err(e) => {
+            eprintln!("❌ [AnalysisAgent] Failed to save report: {}", e);
+            None
+        }
+    };
+
+    let report = AnalysisReport {
+        correlation_id: correlation_id.map(|id| id.to_string()),
+        query: query.to_string(),
+        report: analysis_result.clone(),
+        generated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    // Runs in the background, the doctor gets the report below either way
+    #[cfg(feature = "webhook")]
+    crate::webhook::deliver(&report);
+
+    if emit_raw_json {
+        let json = serde_json::to_string(&report).map_err(|e| {
+            Error::CustomError(format!("Failed to serialize analysis report: {}", e))
+        })?;
+
+        if let Some(path) = &report_path {
+            let json_path = path.with_extension("json");
+            match write_report(&json_path, &json) {
+                Ok(_) => println!(
+                    "💾 [AnalysisAgent] Raw JSON saved to {}",
+                    json_path.display()
+                ),
+                Err(e) => eprintln!("❌ [AnalysisAgent] Failed to save raw JSON: {}", e),
+            }
+        }
+
+        if let Err(e) = context
+            .publish(
+                topics::topic(topics::ANALYSIS_RESPONSE_JSON),
+                Task::new(json),
+            )
+            .await
+        {
+            eprintln!("❌ [AnalysisAgent] Failed to publish raw JSON: {}", e);
+        }
+    }
+
+    #[cfg(feature = "waveform")]
+    if crate::waveform::enabled() {
+        publish_waveform(&context, correlation_id, query, report_path.as_deref()).await;
+    }
+
+    // Analysis is complete - the result will be captured by the event handling system
+    if let Err(e) = context
+        .publish(
+            topics::topic(topics::ANALYSIS_RESPONSE),
+            Task::new(correlation::tag_if_present(
+                correlation_id,
+                &analysis_result,
+            )),
+        )
+        .await
+    {
+        telemetry::lifecycle(
+            "publish_failed",
+            topics::ANALYSIS_RESPONSE,
+            correlation_id,
+            None,
+        );
+        return Err(e.into());
+    }
+    telemetry::lifecycle(
+        "publish_succeeded",
+        topics::ANALYSIS_RESPONSE,
+        correlation_id,
+        None,
+    );
+
+    Ok(analysis_result)
+}
+
+// Plot the sample file named in the query, keep the PNG beside the report and send it to the
+// doctor GUI. Queries without a readable sample file simply get no plot.
+#[cfg(feature = "waveform")]
+// Tagged with the request's correlation id like the report it belongs to
+async fn publish_waveform(
+    context: &Context,
+    correlation_id: Option<correlation::CorrelationId>,
+    query: &str,
+    report_path: Option<&std::path::Path>,
+) {
+    let (_, query) = patient::detach(query);
+    let Some(sample_file) = crate::waveform::sample_file_in(query) else {
+        return;
+    };
+    let png = match fs::read_to_string(&sample_file)
+        .map_err(|e| format!("could not read {}: {}", sample_file.display(), e))
+        .and_then(|content| rhythm::parse_samples(&content))
+        .and_then(|samples| crate::waveform::render_png(&samples))
+    {
+        Ok(png) => png,
+        Err(e) => {
+            eprintln!("❌ [AnalysisAgent] Failed to render waveform: {}", e);
+            return;
+        }
+    };
+
+    if let Some(path) = report_path {
+        let png_path = path.with_extension("png");
+        match fs::write(&png_path, &png) {
+            Ok(_) => println!(
+                "💾 [AnalysisAgent] Waveform saved to {}",
+                png_path.display()
+            ),
+            Err(e) => eprintln!("❌ [AnalysisAgent] Failed to save waveform: {}", e),
+        }
+    }
+
+    let waveform = WaveformImage {
+        query: query.to_string(),
+        png,
+    };
+    if let Err(e) = context
+        .publish(
+            topics::topic(topics::ANALYSIS_WAVEFORM),
+            Task::new(correlation::tag_if_present(
+                correlation_id,
+                &waveform.encode(),
+            )),
+        )
+        .await
+    {
+        eprintln!("❌ [AnalysisAgent] Failed to publish waveform: {}", e);
+    }
+}
+
+// Core ECG analysis of the AnalysisAgent
+async fn analyze_ecg_query(
+    llm: Arc<dyn LLMProvider>,
+    description: &str,
+    query: &str,
+    output_schema: Option<StructuredOutputFormat>,
+) -> Result<String, Error> {
+    let (patient_context, query) = patient::detach(query);
+    if let Some(patient_context) = patient_context {
+        println!("🧠 [AnalysisAgent] Patient context: {}", patient_context);
+    }
+
+    let mut messages = vec![ChatMessage {
+        role: ChatRole::System,
+        message_type: MessageType::Text,
+        content: format!(
+            "{} - > ECG Data Context: {}\nPatient Context: {}",
+            description,
+            "Add ECG",
+            patient_context.unwrap_or("Not provided")
+        ),
+    }];
+
+    let analysis_prompt = format!(
+        "{}
+
+Based on this research data, provide:
+1. Key insights and patterns identified
+2. Strategic recommendations
+3. Risk assessment and opportunities
+4. Actionable next steps
+5. Executive summary of findings
+
+Provide a comprehensive analysis report.",
+        query
+    );
+
+    let chat_msg = ChatMessage {
+        role: ChatRole::User,
+        message_type: MessageType::Text,
+        content: analysis_prompt,
+    };
+    messages.push(chat_msg);
+
+    chat_with_retry(&llm, &messages, output_schema, "analysis").await
+}
+
+// Chat attempts before giving up on an empty or malformed answer
+const LLM_ATTEMPTS: u32 = 3;
+
+// Limit on a single LLM request, from --llm-timeout-secs. Applied to the provider's HTTP client
+// and, as a backstop, around each direct chat call.
+pub const DEFAULT_LLM_TIMEOUT_SECS: u64 = 120;
+static LLM_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_LLM_TIMEOUT_SECS);
+
+pub fn set_llm_timeout(timeout: Duration) {
+    LLM_TIMEOUT_SECS.store(timeout.as_secs().max(1), Ordering::Relaxed);
+}
+
+pub fn llm_timeout() -> Duration {
+    Duration::from_secs(LLM_TIMEOUT_SECS.load(Ordering::Relaxed))
+}
+
+// HTTP clients report an expired request timeout as "operation timed out" or similar
+pub(crate) fn is_timeout_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("timed out") || message.contains("timeout")
+}
+
+pub(crate) fn llm_timeout_message(limit: Duration) -> String {
+    format!("The AI service timed out after {}s", limit.as_secs())
+}
+
+fn llm_timed_out(limit: Duration) -> Error {
+    Error::CustomError(llm_timeout_message(limit))
+}
+
+// Answers shorter than this are treated as empty
+const MIN_RESPONSE_CHARS: usize = 20;
 
-    ## ReAct Execution Pattern
-    As a ReAct agent, you follow this pattern for NEW user queries:
-    1. **Thought**: Analyze what needs to be done and plan your approach
-    2. **Action**: Use appropriate tools to gather information (ONLY for new user queries)
-    3. **Observation**: Process the results from your tools
-    4. **Repeat**: Continue until task is complete
+// An answer is usable when it has some substance and, with a schema, parses as JSON
+fn is_usable_response(text: &str, structured: bool) -> bool {
+    let text = text.trim();
+    text.chars().count() >= MIN_RESPONSE_CHARS
+        && (!structured || serde_json::from_str::<Value>(text).is_ok())
+}
 
-    For ANALYSIS RESPONSES: Skip tools, respond directly to user.
+// The messages of a prompt as text, one block per message. Image bytes are replaced by their
+// size; the text around them is what goes wrong when a prompt is built incorrectly.
+fn describe_prompt(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let attachment = match &message.message_type {
+                MessageType::Text => String::new(),
+                MessageType::Image((mime, bytes)) => {
+                    format!(" <{} image, {} bytes>", mime.mime_type(), bytes.len())
+                }
+                _ => " <non-text content>".to_string(),
+            };
+            format!("[{:?}]{} {}", message.role, attachment, message.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    Remember: Distinguish between new user queries (use tools) and analysis responses (respond directly).",
-    tools = [PublishTopicToAnalysis, CameraAnalysisTool],
-)]
-#[derive(Clone)]
-pub struct DoctorAgent {}
+// Added to the provider's temperature on each retry, so a retry samples a different answer
+const RETRY_TEMPERATURE_STEP: f32 = 0.2;
+// Retries don't go above this unless the provider already does; Anthropic accepts up to 1
+const MAX_RETRY_TEMPERATURE: f32 = 1.0;
 
-#[agent(
-    name = "analysis_agent",
-    description = "You are an analysis agent that receives a query related to the ecg reading and you must provide a recommendation based on the data.",
-    tools = [],
-)]
-pub struct AnalysisAgent {}
+fn retry_temperature(temperature: f32, attempt: u32) -> f32 {
+    let raised = temperature + RETRY_TEMPERATURE_STEP * attempt.saturating_sub(1) as f32;
+    raised.min(MAX_RETRY_TEMPERATURE).max(temperature)
+}
 
-impl ReActExecutor for DoctorAgent {}
+type LlmRebuild = Box<dyn Fn(f32) -> Result<Arc<dyn LLMProvider>, String> + Send + Sync>;
 
-#[async_trait]
-impl AgentExecutor for AnalysisAgent {
-    type Output = String;
-    type Error = Error;
+// How to rebuild a provider at another temperature, and the providers built for its retries
+struct RetryLlms {
+    original: std::sync::Weak<dyn LLMProvider>,
+    temperature: f32,
+    rebuild: LlmRebuild,
+    by_attempt: BTreeMap<u32, Arc<dyn LLMProvider>>,
+}
 
-    fn config(&self) -> ExecutorConfig {
-        ExecutorConfig { max_turns: 10 }
+static RETRY_LLMS: Mutex<Vec<RetryLlms>> = Mutex::new(Vec::new());
+
+// Called wherever a provider is built; the temperature is fixed then, so a retry at a higher
+// one needs a provider of its own
+pub fn register_retry_llms(
+    llm: &Arc<dyn LLMProvider>,
+    temperature: f32,
+    rebuild: impl Fn(f32) -> Result<Arc<dyn LLMProvider>, String> + Send + Sync + 'static,
+) {
+    let mut registered = RETRY_LLMS.lock().unwrap();
+    registered.retain(|entry| entry.original.strong_count() > 0);
+    registered.push(RetryLlms {
+        original: Arc::downgrade(llm),
+        temperature,
+        rebuild: Box::new(rebuild),
+        by_attempt: BTreeMap::new(),
+    });
+}
+
+// The provider for an attempt: `llm` itself first, then one built at a higher temperature.
+// Providers that weren't registered, or can't be rebuilt, retry at their own temperature.
+fn llm_for_attempt(llm: &Arc<dyn LLMProvider>, attempt: u32, agent: &str) -> Arc<dyn LLMProvider> {
+    if attempt <= 1 {
+        return llm.clone();
+    }
+    let mut registered = RETRY_LLMS.lock().unwrap();
+    let Some(entry) = registered.iter_mut().find(|entry| {
+        entry
+            .original
+            .upgrade()
+            .is_some_and(|original| Arc::ptr_eq(&original, llm))
+    }) else {
+        return llm.clone();
+    };
+    if let Some(retry_llm) = entry.by_attempt.get(&attempt) {
+        return retry_llm.clone();
+    }
+    let temperature = retry_temperature(entry.temperature, attempt);
+    match (entry.rebuild)(temperature) {
+        Ok(retry_llm) => {
+            println!("🌡️ [{}] Retrying at temperature {:.1}", agent, temperature);
+            entry.by_attempt.insert(attempt, retry_llm.clone());
+            retry_llm
+        }
+        Err(e) => {
+            eprintln!(
+                "⚠️ [{}] Could not build the LLM at temperature {:.1}, retrying as is: {}",
+                agent, temperature, e
+            );
+            llm.clone()
+        }
     }
+}
 
-    async fn execute(
-        &self,
-        task: &Task,
-        context: Arc<Context>,
-    ) -> Result<Self::Output, Self::Error> {
-        println!("🧠 [AnalysisAgent] *** EXECUTE METHOD CALLED ***");
+// Chat, retrying when the LLM answers with nothing usable, each time at a slightly higher
+// temperature. Provider errors are returned straight away.
+async fn chat_with_retry(
+    llm: &Arc<dyn LLMProvider>,
+    messages: &[ChatMessage],
+    output_schema: Option<StructuredOutputFormat>,
+    agent: &str,
+) -> Result<String, Error> {
+    let structured = output_schema.is_some();
+    // The full prompt, for prompt-construction bugs; off unless -vv or RUST_LOG=trace
+    if log::log_enabled!(log::Level::Trace) {
+        log::trace!("[{}] LLM prompt:\n{}", agent, describe_prompt(messages));
+    }
+    for attempt in 1..=LLM_ATTEMPTS {
+        let limit = llm_timeout();
+        let attempt_llm = llm_for_attempt(llm, attempt, agent);
+        let response = match tokio::time::timeout(
+            limit,
+            attempt_llm.chat(messages, None, output_schema.clone()),
+        )
+        .await
+        {
+            Ok(Err(e)) if is_timeout_error(&e.to_string()) => return Err(llm_timed_out(limit)),
+            Ok(response) => response?,
+            Err(_) => return Err(llm_timed_out(limit)),
+        };
+        usage::record(agent, response.as_ref());
+        let text = response.text().unwrap_or_default();
+        if is_usable_response(&text, structured) {
+            return Ok(text);
+        }
         println!(
-            "🧠 [AnalysisAgent] Received research data for analysis: {}",
-            task.prompt
+            "⚠️ [{}] LLM returned an empty or malformed answer (attempt {}/{})",
+            agent, attempt, LLM_ATTEMPTS
         );
-        println!("🧠 [AnalysisAgent] Task details: {:?}", task);
+    }
+    Err(Error::CustomError(format!(
+        "the LLM returned no usable answer after {} attempts",
+        LLM_ATTEMPTS
+    )))
+}
 
-        // Skip self-test messages to avoid infinite loop
-        if task.prompt == "SELF_TEST" {
-            println!("🧠 [AnalysisAgent] Skipping SELF_TEST message");
-            return Ok("Self-test completed successfully".to_string());
+// Run a single analysis without joining the cluster, for batch and cron use
+pub async fn run_analysis_once(
+    llm: Arc<dyn LLMProvider>,
+    query: String,
+    output: Option<PathBuf>,
+) -> Result<String, Error> {
+    println!("🧠 [AnalysisAgent] Running one-shot analysis: {}", query);
+    // Saved to the reports directory by the agent, like a cluster analysis
+    let analysis_result = analyze_standalone(llm, &query).await?;
+
+    println!("{}", analysis_result);
+    if let Some(output) = output {
+        write_report(&output, &analysis_result)?;
+        println!("💾 [AnalysisAgent] Report written to {}", output.display());
+    }
+    usage::print_final_summary();
+
+    Ok(analysis_result)
+}
+
+// Run a query through AnalysisAgent::execute outside the cluster (analyze-once, batch-analyze),
+// stopped after DEFAULT_TASK_TIMEOUT like a cluster task. Nothing is subscribed to what the
+// agent publishes, so those events are dropped.
+pub async fn analyze_standalone(llm: Arc<dyn LLMProvider>, query: &str) -> Result<String, Error> {
+    let analysis_agent = AnalysisAgent::new(false, 1, DEFAULT_TASK_TIMEOUT);
+    let (tx, mut rx) = mpsc::channel::<Event>(16);
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    let context = Context::new(llm, Some(tx)).with_config(AgentConfig {
+        id: uuid::Uuid::new_v4(),
+        name: analysis_agent.name().to_string(),
+        description: analysis_agent.description().to_string(),
+        output_schema: analysis_agent.output_schema(),
+    });
+    let analysis_result = analysis_agent
+        .execute(&Task::new(query.to_string()), Arc::new(context))
+        .await?;
+    if analysis_result.trim().is_empty() {
+        return Err(Error::CustomError(
+            "LLM returned an empty analysis".to_string(),
+        ));
+    }
+    Ok(analysis_result)
+}
+
+fn save_report(report: &str) -> std::io::Result<PathBuf> {
+    let dir = reports_dir();
+    fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let path = dir.join(format!("report_{}.md", timestamp));
+    write_report(&path, report)?;
+    println!("💾 [AnalysisAgent] Report saved to {}", path.display());
+    Ok(path)
+}
+
+// Reports are always written as UTF-8 so units like µV and ° survive the round trip
+fn write_report(path: &Path, report: &str) -> std::io::Result<()> {
+    fs::write(path, report.as_bytes())
+}
+
+// Read a report as UTF-8. A file with invalid sequences (edited elsewhere or written in another
+// encoding) is decoded lossily with a warning instead of being dropped.
+fn read_report(path: &Path) -> std::io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!(
+                "⚠️ Report {} is not valid UTF-8 after byte {}, replacing invalid sequences",
+                path.display(),
+                e.utf8_error().valid_up_to()
+            );
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
         }
+    })
+}
 
-        let mut messages = vec![ChatMessage {
-            role: ChatRole::System,
-            message_type: MessageType::Text,
-            content: format!(
-                "{} - > ECG Data Context: {}",
-                context.config().description,
-                "Add ECG"
-            ),
-        }];
+// Machine-readable form of an analysis, published with --emit-raw-json and posted to --webhook-url
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AnalysisReport {
+    pub correlation_id: Option<String>,
+    pub query: String,
+    pub report: String,
+    pub generated_at: u64, // Unix seconds
+}
 
-        let analysis_prompt = format!(
-            "{}
+// Sent on DoctorChannels::user_rx when the GUI window closes; stops the doctor node. Closing
+// the channel has the same effect.
+pub const SHUTDOWN_MESSAGE: &str = "SHUTDOWN";
 
-Based on this research data, provide:
-1. Key insights and patterns identified
-2. Strategic recommendations
-3. Risk assessment and opportunities
-4. Actionable next steps
-5. Executive summary of findings
+// Channels connecting the doctor agent to the GUI thread
+pub struct DoctorChannels {
+    pub user_rx: mpsc::Receiver<String>, // Bounded by DoctorSettings::channel_capacity
+    pub response_tx: mpsc::Sender<AgentResponse>, // Bounded by DoctorSettings::channel_capacity
+    pub ack_tx: mpsc::UnboundedSender<String>, // Queries the doctor started handling
+    pub cancel_rx: mpsc::UnboundedReceiver<()>,
+    pub camera_confirm_tx: Option<mpsc::UnboundedSender<CameraConfirmRequest>>, // Set with --confirm-camera
+    pub queue_tx: mpsc::UnboundedSender<QueueStatus>,
+    pub reasoning_tx: mpsc::UnboundedSender<String>, // Intermediate ReAct steps for the GUI
+    pub upload_rx: mpsc::UnboundedReceiver<UploadedImage>, // Images attached in the GUI
+}
 
-Provide a comprehensive analysis report.",
-            task.prompt
+// A response for the GUI, tagged with the agent that produced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentResponse {
+    pub content: String,
+    pub agent_source: Option<String>, // "analysis", "camera" or "doctor"; None for notices
+    pub is_error: bool, // Shown as an error bubble; doesn't count as the answer to the query
+    pub image: Option<Vec<u8>>, // PNG shown above the text, e.g. an ECG waveform
+    pub in_reply_to: Option<String>, // User question a camera result answers
+    pub is_final: bool, // The doctor's answer with no analysis or camera result still to come
+}
+
+impl AgentResponse {
+    // A message from the doctor node itself rather than from an agent
+    pub fn notice(content: impl Into<String>) -> Self {
+        AgentResponse {
+            content: content.into(),
+            agent_source: None,
+            is_error: false,
+            image: None,
+            in_reply_to: None,
+            is_final: false,
+        }
+    }
+
+    pub fn error(content: impl Into<String>) -> Self {
+        AgentResponse {
+            is_error: true,
+            ..AgentResponse::notice(content)
+        }
+    }
+}
+
+// Snapshot of the doctor's query queue, shown in the GUI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueStatus {
+    pub active: Option<String>, // Query currently being processed
+    pub queued: usize,          // Queries waiting behind it
+}
+
+// Apply a change to the shared queue status and push the new snapshot to the GUI
+fn update_queue_status(
+    status: &Mutex<QueueStatus>,
+    queue_tx: &mpsc::UnboundedSender<QueueStatus>,
+    change: impl FnOnce(&mut QueueStatus),
+) {
+    let mut status = status.lock().unwrap();
+    change(&mut status);
+    let _ = queue_tx.send(status.clone());
+}
+
+// How long a query may hold the queue without a final answer before the next one starts,
+// where no --query-timeout-secs is given
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Tunables for the doctor agent, taken from the Doctor command flags
+#[derive(Debug, Clone)]
+pub struct DoctorSettings {
+    pub memory_window: usize,
+    pub confirm_camera: bool,
+    pub rate_limit_per_min: u32,
+    pub max_query_chars: usize, // Longer queries are condensed by the LLM before the ReAct loop
+    pub channel_capacity: usize, // Buffered messages in each direction between the GUI and the agent
+    pub max_tool_calls: usize,   // Calls allowed per tool within one query before it is aborted
+    pub max_turns: usize,        // ReAct iterations per query
+    pub query_timeout: Duration, // Longest a query holds the queue without a final answer
+    pub history: Vec<StoredMessage>, // The open GUI conversation, replayed into the agent's memory
+}
+
+// Turns of a saved conversation as agent memory: the user's queries and the agents' answers.
+// Errors and the GUI's own notices never came from the agent, so they stay out.
+fn memory_from_history(history: &[StoredMessage]) -> Vec<ChatMessage> {
+    history
+        .iter()
+        .filter(|message| !message.is_error && (message.is_user || message.agent_source.is_some()))
+        .map(|message| ChatMessage {
+            role: if message.is_user {
+                ChatRole::User
+            } else {
+                ChatRole::Assistant
+            },
+            message_type: MessageType::Text,
+            content: message.content.clone(),
+        })
+        .collect()
+}
+
+// Deliver an agent answer to the GUI. Answers are never dropped: when the GUI falls behind
+// this waits for room, which in turn holds back the next query.
+async fn send_to_gui(response_tx: &mpsc::Sender<AgentResponse>, response: AgentResponse) {
+    let response = match response_tx.try_send(response) {
+        Ok(()) => return,
+        Err(TrySendError::Full(response)) => response,
+        Err(TrySendError::Closed(_)) => {
+            eprintln!("❌ Failed to send response to GUI: channel closed");
+            return;
+        }
+    };
+    println!("⏳ GUI response channel is full, waiting for the GUI to catch up");
+    if response_tx.send(response).await.is_err() {
+        eprintln!("❌ Failed to send response to GUI: channel closed");
+    }
+}
+
+// Notices are informational, so one that doesn't fit is dropped instead of blocking
+fn send_notice(response_tx: &mpsc::Sender<AgentResponse>, content: String) {
+    if let Err(TrySendError::Full(notice)) = response_tx.try_send(AgentResponse::notice(content)) {
+        println!(
+            "⚠️ GUI response channel is full, dropping notice: {}",
+            notice.content
         );
+    }
+}
+
+// Per-query count of the doctor's tool calls: a hard stop for ReAct loops that the prompt's
+// loop-prevention rules didn't prevent
+struct ToolCallGuard {
+    limit: usize,
+    counts: BTreeMap<String, usize>,
+    request: CancellationToken, // Cancelled when the limit trips, which ends the query
+    tripped: bool,
+}
+
+impl ToolCallGuard {
+    fn new(limit: usize, request: CancellationToken) -> Self {
+        ToolCallGuard {
+            limit,
+            counts: BTreeMap::new(),
+            request,
+            tripped: false,
+        }
+    }
+
+    // Count one call of `tool`; Err once it goes over the limit for this query
+    fn record(&mut self, tool: &str) -> Result<(), String> {
+        let count = self.counts.entry(tool.to_string()).or_default();
+        *count += 1;
+        if *count > self.limit {
+            Err(format!(
+                "The assistant called {} {} times for one question (limit {}), so the request was stopped.",
+                tool, count, self.limit
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// None until the doctor starts a query; tools used outside the doctor aren't limited
+static TOOL_CALLS: Mutex<Option<ToolCallGuard>> = Mutex::new(None);
+
+fn reset_tool_calls(limit: usize, request: CancellationToken) {
+    *TOOL_CALLS.lock().unwrap() = Some(ToolCallGuard::new(limit, request));
+}
+
+fn tool_call_limit_tripped() -> bool {
+    TOOL_CALLS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|guard| guard.tripped)
+}
+
+// Called first by every doctor tool. Going over the limit cancels the query (its remaining
+// responses are dropped and the queue moves on) and tells the user why.
+fn guard_tool_call(tool: &str) -> Result<(), ToolCallError> {
+    let mut calls = TOOL_CALLS.lock().unwrap();
+    let Some(guard) = calls.as_mut() else {
+        return Ok(());
+    };
+    // A stopped query doesn't get to publish anything more
+    if guard.request.is_cancelled() {
+        return Err(ToolCallError::from(Box::<
+            dyn std::error::Error + Send + Sync,
+        >::from(
+            "the request was cancelled"
+        )));
+    }
+    let Err(message) = guard.record(tool) else {
+        return Ok(());
+    };
+    if !guard.tripped {
+        guard.tripped = true;
+        eprintln!("🛑 {}", message);
+        guard.request.cancel();
+        notify_gui(format!("🛑 {} Please rephrase your question.", message));
+    }
+    Err(ToolCallError::from(Box::<
+        dyn std::error::Error + Send + Sync,
+    >::from(message)))
+}
+
+// Route for notices raised inside tools, which have no handle on the GUI channels
+static GUI_NOTICE: Mutex<Option<mpsc::Sender<AgentResponse>>> = Mutex::new(None);
 
-        let chat_msg = ChatMessage {
+fn notify_gui(content: String) {
+    match GUI_NOTICE.lock().unwrap().as_ref() {
+        Some(response_tx) => send_notice(response_tx, content),
+        None => println!("ℹ️ {}", content),
+    }
+}
+
+// Ask the LLM to condense an overly long query, keeping every clinical detail. The result is
+// capped at `max_chars` in case the model ignores the limit.
+async fn condense_query(
+    llm: &Arc<dyn LLMProvider>,
+    query: &str,
+    max_chars: usize,
+) -> Result<String, Error> {
+    let messages = vec![
+        ChatMessage {
+            role: ChatRole::System,
+            message_type: MessageType::Text,
+            content: format!(
+                "You condense long messages sent to a clinical ECG assistant. Keep every clinical fact, measurement, question and instruction; drop repetition and boilerplate. Reply with the condensed message only, in at most {} characters.",
+                max_chars
+            ),
+        },
+        ChatMessage {
             role: ChatRole::User,
             message_type: MessageType::Text,
-            content: analysis_prompt,
-        };
-        messages.push(chat_msg);
+            content: query.to_string(),
+        },
+    ];
+    let condensed = chat_with_retry(llm, &messages, None, "doctor").await?;
+    Ok(condensed.trim().chars().take(max_chars).collect())
+}
 
-        let response = context
-            .llm()
-            .chat(&messages, None, context.config().output_schema.clone())
-            .await?;
-        let analysis_result = response.text().unwrap_or_default();
+// Token bucket capping how many user messages are forwarded to the agent per minute
+struct RateLimiter {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
 
-        println!("📈 [AnalysisAgent] Analysis completed!");
-        println!("\n{}", "=".repeat(80));
-        println!("🎯 FINAL ANALYSIS REPORT:");
-        println!("{}", "=".repeat(80));
-        println!("{}", analysis_result);
-        println!("{}\n", "=".repeat(80));
+impl RateLimiter {
+    fn per_minute(limit: u32) -> Self {
+        Self {
+            capacity: limit as f64,
+            tokens: limit as f64,
+            refill_per_sec: limit as f64 / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
 
-        // Analysis is complete - the result will be captured by the event handling system
-        context
-            .publish(
-                Topic::<Task>::new("analysis_response"),
-                Task::new(analysis_result.clone()),
-            )
-            .await?;
+    fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
 
-        Ok(analysis_result)
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
     }
 }
 
 pub async fn run_doctor_agent(
-    llm: Arc<OpenAI>,
-    node_name: String,
-    port: u16,
-    host_addr: String,
-    host: String,
-    mut user_rx: mpsc::UnboundedReceiver<String>,
-    response_tx: mpsc::UnboundedSender<String>,
+    llm: Arc<dyn LLMProvider>,
+    node: ClusterNode,
+    channels: DoctorChannels,
+    settings: DoctorSettings,
 ) -> Result<(), Error> {
-    println!(
-        "🔍 Initializing DoctorAgent cluster client on port {}",
-        port
-    );
+    let DoctorChannels {
+        mut user_rx,
+        response_tx,
+        ack_tx,
+        mut cancel_rx,
+        camera_confirm_tx,
+        queue_tx,
+        reasoning_tx,
+        mut upload_rx,
+    } = channels;
+    CONFIRM_CAMERA.store(camera_confirm_tx.is_some(), Ordering::Relaxed);
+    *GUI_NOTICE.lock().unwrap() = Some(response_tx.clone());
 
-    let sliding_window_memory = Box::new(SlidingWindowMemory::new(50));
-    let research_topic = Topic::<Task>::new("doctor_agent");
-    let user_messages_topic = Topic::<Task>::new("user_messages"); // Separate topic for GUI messages
-
-    // Create cluster client runtime for DoctorAgent - it will connect to dedicated cluster host
-    let runtime = ClusterClientRuntime::new(
-        "doctor_client".to_string(),
-        host_addr.clone(),
-        node_name,
-        "cluster-cookie".to_string(),
-        port,
-        host,
-    );
+    // Token for the request currently being processed - cancelled by the GUI's Stop button
+    let current_request = Arc::new(Mutex::new(CancellationToken::new()));
 
-    let research_agent = DoctorAgent {};
+    // Notices for the GUI that don't come from the agent itself
+    let notice_tx = response_tx.clone();
+    let condense_notice_tx = response_tx.clone();
 
-    // Build and register DoctorAgent - subscribe to user_messages topic (not doctor_agent to avoid loops)
-    let _ = AgentBuilder::new(research_agent)
-        .with_llm(llm)
+    // Agent responses go through a gate that drops anything belonging to a cancelled request.
+    // The doctor's final answer also signals the queue that the active query is done; reports,
+    // notices and the "request submitted" step in between don't.
+    let (agent_response_tx, mut agent_response_rx) = mpsc::unbounded_channel::<AgentResponse>();
+    let waveform_tx = agent_response_tx.clone();
+    let llm_failure_tx = agent_response_tx.clone();
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<()>();
+    let gate_request = current_request.clone();
+    tokio::spawn(async move {
+        while let Some(response) = agent_response_rx.recv().await {
+            if gate_request.lock().unwrap().is_cancelled() {
+                println!(
+                    "🛑 Dropping response for cancelled request: {}",
+                    response.content
+                );
+                continue;
+            }
+            let is_final = response.is_final;
+            send_to_gui(&response_tx, response).await;
+            if is_final {
+                let _ = done_tx.send(());
+            }
+        }
+    });
+
+    let node_topics: Vec<&str> = topics::DOCTOR_AGENT_TOPICS
+        .into_iter()
+        .chain([
+            topics::USER_ACK,
+            topics::HEARTBEAT,
+            topics::AGENT_ANNOUNCE,
+            topics::ANALYSIS_WAVEFORM,
+            topics::ANALYSIS_PROGRESS,
+            topics::CAMERA_CONFIRM,
+        ])
+        .collect();
+    let runner = AgentRunner::new("🔍 DoctorAgent", "doctor_client", node)?
+        .announce(&["doctor"], &node_topics)
+        .forward_responses(agent_response_tx)
+        .forward_reasoning(reasoning_tx);
+    let runtime = runner.runtime();
+    *FOLLOWUP_RUNTIME.lock().unwrap() = Some(runtime.clone());
+    // Timers of an earlier doctor run died with its runtime
+    for (follow_up, token) in followups::armed() {
+        println!("⏰ Re-arming follow-up {}", follow_up.describe());
+        arm_followup(runtime.clone(), &follow_up, token);
+    }
+
+    // Build and register RosterAgent - tracks heartbeats from the other agents
+    registry::start_listening();
+    let _ = AgentBuilder::new(RosterAgent {})
+        .with_llm(llm.clone())
+        .runtime(runtime.clone())
+        .subscribe_topic(topics::topic(topics::HEARTBEAT))
+        .build()
+        .await?;
+
+    // Build and register RegistryAgent - learns which capabilities the cluster offers
+    let _ = AgentBuilder::new(RegistryAgent {})
+        .with_llm(llm.clone())
+        .runtime(runtime.clone())
+        .subscribe_topic(topics::topic(topics::AGENT_ANNOUNCE))
+        .build()
+        .await?;
+
+    // Build and register AckAgent - tells the GUI which queries the doctor picked up
+    let _ = AgentBuilder::new(AckAgent { ack_tx })
+        .with_llm(llm.clone())
+        .runtime(runtime.clone())
+        .subscribe_topic(topics::topic(topics::USER_ACK))
+        .build()
+        .await?;
+
+    // Build and register WaveformAgent - ECG plots from the analysis node (--render-waveform)
+    let _ = AgentBuilder::new(WaveformAgent {
+        response_tx: waveform_tx,
+    })
+    .with_llm(llm.clone())
+    .runtime(runtime.clone())
+    .subscribe_topic(topics::topic(topics::ANALYSIS_WAVEFORM))
+    .build()
+    .await?;
+
+    // Build and register ProgressAgent - re-sent analysis requests that are still running
+    let _ = AgentBuilder::new(ProgressAgent {})
+        .with_llm(llm.clone())
         .runtime(runtime.clone())
-        .subscribe_topic(user_messages_topic.clone()) // "user_messages" topic for GUI user queries
-        .subscribe_topic(Topic::<Task>::new("analysis_response")) // "analysis_response" topic for analysis results
-        .subscribe_topic(Topic::<Task>::new("camera_response")) // "camera_response" topic for camera analysis results
-        // DO NOT subscribe to "analysis_agent" topic - that's for AnalysisAgent only
-        .with_memory(sliding_window_memory)
+        .subscribe_topic(topics::topic(topics::ANALYSIS_PROGRESS))
+        .build()
+        .await?;
+
+    // Build and register CameraConfirmAgent - the approval dialog for captures (--confirm-camera)
+    if let Some(confirm_tx) = camera_confirm_tx {
+        let _ = AgentBuilder::new(CameraConfirmAgent { confirm_tx })
+            .with_llm(llm.clone())
+            .runtime(runtime.clone())
+            .subscribe_topic(topics::topic(topics::CAMERA_CONFIRM))
+            .build()
+            .await?;
+    }
+
+    // Each conversation has its own memory: the doctor restarts on a switch and starts from the
+    // conversation's earlier turns
+    let mut memory = SlidingWindowMemory::new(settings.memory_window);
+    for message in memory_from_history(&settings.history) {
+        if let Err(e) = memory.remember(&message).await {
+            println!("⚠️ Could not restore conversation memory: {}", e);
+        }
+    }
+
+    // Build and register DoctorAgent - user queries plus the analysis and camera results
+    // (not "analysis_agent", which is the AnalysisAgent's and would loop)
+    let condense_llm = llm.clone();
+    let _ = topics::DOCTOR_AGENT_TOPICS
+        .into_iter()
+        .fold(
+            AgentBuilder::new(DoctorAgent {
+                max_turns: settings.max_turns,
+            })
+            // Stopping a query aborts the ReAct loop at its LLM calls, and a timed-out call
+            // ends the query with an error the user sees
+            .with_llm(Arc::new(DoctorLlm::new(
+                llm,
+                current_request.clone(),
+                llm_failure_tx,
+            )))
+            .runtime(runtime.clone()),
+            |builder, topic| builder.subscribe_topic(topics::topic(topic)),
+        )
+        .with_memory(Box::new(memory))
         .build()
         .await?;
 
     println!(
-        "🔍 DoctorAgent subscribed to topics: ['user_messages', 'analysis_response', 'camera_response']"
+        "🔍 DoctorAgent subscribed to topics: {:?}",
+        topics::DOCTOR_AGENT_TOPICS
     );
     println!("🔍 DoctorAgent processes user messages from 'user_messages' topic (no loops)");
     println!("🔍 DoctorAgent receives analysis results from 'analysis_response' topic");
     println!("🔍 DoctorAgent receives camera analysis results from 'camera_response' topic");
 
-    // Create environment and set up event handling
-    let mut environment = Environment::new(None);
-    let _ = environment.register_runtime(runtime.clone()).await;
+    runner.start().await?;
+    sleep(Duration::from_secs(2)).await;
 
-    let receiver = environment.take_event_receiver(None).await?;
-    handle_events(receiver, response_tx.clone(), runtime.clone(), false);
+    // Listen for cancellation requests from the GUI
+    let cancel_request = current_request.clone();
+    tokio::spawn(async move {
+        while cancel_rx.recv().await.is_some() {
+            println!("🛑 Cancelling in-flight request");
+            cancel_request.lock().unwrap().cancel();
+        }
+    });
 
-    // Start the runtime and environment
+    // Uploaded images go straight to the camera node's vision path
+    let upload_runtime = runtime.clone();
     tokio::spawn(async move {
-        if let Err(e) = environment.run().await {
-            eprintln!("Environment error: {}", e);
+        let upload_topic = topics::topic(topics::UPLOADED_IMAGE);
+        while let Some(upload) = upload_rx.recv().await {
+            let correlation_id = correlation::new_id();
+            println!(
+                "🖼️ [{}] Forwarding uploaded image {} for analysis",
+                correlation_id, upload.file_name
+            );
+            let task = Task::new(correlation::tag(correlation_id, &upload.encode()));
+            if let Err(e) = upload_runtime.publish(&upload_topic, task).await {
+                eprintln!("❌ Failed to publish uploaded image: {}", e);
+            }
         }
     });
 
-    // Connection to host is handled automatically in ClusterClientRuntime
-    println!(
-        "🌐 ClusterClientRuntime will connect to cluster host at {}",
-        host_addr
-    );
-    sleep(Duration::from_secs(2)).await;
+    // User queries wait in this queue and are published one at a time so responses arrive in order.
+    // It is bounded like the GUI channel: when it is full the loop below stops reading user_rx,
+    // which fills up in turn and disables Send in the GUI.
+    let (query_tx, mut query_rx) = mpsc::channel::<String>(settings.channel_capacity);
+    let queue_status = Arc::new(Mutex::new(QueueStatus::default()));
 
-    // Listen for user messages from the GUI - create agent tasks directly to avoid cluster loops
-    let runtime_clone = runtime.clone();
-    let user_messages_topic_clone = user_messages_topic.clone();
+    // Listen for user messages from the GUI and queue the actual send events
+    let enqueue_status = queue_status.clone();
+    let enqueue_queue_tx = queue_tx.clone();
+    let mut rate_limiter = RateLimiter::per_minute(settings.rate_limit_per_min);
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
     tokio::spawn(async move {
         while let Some(message) = user_rx.recv().await {
             println!("📋 Received user message: {}", message);
+            if message == SHUTDOWN_MESSAGE {
+                break;
+            }
 
             // Only process messages that start with "USER_SEND:" to identify actual send events
             if message.starts_with("USER_SEND:") {
                 let actual_message = message.strip_prefix("USER_SEND:").unwrap_or(&message);
-                println!("✉️ Processing user send event directly: {}", actual_message);
 
-                // Use regular publish - we'll handle deduplication at the agent level
-                if let Err(e) = runtime_clone
-                    .publish(
-                        &user_messages_topic_clone,
-                        Task::new(actual_message.to_string()),
-                    )
-                    .await
-                {
-                    eprintln!("Failed to publish user message: {}", e);
+                // Drop messages over the per-minute budget to bound LLM costs
+                if !rate_limiter.try_acquire() {
+                    println!(
+                        "🚦 Rate limit reached, dropping message: {}",
+                        actual_message
+                    );
+                    send_notice(&notice_tx, "Rate limit reached, please wait.".to_string());
+                    continue;
+                }
+
+                update_queue_status(&enqueue_status, &enqueue_queue_tx, |status| {
+                    status.queued += 1
+                });
+                if query_tx.send(actual_message.to_string()).await.is_err() {
+                    break;
                 }
             } else {
                 println!("🔇 Skipping non-send message: {}", message);
             }
         }
+        println!("🚪 GUI disconnected, stopping the doctor agent");
+        let _ = shutdown_tx.send(());
     });
 
-    // Keep running until Ctrl+C
-    tokio::signal::ctrl_c()
-        .await
-        .expect("Failed to listen for Ctrl+C");
-    println!("🔍 Shutting down ResearchAgent...");
-    if let Err(e) = runtime.stop().await {
-        eprintln!("Error stopping runtime: {}", e);
-    }
+    // Work through the queue - create agent tasks directly to avoid cluster loops
+    let max_query_chars = settings.max_query_chars;
+    let max_tool_calls = settings.max_tool_calls;
+    let query_timeout = settings.query_timeout;
+    let runtime_clone = runtime.clone();
+    let user_messages_topic = topics::topic(topics::USER_MESSAGES);
+    tokio::spawn(async move {
+        while let Some(query) = query_rx.recv().await {
+            update_queue_status(&queue_status, &queue_tx, |status| {
+                status.queued = status.queued.saturating_sub(1);
+                status.active = Some(query.clone());
+            });
+            println!("✉️ Processing user send event directly: {}", query);
+            // Receipt for the GUI: the query left the queue and is being worked on
+            if let Err(e) = runtime_clone
+                .publish(&topics::topic(topics::USER_ACK), Task::new(query.clone()))
+                .await
+            {
+                eprintln!("❌ Failed to publish receipt for user message: {}", e);
+            }
 
-    Ok(())
+            // A new query starts with a fresh, uncancelled token
+            let token = CancellationToken::new();
+            *current_request.lock().unwrap() = token.clone();
+            reset_tool_calls(max_tool_calls, token.clone());
+            *ACTIVE_QUERY.lock().unwrap() = Some(query.clone());
+            AWAITED_RESULTS.lock().unwrap().clear();
+
+            // Responses that trickled in after the previous query finished don't count for this one
+            while done_rx.try_recv().is_ok() {}
+
+            // Condense pasted walls of text first to keep the token budget bounded
+            let query_chars = query.chars().count();
+            let prompt = if query_chars > max_query_chars {
+                match condense_query(&condense_llm, &query, max_query_chars).await {
+                    Ok(condensed) => {
+                        println!(
+                            "✂️ Condensed a {}-character query to {} characters",
+                            query_chars,
+                            condensed.chars().count()
+                        );
+                        send_notice(
+                            &condense_notice_tx,
+                            format!(
+                                "✂️ Your message was {} characters long, so it was condensed before analysis.",
+                                query_chars
+                            ),
+                        );
+                        condensed
+                    }
+                    Err(e) => {
+                        eprintln!("❌ Could not condense long query, sending it as is: {}", e);
+                        query.clone()
+                    }
+                }
+            } else {
+                query.clone()
+            };
+
+            // Use regular publish - we'll handle deduplication at the agent level
+            match runtime_clone
+                .publish(&user_messages_topic, Task::new(prompt))
+                .await
+            {
+                Ok(_) => {
+                    tokio::select! {
+                        _ = done_rx.recv() => println!("✅ Query answered: {}", query),
+                        _ = token.cancelled() => println!("🛑 Query cancelled: {}", query),
+                        _ = sleep(query_timeout) => {
+                            println!("⏰ No final answer within {:?}, moving on: {}", query_timeout, query)
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Failed to publish user message: {}", e),
+            }
+
+            update_queue_status(&queue_status, &queue_tx, |status| status.active = None);
+        }
+    });
+
+    runner
+        .run_until(async {
+            let _ = shutdown_rx.await;
+        })
+        .await
 }
 
 pub async fn run_analysis_agent(
-    llm: Arc<OpenAI>,
-    node_name: String,
-    port: u16,
-    host_addr: String,
-    host: String,
+    llm: Arc<dyn LLMProvider>,
+    node: ClusterNode,
+    memory_window: usize,
+    emit_raw_json: bool,
+    concurrency: usize,
+    task_timeout: Duration,
 ) -> Result<(), Error> {
-    println!(
-        "🧠 Initializing AnalysisAgent cluster client on port {}",
-        port
-    );
-
-    let sliding_window_memory = Box::new(SlidingWindowMemory::new(10));
-    let analysis_topic = Topic::<Task>::new("analysis_agent");
-
-    // Create cluster client runtime for AnalysisAgent - it will connect to dedicated cluster host
-    let runtime = ClusterClientRuntime::new(
-        "analysis_client".to_string(),
-        host_addr.clone(),
-        node_name,
-        "cluster-cookie".to_string(),
-        port,
-        host,
-    );
-
-    let analysis_agent = AnalysisAgent {};
+    let runner = AgentRunner::new("🧠 AnalysisAgent", "analysis_client", node)?
+        .analysis_routing()
+        .heartbeat("analysis")
+        .announce(&["analysis"], &[topics::ANALYSIS_REQUESTS]);
 
     // Build and register AnalysisAgent
-    let _ = AgentBuilder::new(analysis_agent)
+    let _ = AgentBuilder::new(AnalysisAgent::new(emit_raw_json, concurrency, task_timeout))
         .with_llm(llm)
-        .runtime(runtime.clone())
-        .subscribe_topic(analysis_topic.clone())
-        .with_memory(sliding_window_memory)
+        .runtime(runner.runtime())
+        .subscribe_topic(topics::topic(topics::ANALYSIS_REQUESTS))
+        .with_memory(Box::new(SlidingWindowMemory::new(memory_window)))
         .build()
         .await?;
 
-    // Create environment and set up event handling
-    let mut environment = Environment::new(None);
-    let _ = environment.register_runtime(runtime.clone()).await;
-
-    let receiver = environment.take_event_receiver(None).await?;
-    let (_dummy_tx, _) = mpsc::unbounded_channel::<String>();
-
-    // Use the regular handle_events function but with specific AnalysisAgent debugging
-    let (analysis_response_tx, _) = mpsc::unbounded_channel::<String>();
-    println!("🧠 Setting up AnalysisAgent event handler...");
-    handle_events(receiver, analysis_response_tx, runtime.clone(), true);
-
-    // Start the runtime and environment
-    tokio::spawn(async move {
-        if let Err(e) = environment.run().await {
-            eprintln!("Environment error: {}", e);
-        }
-    });
-
-    // Connection to host is handled automatically in ClusterClientRuntime
-    println!(
-        "🌐 ClusterClientRuntime will connect to cluster host at {}",
-        host_addr
-    );
-
+    runner.start().await?;
     println!("🧠 AnalysisAgent ready to receive research data for analysis...");
     println!("🧠 AnalysisAgent subscribed to topic: analysis_agent");
-    println!("🧠 AnalysisAgent runtime: {:?}", runtime);
-
-    // Keep running until Ctrl+C
-    tokio::signal::ctrl_c()
-        .await
-        .expect("Failed to listen for Ctrl+C");
-    println!("🧠 Shutting down AnalysisAgent...");
-    if let Err(e) = runtime.stop().await {
-        eprintln!("Error stopping runtime: {}", e);
-    }
-
-    Ok(())
+    runner.wait_for_shutdown().await
 }
 
 pub async fn run_camera_agent(
-    llm: Arc<OpenAI>,
-    node_name: String,
-    port: u16,
-    host_addr: String,
-    host: String,
+    llm: Arc<dyn LLMProvider>,
+    node: ClusterNode,
+    memory_window: usize,
+    settings: CameraSettings,
 ) -> Result<(), Error> {
-    println!(
-        "📷 Initializing CameraAgent cluster client on port {}",
-        port
-    );
+    let runner = AgentRunner::new("📷 CameraAgent", "camera_client", node)?
+        .heartbeat("camera")
+        .announce(
+            &["camera", "image_upload"],
+            &[topics::CAMERA_REQUESTS, topics::UPLOADED_IMAGE],
+        );
 
-    let sliding_window_memory = Box::new(SlidingWindowMemory::new(10));
-    let camera_topic = Topic::<Task>::new("camera_requests");
-
-    // Create cluster client runtime for CameraAgent - it will connect to dedicated cluster host
-    let runtime = ClusterClientRuntime::new(
-        "camera_client".to_string(),
-        host_addr.clone(),
-        node_name,
-        "cluster-cookie".to_string(),
-        port,
-        host,
-    );
+    // Build and register CameraAgent
+    let _ = AgentBuilder::new(CameraAgent {
+        settings: settings.clone(),
+    })
+    .with_llm(llm.clone())
+    .runtime(runner.runtime())
+    .subscribe_topic(topics::topic(topics::CAMERA_REQUESTS))
+    .with_memory(Box::new(SlidingWindowMemory::new(memory_window)))
+    .build()
+    .await?;
 
-    println!("📷 Creating CameraAgent instance...");
-    let camera_agent = CameraAgent {};
+    println!(
+        "📷 Camera results are published to: {}",
+        settings.result_topic
+    );
 
-    // Create and initialize agent
-    let _agent_instance = AgentBuilder::new(camera_agent)
+    // Build and register UploadedImageAgent - same vision path for images sent from the GUI
+    let _ = AgentBuilder::new(UploadedImageAgent { settings })
         .with_llm(llm)
-        .runtime(runtime.clone())
-        .subscribe_topic(camera_topic.clone())
-        .with_memory(sliding_window_memory)
+        .runtime(runner.runtime())
+        .subscribe_topic(topics::topic(topics::UPLOADED_IMAGE))
+        .with_memory(Box::new(SlidingWindowMemory::new(memory_window)))
         .build()
         .await?;
 
-    // Create environment and set up event handling
-    let mut environment = Environment::new(None);
-    let _ = environment.register_runtime(runtime.clone()).await;
+    runner.start().await?;
+    println!("📷 CameraAgent ready to analyze images for medical queries...");
+    println!("📷 CameraAgent subscribed to topics: camera_requests, uploaded_image");
+    println!("📷 Camera capture methods: ImageSnap (primary), FFmpeg (fallback)");
+    runner.wait_for_shutdown().await
+}
 
-    let receiver = environment.take_event_receiver(None).await?;
-    let (_dummy_tx, _) = mpsc::unbounded_channel::<String>();
+// What handle_events should do with a single event received by an agent node
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoutingDecision {
+    ForwardToGui(String), // Send the text straight to the GUI response channel
+    LetAgentHandle,       // Leave the event for the agent itself to process
+    Ignore,
+}
 
-    // Use the regular handle_events function for camera responses
-    let (camera_response_tx, _) = mpsc::unbounded_channel::<String>();
-    println!("📷 Setting up CameraAgent event handler...");
-    handle_events(receiver, camera_response_tx, runtime.clone(), false);
+// Markers that identify a task as an analysis result rather than a new user query
+const ANALYSIS_RESULT_MARKERS: [&str; 5] = [
+    "Analysis Report",
+    "Key Insights",
+    "Strategic Recommendations",
+    "Executive Summary",
+    "RESEARCH DATA FOR ANALYSIS",
+];
 
-    // Spawn environment runner in background
-    let _env_handle = tokio::spawn(async move {
-        if let Err(e) = environment.run().await {
-            eprintln!("Environment error: {}", e);
+fn is_analysis_result(prompt: &str) -> bool {
+    prompt.starts_with("### ")
+        || ANALYSIS_RESULT_MARKERS
+            .iter()
+            .any(|marker| prompt.contains(marker))
+}
+
+pub fn classify_and_route(event: &Event, is_analysis_agent: bool) -> RoutingDecision {
+    match event {
+        Event::NewTask { task, .. } => {
+            let (_, prompt) = correlation::split(&task.prompt);
+            // Only forward analysis results, not user-initiated tasks, to avoid infinite loops
+            if !is_analysis_agent && is_analysis_result(prompt) {
+                RoutingDecision::ForwardToGui(prompt.to_string())
+            } else {
+                RoutingDecision::LetAgentHandle
+            }
         }
-    });
+        Event::TaskComplete {
+            actor_name,
+            result: TaskResult::Value(val),
+            ..
+        } => {
+            // First try to parse as ReActAgentOutput, then fall back to a plain string
+            if let Ok(out) = serde_json::from_value::<ReActAgentOutput>(val.clone()) {
+                RoutingDecision::ForwardToGui(out.response)
+            } else if let Ok(out) = serde_json::from_value::<String>(val.clone()) {
+                if is_analysis_agent {
+                    RoutingDecision::Ignore
+                } else {
+                    RoutingDecision::ForwardToGui(out)
+                }
+            } else if val.is_null() || is_analysis_agent || actor_name != DOCTOR_AGENT_NAME {
+                // Helper agents (roster, registry, acks, waveforms) finish every task with no
+                // output; only the doctor's own answer needs a fallback
+                RoutingDecision::Ignore
+            } else {
+                eprintln!(
+                    "⚠️ Task result is neither a ReAct output nor a string: {}",
+                    val
+                );
+                RoutingDecision::ForwardToGui(unparsed_response(val))
+            }
+        }
+        _ => RoutingDecision::Ignore,
+    }
+}
 
-    println!(
-        "🌐 ClusterClientRuntime will connect to cluster host at {}",
-        host_addr
-    );
+// Name the DoctorAgent's completions carry (its #[agent] name)
+const DOCTOR_AGENT_NAME: &str = "doctor_agent";
 
-    println!("📷 CameraAgent ready to analyze images for medical queries...");
-    println!("📷 CameraAgent subscribed to topic: camera_requests");
-    println!("📷 CameraAgent runtime: {:?}", runtime);
-    println!("📷 Camera capture methods: ImageSnap (primary), FFmpeg (fallback)");
+const UNEXPECTED_RESPONSE_NOTICE: &str =
+    "⚠️ Received an unexpected response format from the agent.";
 
-    // Keep running until Ctrl+C
-    tokio::signal::ctrl_c()
-        .await
-        .expect("Failed to listen for Ctrl+C");
-    println!("📷 Shutting down CameraAgent...");
-    if let Err(e) = runtime.stop().await {
-        eprintln!("Error stopping runtime: {}", e);
+// Shown when the doctor's answer can't be parsed, so the user isn't left without one
+fn unparsed_response(val: &Value) -> String {
+    format!(
+        "{}\n\n{}",
+        UNEXPECTED_RESPONSE_NOTICE,
+        serde_json::to_string_pretty(val).unwrap_or_else(|_| val.to_string())
+    )
+}
+
+// Which agent a forwarded response came from. Results from the camera and analysis nodes arrive
+// as tasks on their response topics; the event doesn't name the topic, so the report heading
+// tells them apart. A completed task is the doctor's own ReAct answer.
+pub fn response_source(event: &Event) -> Option<String> {
+    match event {
+        Event::NewTask { task, .. } => {
+            let (_, prompt) = correlation::split(&task.prompt);
+            let source = if prompt.starts_with("### Camera") {
+                "camera"
+            } else {
+                "analysis"
+            };
+            Some(source.to_string())
+        }
+        Event::TaskComplete { .. } => Some("doctor".to_string()),
+        _ => None,
     }
+}
 
-    Ok(())
+// The user question a camera result answers, from the correlation id the camera node echoed
+pub fn replied_question(event: &Event) -> Option<String> {
+    match event {
+        Event::NewTask { task, .. } => take_camera_request(correlation::split(&task.prompt).0?),
+        _ => None,
+    }
+}
+
+// Longest tool argument or observation shown in a reasoning step
+const REASONING_STEP_CHARS: usize = 300;
+
+// Describe a ReAct tool step for the GUI's reasoning view; other events aren't steps
+pub fn reasoning_step(event: &Event) -> Option<String> {
+    let clip = |value: String| -> String {
+        if value.chars().count() > REASONING_STEP_CHARS {
+            let clipped: String = value.chars().take(REASONING_STEP_CHARS).collect();
+            format!("{}…", clipped)
+        } else {
+            value
+        }
+    };
+    match event {
+        Event::ToolCallRequested {
+            tool_name,
+            arguments,
+            ..
+        } => Some(format!(
+            "🔧 Action: {}({})",
+            tool_name,
+            clip(arguments.to_string())
+        )),
+        Event::ToolCallCompleted {
+            tool_name, result, ..
+        } => Some(format!(
+            "👁 Observation from {}: {}",
+            tool_name,
+            clip(ToolResult::describe(result))
+        )),
+        Event::ToolCallFailed {
+            tool_name, error, ..
+        } => Some(format!(
+            "⚠️ {} failed: {}",
+            tool_name,
+            clip(error.to_string())
+        )),
+        _ => None,
+    }
+}
+
+// User-facing text for a failed call to a tool that reaches another node; failures of local
+// tools are left to the agent's own answer
+fn tool_failure_message(tool_name: &str, error: &str) -> Option<String> {
+    let service = match tool_name {
+        "ecg_analysis_tool" => "the analysis service",
+        "camera_analysis" => "the camera service",
+        _ => return None,
+    };
+    Some(format!("⚠️ Couldn't reach {}: {}", service, error))
 }
 
-fn handle_events(
+pub(crate) fn handle_events(
     mut event_stream: ReceiverStream<Event>,
-    response_sender: mpsc::UnboundedSender<String>,
+    response_sender: mpsc::UnboundedSender<AgentResponse>,
     _runtime: Arc<dyn Runtime>,
     is_analysis_agent: bool,
+    metrics: Option<TopicMetrics>,
+    reasoning_sender: Option<mpsc::UnboundedSender<String>>,
 ) {
     tokio::spawn(async move {
         let agent_type = if is_analysis_agent {
@@ -739,45 +3572,58 @@ fn handle_events(
                 "{}",
                 format!("{} Received event: {:?}", agent_type, event).cyan()
             );
-            match event {
-                Event::NewTask { actor_id: _, task } => {
-                    println!("{}", format!("📨 New TASK: {:?}", task).green());
-
-                    // Only forward user-initiated tasks, not analysis results, to avoid infinite loops
-                    if !is_analysis_agent {
-                        // Check if this is an analysis result that should be sent directly to GUI
-                        if task.prompt.starts_with("### ")
-                            || task.prompt.contains("Analysis Report")
-                            || task.prompt.contains("Key Insights")
-                            || task.prompt.contains("Strategic Recommendations")
-                            || task.prompt.contains("Executive Summary")
-                            || task.prompt.contains("RESEARCH DATA FOR ANALYSIS")
-                        {
-                            println!("📊 Received analysis result, sending directly to GUI");
-                            match response_sender.send(task.prompt) {
-                                Ok(_) => {
-                                    println!("✅ Successfully sent analysis result to GUI channel")
-                                }
-                                Err(e) => {
-                                    eprintln!("❌ Failed to send analysis result to GUI: {}", e)
-                                }
-                            }
-                        } else {
-                            println!(
-                                "🔄 Doctor agent received new user task, forwarding to agent: {}",
-                                task.prompt
-                            );
-                            // This is a regular user query - let it be processed by the agent
-                            // Don't send to GUI here, let the agent handle it
-                        }
+            // Count every task routed through this node per topic
+            if let (Some(metrics), Event::PublishMessage { topic_name, .. }) = (&metrics, &event) {
+                metrics.increment(topic_name);
+            }
+            // The host sees every announcement it routes and keeps the capability registry
+            if let Event::PublishMessage {
+                topic_name,
+                message,
+                ..
+            } = &event
+            {
+                if *topic_name == topics::name(topics::AGENT_ANNOUNCE) {
+                    if let Some(task) = message.downcast_ref::<Task>() {
+                        registry::record_payload(&task.prompt);
                     }
                 }
-                Event::ToolCallRequested {
-                    id: _,
-                    tool_name,
-                    arguments: _,
-                } => {
+            }
+            if let (Some(sender), Some(step)) = (&reasoning_sender, reasoning_step(&event)) {
+                let _ = sender.send(step);
+            }
+
+            match &event {
+                Event::NewTask { task, .. } => {
+                    let (correlation_id, _) = correlation::split(&task.prompt);
+                    println!(
+                        "{}",
+                        format!(
+                            "📨 [{}] New TASK: {:?}",
+                            correlation::display(correlation_id),
+                            task
+                        )
+                        .green()
+                    );
+                    telemetry::lifecycle("task_received", "-", correlation_id, None);
+                }
+                Event::ToolCallRequested { tool_name, .. } => {
                     println!("{}", format!("📨 New TOOL CALL: {}", tool_name).green());
+                    telemetry::lifecycle("tool_called", tool_name, None, None);
+                }
+                Event::ToolCallFailed {
+                    tool_name, error, ..
+                } => {
+                    eprintln!("❌ Tool call {} failed: {}", tool_name, error);
+                    telemetry::lifecycle("tool_failed", tool_name, None, None);
+                    // A tripped tool-call limit already told the user why the query stopped
+                    if let Some(message) = tool_failure_message(tool_name, &error.to_string())
+                        .filter(|_| !tool_call_limit_tripped())
+                    {
+                        if let Err(e) = response_sender.send(AgentResponse::error(message)) {
+                            eprintln!("❌ Failed to send tool error to GUI: {}", e);
+                        }
+                    }
                 }
                 Event::TaskComplete {
                     result: TaskResult::Value(val),
@@ -787,72 +3633,115 @@ fn handle_events(
                         "{}",
                         format!("🎯 Task completed with value: {:?}", val).blue()
                     );
+                }
+                _ => {
+                    println!("{}", "🔄 Other event received".cyan());
+                }
+            }
 
-                    // First try to parse as ReActAgentOutput
-                    match serde_json::from_value::<ReActAgentOutput>(val.clone()) {
-                        Ok(out) => {
-                            println!(
-                                "{}",
-                                format!("✅ Agent Response (ReAct): {}", out.response).green()
-                            );
-
-                            // Send as-is if it's not JSON
-                            println!("🚀 Sending raw response to GUI: {}", out.response);
-                            match response_sender.send(out.response.clone()) {
-                                Ok(_) => {
-                                    println!("✅ Successfully sent raw response to GUI channel")
-                                }
-                                Err(e) => eprintln!("❌ Failed to send response to GUI: {}", e),
-                            }
-                        }
-                        Err(_) => {
-                            // Try to parse as string
-                            match serde_json::from_value::<String>(val.clone()) {
-                                Ok(out) => {
-                                    println!(
-                                        "{}",
-                                        format!("✅ Agent Response (String): {}", out).green()
-                                    );
-                                    // Send directly to GUI channel instead of publishing to cluster
-                                    println!("🚀 Sending string response directly to GUI: {}", out);
-                                    if !is_analysis_agent {
-                                        match response_sender.send(out) {
-                                            Ok(_) => println!(
-                                                "✅ Successfully sent string response to GUI channel"
-                                            ),
-                                            Err(e) => eprintln!(
-                                                "❌ Failed to send string response to GUI: {}",
-                                                e
-                                            ),
-                                        }
-                                    }
-                                }
-                                Err(_) => {}
-                            }
+            let decision = classify_and_route(&event, is_analysis_agent);
+            event_log::record(&event, is_analysis_agent, &decision);
+            match decision {
+                RoutingDecision::ForwardToGui(response) => {
+                    println!("🚀 Sending response to GUI: {}", response);
+                    // A report (or error report) for a tracked analysis or camera request; it
+                    // needn't be sent again, and the active query no longer waits for it
+                    if let Event::NewTask { task, .. } = &event {
+                        if let Some(id) = correlation::split(&task.prompt).0 {
+                            delivery::resolve(id);
+                            result_arrived(id);
                         }
                     }
+                    let response = AgentResponse {
+                        content: response,
+                        agent_source: response_source(&event),
+                        is_error: false,
+                        image: None,
+                        in_reply_to: replied_question(&event),
+                        is_final: matches!(event, Event::TaskComplete { .. })
+                            && !awaiting_results(),
+                    };
+                    match response_sender.send(response) {
+                        Ok(_) => println!("✅ Successfully sent response to GUI channel"),
+                        Err(e) => eprintln!("❌ Failed to send response to GUI: {}", e),
+                    }
                 }
-                _ => {
-                    println!("{}", format!("🔄 Other event received").cyan());
+                RoutingDecision::LetAgentHandle => {
+                    println!("🔄 {} forwarding task to agent", agent_type);
                 }
+                RoutingDecision::Ignore => {}
             }
         }
     });
 }
 
-pub async fn run_cluster_host(node_name: String, port: u16, host: String) -> Result<(), Error> {
-    println!("🏠 Initializing ClusterHostRuntime on port {}", port);
+pub async fn run_cluster_host(
+    node_name: String,
+    port: u16,
+    bind: String,
+    metrics_port: Option<u16>,
+) -> Result<(), Error> {
+    println!("🏠 Initializing ClusterHostRuntime on {}:{}", bind, port);
+    runner::validate_host(&bind).map_err(Error::CustomError)?;
 
     // Create cluster host runtime - this coordinates all client connections and routes events
-    let runtime = ClusterHostRuntime::new(node_name, "cluster-cookie".to_string(), port, host);
+    let runtime = ClusterHostRuntime::new(node_name, runner::cluster_cookie(), port, bind);
 
     // Create environment and set up event handling
     let mut environment = Environment::new(None);
     let _ = environment.register_runtime(runtime.clone()).await;
 
-    let receiver = environment.take_event_receiver(None).await?;
-    let (dummy_tx, _) = mpsc::unbounded_channel::<String>();
-    handle_events(receiver, dummy_tx, runtime.clone(), false);
+    // Agents from the previous run (with --state-file) should announce themselves again soon
+    let expected = registry::restore_state();
+    if !expected.is_empty() {
+        println!(
+            "🧭 Expecting agents from the last run to reconnect: {}",
+            expected.join(", ")
+        );
+        tokio::spawn(async move {
+            sleep(registry::RECONNECT_GRACE).await;
+            let missing = registry::give_up_on_reconnects();
+            if !missing.is_empty() {
+                println!(
+                    "⚠️ Agents from the last run haven't reconnected after {}s, forgetting them: {}",
+                    registry::RECONNECT_GRACE.as_secs(),
+                    missing.join(", ")
+                );
+            }
+        });
+    }
+
+    let topic_metrics = TopicMetrics::new();
+    let receiver = runner::take_event_stream(&mut environment, "🏠 ClusterHost").await?;
+    let (dummy_tx, _) = mpsc::unbounded_channel::<AgentResponse>();
+    handle_events(
+        receiver,
+        dummy_tx,
+        runtime.clone(),
+        false,
+        Some(topic_metrics.clone()),
+        None,
+    );
+
+    // Log a per-topic summary periodically for capacity planning
+    let summary_metrics = topic_metrics.clone();
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_secs(30)).await;
+            println!("{}", summary_metrics.summary_table());
+            println!("{}", registry::summary());
+        }
+    });
+
+    // Optionally expose the counters over HTTP for Prometheus scraping
+    if let Some(metrics_port) = metrics_port {
+        let endpoint_metrics = topic_metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve_metrics(endpoint_metrics, metrics_port).await {
+                eprintln!("❌ Metrics endpoint error: {}", e);
+            }
+        });
+    }
 
     // Start the runtime and environment
     tokio::spawn(async move {
@@ -868,9 +3757,574 @@ pub async fn run_cluster_host(node_name: String, port: u16, host: String) -> Res
         .await
         .expect("Failed to listen for Ctrl+C");
     println!("🏠 Shutting down ClusterHostRuntime...");
+    println!("{}", topic_metrics.summary_table());
+    println!("{}", registry::summary());
     if let Err(e) = runtime.stop().await {
         eprintln!("Error stopping runtime: {}", e);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn new_task(prompt: &str) -> Event {
+        Event::NewTask {
+            actor_id: Uuid::new_v4(),
+            task: Task::new(prompt.to_string()),
+        }
+    }
+
+    fn task_complete(value: Value) -> Event {
+        Event::TaskComplete {
+            sub_id: Uuid::new_v4(),
+            actor_id: Uuid::new_v4(),
+            actor_name: "doctor_agent".to_string(),
+            result: TaskResult::Value(value),
+        }
+    }
+
+    // Accept one HTTP request on a loopback port, answer it with `response` and return the
+    // request (head and body) as text
+    async fn capture_one_request(
+        response: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let url = format!("http://{}/v1/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 8192];
+            loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                let Some(head_end) = text.find("\r\n\r\n") else {
+                    assert!(read > 0, "connection closed before the request head");
+                    continue;
+                };
+                let content_length = text[..head_end]
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if request.len() >= head_end + 4 + content_length || read == 0 {
+                    break;
+                }
+            }
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            );
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+        (url, server)
+    }
+
+    // The Anthropic backend always posts to api.anthropic.com, so only the OpenAI backend's
+    // request can be captured without a network call
+    #[tokio::test]
+    async fn openai_backend_sends_the_capture_as_a_base64_jpeg_data_url() {
+        use autoagents::llm::backends::openai::OpenAI;
+        use autoagents::llm::builder::LLMBuilder;
+        use base64::{Engine, engine::general_purpose::STANDARD};
+
+        let (url, server) = capture_one_request(
+            r#"{"id":"chatcmpl-1","object":"chat.completion","created":0,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"The patient is awake and sitting up."},"finish_reason":"stop"}],"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#,
+        )
+        .await;
+        let llm: Arc<dyn LLMProvider> = LLMBuilder::<OpenAI>::new()
+            .api_key("test-key")
+            .base_url(url)
+            .model("gpt-4o")
+            .build()
+            .unwrap();
+        let image = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46];
+        let messages =
+            build_camera_messages("camera agent", "Is the patient awake?", image.clone());
+
+        let response = llm.chat(&messages, None, None).await.unwrap();
+        assert_eq!(
+            response.text().as_deref(),
+            Some("The patient is awake and sitting up.")
+        );
+
+        let request = server.await.unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /v1/chat/completions"));
+        assert!(
+            head.to_lowercase()
+                .contains("authorization: bearer test-key")
+        );
+        let body: Value = serde_json::from_str(body).unwrap();
+        let user = body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|message| message["role"] == "user")
+            .unwrap();
+        let parts = user["content"].as_array().unwrap();
+        assert!(parts.iter().any(|part| {
+            part["type"] == "image_url"
+                && part["image_url"]["url"]
+                    == format!("data:image/jpeg;base64,{}", STANDARD.encode(&image))
+        }));
+    }
+
+    #[test]
+    fn reports_with_unicode_symbols_round_trip() {
+        let dir = std::env::temp_dir().join(format!("liquidos-reports-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let report = "### ECG Analysis Report\nST elevation 150 µV, QRS axis +60°, ΔQT ≈ 20 ms ✅";
+        let path = dir.join("report_unicode.md");
+        write_report(&path, report).unwrap();
+        assert_eq!(read_report(&path).unwrap(), report);
+
+        // Latin-1 encoded "µV" is not valid UTF-8 and is replaced rather than failing the read
+        let latin1 = dir.join("report_latin1.md");
+        fs::write(&latin1, b"150 \xB5V").unwrap();
+        assert_eq!(read_report(&latin1).unwrap(), "150 \u{FFFD}V");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn uploaded_image_round_trips_through_task_prompt() {
+        let upload = UploadedImage {
+            query: "Is this ECG normal?".to_string(),
+            file_name: "ecg.png".to_string(),
+            bytes: vec![0x89, 0x50, 0x4E, 0x47, 0x00, 0xFF],
+        };
+
+        assert_eq!(UploadedImage::decode(&upload.encode()), Ok(upload));
+        assert!(UploadedImage::decode("not json").is_err());
+    }
+
+    #[test]
+    fn logged_prompts_show_image_size_instead_of_bytes() {
+        let messages =
+            build_camera_messages("camera agent", "Is the patient awake?", vec![0xFF; 2048]);
+        let logged = describe_prompt(&messages);
+
+        assert!(logged.contains("Is the patient awake?"));
+        assert!(logged.contains("<image/jpeg image, 2048 bytes>"));
+        assert!(!logged.contains("255"));
+    }
+
+    #[test]
+    fn text_fallback_messages_are_text_only_with_caveat() {
+        let messages = build_text_fallback_messages("camera agent", "Is the patient awake?");
+
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[1].message_type, MessageType::Text));
+        assert!(
+            messages[1]
+                .content
+                .contains("Camera capture was unavailable")
+        );
+        assert!(messages[1].content.contains("Is the patient awake?"));
+    }
+
+    #[test]
+    fn camera_history_goes_between_the_system_prompt_and_the_new_query() {
+        let turn = |role, content: &str| ChatMessage {
+            role,
+            message_type: MessageType::Text,
+            content: content.to_string(),
+        };
+        let history = vec![
+            turn(
+                ChatRole::User,
+                "[Captured image cap_p-17_is-the-patient-awake_1.jpg] Is the patient awake?",
+            ),
+            turn(ChatRole::Assistant, "Eyes open, responsive."),
+        ];
+        let messages = with_history(
+            build_camera_messages("camera agent", "Compare to the last photo", vec![0xFF]),
+            history,
+        );
+
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(messages[0].role, ChatRole::System));
+        assert!(contents[1].contains("cap_p-17_is-the-patient-awake_1.jpg"));
+        assert_eq!(contents[2], "Eyes open, responsive.");
+        assert!(matches!(messages[3].message_type, MessageType::Image(_)));
+    }
+
+    #[test]
+    fn capture_file_names_carry_the_patient_and_a_query_slug() {
+        assert_eq!(
+            capture_file_stem(Some("P-17"), "Is the patient's IV line OK?", 1700000000),
+            "cap_p-17_is-the-patient-s-iv-line-ok_1700000000"
+        );
+        assert_eq!(
+            capture_file_stem(None, "¿?", 1700000000),
+            "cap_nopatient_query_1700000000"
+        );
+        let long = capture_file_stem(Some("../../etc"), &"word ".repeat(30), 1);
+        assert!(!long.contains('/') && !long.contains('.'));
+        assert!(long.len() <= "cap___1".len() + 2 * CAPTURE_SLUG_CHARS);
+        assert_eq!(
+            patient::id_from_summary("Patient P-17, age 54, notes: post-op"),
+            Some("P-17")
+        );
+    }
+
+    #[test]
+    fn camera_permission_errors_are_told_apart_from_other_failures() {
+        assert!(is_permission_denied(
+            b"Error: Camera access Not Authorized for this application"
+        ));
+        assert!(is_permission_denied(
+            b"[AVFoundation indev @ 0x7f] Failed to create AV capture input device: Not authorized to capture video"
+        ));
+        assert!(!is_permission_denied(b"imagesnap: command not found"));
+        assert!(!is_permission_denied(
+            b"[avfoundation @ 0x7f] Video device not found"
+        ));
+    }
+
+    #[test]
+    fn empty_short_or_malformed_llm_answers_are_not_usable() {
+        assert!(!is_usable_response("", false));
+        assert!(!is_usable_response("   OK   ", false));
+        assert!(is_usable_response("Normal sinus rhythm at 72 bpm.", false));
+        assert!(!is_usable_response("Normal sinus rhythm at 72 bpm.", true));
+        assert!(is_usable_response(
+            r#"{"rhythm": "normal sinus", "rate": 72}"#,
+            true
+        ));
+    }
+
+    #[test]
+    fn retries_raise_the_temperature_up_to_a_ceiling() {
+        assert_eq!(retry_temperature(0.2, 1), 0.2);
+        assert!((retry_temperature(0.2, 2) - 0.4).abs() < 1e-6);
+        assert!((retry_temperature(0.2, 3) - 0.6).abs() < 1e-6);
+        assert_eq!(retry_temperature(0.9, 3), MAX_RETRY_TEMPERATURE);
+        // Never lower than what the user chose
+        assert_eq!(retry_temperature(1.5, 3), 1.5);
+    }
+
+    #[test]
+    fn compare_reports_are_taken_as_text_unless_they_name_a_report_file() {
+        let report = "### ECG Analysis Report\nKey Insights: sinus rhythm";
+        assert_eq!(resolve_report(report), report);
+        // Paths outside the reports directory are never read
+        assert_eq!(resolve_report("../Cargo.toml"), "../Cargo.toml");
+
+        let messages = build_comparison_messages("rate 72 bpm", "rate 110 bpm");
+        assert!(messages[1].content.contains("### Worsened findings"));
+        assert!(messages[1].content.contains("rate 110 bpm"));
+    }
+
+    #[test]
+    fn new_task_with_analysis_marker_is_forwarded_to_gui() {
+        let report = "### ECG Analysis Report\nKey Insights: sinus rhythm";
+        assert_eq!(
+            classify_and_route(&new_task(report), false),
+            RoutingDecision::ForwardToGui(report.to_string())
+        );
+    }
+
+    #[test]
+    fn new_task_with_analysis_marker_is_left_to_analysis_agent() {
+        let report = "Executive Summary of the ECG";
+        assert_eq!(
+            classify_and_route(&new_task(report), true),
+            RoutingDecision::LetAgentHandle
+        );
+    }
+
+    #[test]
+    fn tagged_analysis_result_is_forwarded_without_correlation_tag() {
+        let report = "### Camera Analysis Result\nPatient is resting";
+        let tagged = correlation::tag(correlation::new_id(), report);
+        assert_eq!(
+            classify_and_route(&new_task(&tagged), false),
+            RoutingDecision::ForwardToGui(report.to_string())
+        );
+    }
+
+    #[test]
+    fn camera_results_name_the_question_that_requested_them() {
+        let id = correlation::new_id();
+        remember_camera_request(id, "Is the patient awake?".to_string());
+        let result = correlation::tag(id, "### Camera Analysis Result\nEyes open");
+        assert_eq!(
+            replied_question(&new_task(&result)),
+            Some("Is the patient awake?".to_string())
+        );
+        // Matched once; untagged and unknown results aren't replies
+        assert_eq!(replied_question(&new_task(&result)), None);
+        assert_eq!(
+            replied_question(&new_task("### Camera Analysis Result\nEyes open")),
+            None
+        );
+    }
+
+    #[test]
+    fn new_task_user_query_is_left_to_agent() {
+        assert_eq!(
+            classify_and_route(&new_task("How is the patient's heart rate?"), false),
+            RoutingDecision::LetAgentHandle
+        );
+    }
+
+    #[test]
+    fn task_complete_with_react_output_is_forwarded() {
+        let value = json!({
+            "response": "The ECG shows normal sinus rhythm.",
+            "tool_calls": [],
+            "done": true,
+        });
+        assert_eq!(
+            classify_and_route(&task_complete(value), false),
+            RoutingDecision::ForwardToGui("The ECG shows normal sinus rhythm.".to_string())
+        );
+    }
+
+    #[test]
+    fn task_complete_with_plain_string_is_forwarded_by_doctor_only() {
+        let value = json!("Analysis finished");
+        assert_eq!(
+            classify_and_route(&task_complete(value.clone()), false),
+            RoutingDecision::ForwardToGui("Analysis finished".to_string())
+        );
+        assert_eq!(
+            classify_and_route(&task_complete(value), true),
+            RoutingDecision::Ignore
+        );
+    }
+
+    #[test]
+    fn task_complete_with_unparseable_value_still_answers_the_user() {
+        let value = json!({"answer": "sinus rhythm", "confidence": 0.9});
+        match classify_and_route(&task_complete(value.clone()), false) {
+            RoutingDecision::ForwardToGui(text) => {
+                assert!(text.starts_with(UNEXPECTED_RESPONSE_NOTICE));
+                assert!(text.contains("\"answer\": \"sinus rhythm\""));
+            }
+            other => panic!("expected a forwarded fallback, got {:?}", other),
+        }
+        assert_eq!(
+            classify_and_route(&task_complete(value.clone()), true),
+            RoutingDecision::Ignore
+        );
+
+        // Unit-output helper agents complete with null, and other agents' values aren't answers
+        assert_eq!(
+            classify_and_route(&task_complete(Value::Null), false),
+            RoutingDecision::Ignore
+        );
+        let roster_done = Event::TaskComplete {
+            sub_id: Uuid::new_v4(),
+            actor_id: Uuid::new_v4(),
+            actor_name: "roster_agent".to_string(),
+            result: TaskResult::Value(value),
+        };
+        assert_eq!(
+            classify_and_route(&roster_done, false),
+            RoutingDecision::Ignore
+        );
+    }
+
+    #[test]
+    fn tool_call_guard_trips_only_past_the_limit_per_tool() {
+        let mut guard = ToolCallGuard::new(2, CancellationToken::new());
+        assert!(guard.record("ecg_analysis_tool").is_ok());
+        assert!(guard.record("ecg_analysis_tool").is_ok());
+        assert!(guard.record("camera_analysis").is_ok());
+        let error = guard.record("ecg_analysis_tool").unwrap_err();
+        assert!(error.contains("ecg_analysis_tool"));
+        assert!(error.contains("limit 2"));
+    }
+
+    #[test]
+    fn every_doctor_tool_can_have_its_description_overridden() {
+        let compiled = doctor_tool_descriptions(&tool_docs::Overrides::new());
+        assert_eq!(compiled.len(), DOCTOR_TOOLS.len());
+        assert!(!compiled[0].1.contains("analyssi"));
+
+        let overrides: BTreeMap<&str, String> = compiled
+            .iter()
+            .map(|(name, _)| (*name, format!("Site note for {}.", name)))
+            .collect();
+        let path = std::env::temp_dir().join(format!(
+            "liquidos-doctor-tool-descriptions-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, serde_json::to_string(&overrides).unwrap()).unwrap();
+        let overrides = tool_docs::read(&path, DOCTOR_TOOLS).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for (name, description) in doctor_tool_descriptions(&overrides) {
+            assert_eq!(description, format!("Site note for {}.", name));
+        }
+    }
+
+    #[test]
+    fn timed_out_analyses_are_reported_as_analysis_errors() {
+        let report = timeout_report("Check rhythm", Duration::from_secs(90));
+        assert!(report.starts_with("### ECG Analysis Error"));
+        assert!(report.contains("'Check rhythm'") && report.contains("90 seconds"));
+        // Routed to the GUI like any other analysis result
+        assert!(matches!(
+            classify_and_route(&new_task(&report), false),
+            RoutingDecision::ForwardToGui(_)
+        ));
+    }
+
+    #[test]
+    fn http_timeouts_are_recognized_as_llm_timeouts() {
+        assert!(is_timeout_error(
+            "HTTP request failed: error sending request: operation timed out"
+        ));
+        assert!(is_timeout_error("Request Timeout"));
+        assert!(!is_timeout_error("401 Unauthorized: invalid api key"));
+        assert!(
+            llm_timed_out(Duration::from_secs(30))
+                .to_string()
+                .contains("The AI service timed out after 30s")
+        );
+    }
+
+    #[test]
+    fn tool_results_are_machine_readable_and_described_briefly() {
+        let value = ToolResult::new(ToolStatus::Submitted, "Analysis request submitted")
+            .with_data(json!({ "correlation_id": "abc" }))
+            .into_value();
+        assert_eq!(value["status"], "submitted");
+        assert_eq!(value["data"]["correlation_id"], "abc");
+        assert_eq!(
+            ToolResult::describe(&value),
+            "[submitted] Analysis request submitted"
+        );
+
+        let declined = ToolResult::new(ToolStatus::Declined, "No image was taken").into_value();
+        assert!(declined.get("data").is_none());
+        // Other tools still return plain values
+        assert_eq!(ToolResult::describe(&json!("3 reports")), "\"3 reports\"");
+    }
+
+    #[test]
+    fn only_failures_of_remote_tools_become_error_bubbles() {
+        assert_eq!(
+            tool_failure_message("ecg_analysis_tool", "connection refused").as_deref(),
+            Some("⚠️ Couldn't reach the analysis service: connection refused")
+        );
+        assert!(
+            tool_failure_message("camera_analysis", "timeout")
+                .unwrap()
+                .contains("the camera service")
+        );
+        assert!(tool_failure_message("fetch_past_reports", "no such file").is_none());
+        assert!(AgentResponse::error("failed").is_error);
+        assert!(!AgentResponse::notice("queued").is_error);
+    }
+
+    #[test]
+    fn camera_results_for_the_analysis_agent_are_marked_as_observations() {
+        let observation = camera_result_prompt(topics::ANALYSIS_REQUESTS, "Patient is resting");
+        assert!(observation.starts_with(CAMERA_OBSERVATION_HEADING));
+        assert!(observation.ends_with("Patient is resting"));
+        assert_eq!(
+            camera_result_prompt(topics::CAMERA_RESPONSE, "Patient is resting"),
+            "### Camera Analysis Result\nPatient is resting"
+        );
+    }
+
+    #[test]
+    fn forwarded_responses_name_the_agent_that_wrote_them() {
+        let camera = correlation::tag(
+            correlation::new_id(),
+            "### Camera Analysis Result
+ok",
+        );
+        assert_eq!(
+            response_source(&new_task(&camera)).as_deref(),
+            Some("camera")
+        );
+        assert_eq!(
+            response_source(&new_task(
+                "### ECG Analysis Report
+ok"
+            ))
+            .as_deref(),
+            Some("analysis")
+        );
+        assert_eq!(
+            response_source(&task_complete(json!("done"))).as_deref(),
+            Some("doctor")
+        );
+    }
+
+    #[test]
+    fn conversation_history_restores_queries_and_agent_answers_only() {
+        let history = vec![
+            StoredMessage {
+                content: "Welcome".to_string(),
+                ..Default::default()
+            },
+            StoredMessage {
+                content: "Check bed 4".to_string(),
+                is_user: true,
+                ..Default::default()
+            },
+            StoredMessage {
+                content: "Sinus rhythm".to_string(),
+                agent_source: Some("🔍 DoctorAgent".to_string()),
+                ..Default::default()
+            },
+            StoredMessage {
+                content: "Analysis timed out".to_string(),
+                agent_source: Some("🔍 DoctorAgent".to_string()),
+                is_error: true,
+                ..Default::default()
+            },
+        ];
+        let memory = memory_from_history(&history);
+        assert_eq!(memory.len(), 2);
+        assert!(matches!(memory[0].role, ChatRole::User));
+        assert_eq!(memory[0].content, "Check bed 4");
+        assert!(matches!(memory[1].role, ChatRole::Assistant));
+        assert_eq!(memory[1].content, "Sinus rhythm");
+    }
+
+    #[test]
+    fn a_disabled_camera_is_left_out_of_the_doctor_prompt() {
+        let prompt = DoctorAgent { max_turns: 1 }.description();
+        assert!(prompt.contains("camera"));
+        let prompt = without_camera_instructions(prompt);
+        assert!(prompt.contains("fetch_past_reports"));
+        let (instructions, note) = prompt.rsplit_once("\n\n").unwrap();
+        assert!(!instructions.to_lowercase().contains("camera"));
+        assert!(note.contains("disabled by site policy"));
+    }
+
+    #[test]
+    fn doctor_prompt_names_the_tag_results_start_with() {
+        let tagged = correlation::tag(correlation::new_id(), "### ECG Analysis Report");
+        let tag_start = &tagged[..=tagged.find('=').unwrap()];
+        assert!(
+            DoctorAgent { max_turns: 1 }
+                .description()
+                .contains(tag_start)
+        );
+    }
+}
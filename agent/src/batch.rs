@@ -0,0 +1,274 @@
+// batch-analyze: every ECG sample file in a directory through the local rhythm estimate and
+// the AnalysisAgent's LLM analysis, one report per file plus summary.csv with the key metrics.
+// A file that fails is recorded in the summary and the batch moves on.
+use crate::agents;
+use crate::rhythm::{self, RhythmSummary};
+use autoagents::llm::LLMProvider;
+use futures::StreamExt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Extensions picked up from the input directory; anything else is skipped
+const ECG_EXTENSIONS: [&str; 3] = ["csv", "txt", "dat"];
+
+pub const SUMMARY_FILE: &str = "summary.csv";
+
+const SUMMARY_HEADER: &str = "file,status,heart_rate_bpm,rate,regular,mean_rr_ms,rr_variation,beats,duration_secs,report,error";
+
+#[derive(Debug, Clone)]
+pub struct BatchSettings {
+    pub output_dir: PathBuf,
+    pub concurrency: usize,
+    pub sample_rate_hz: f64,
+    pub dry_run: bool, // Local rhythm estimate only, no LLM calls
+}
+
+// One line of summary.csv
+#[derive(Debug, Clone, PartialEq)]
+struct BatchRow {
+    file: String,
+    rhythm: Option<RhythmSummary>,
+    report: Option<PathBuf>,
+    error: Option<String>,
+}
+
+impl BatchRow {
+    fn to_csv(&self) -> String {
+        let status = if self.error.is_some() { "error" } else { "ok" };
+        let metrics = match &self.rhythm {
+            Some(r) => vec![
+                format!("{:.1}", r.heart_rate_bpm),
+                r.rate.label().to_string(),
+                r.regular.to_string(),
+                format!("{:.0}", r.mean_rr_ms),
+                format!("{:.3}", r.rr_variation),
+                r.beats.to_string(),
+                format!("{:.1}", r.duration_secs),
+            ],
+            None => vec![String::new(); 7],
+        };
+        [self.file.clone(), status.to_string()]
+            .into_iter()
+            .chain(metrics)
+            .chain([
+                self.report
+                    .as_ref()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+                self.error.clone().unwrap_or_default(),
+            ])
+            .map(|field| csv_field(&field))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+// Quoted when it contains a separator, quote or line break, with quotes doubled
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// ECG files directly inside `dir`, sorted so reruns process them in the same order
+fn ecg_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ECG_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// Analyze every ECG file in `dir`. Returns the number of files that failed.
+pub async fn run(
+    llm: Option<Arc<dyn LLMProvider>>,
+    dir: &Path,
+    settings: BatchSettings,
+) -> std::io::Result<usize> {
+    let files = ecg_files(dir)?;
+    if files.is_empty() {
+        println!(
+            "⚠️ No ECG files ({}) found in {}",
+            ECG_EXTENSIONS.join(", "),
+            dir.display()
+        );
+        return Ok(0);
+    }
+    fs::create_dir_all(&settings.output_dir)?;
+    println!(
+        "📚 Analyzing {} ECG file(s) from {} with concurrency {}{}",
+        files.len(),
+        dir.display(),
+        settings.concurrency,
+        if settings.dry_run { " (dry run)" } else { "" }
+    );
+
+    let total = files.len();
+    let done = AtomicUsize::new(0);
+    let mut rows: Vec<BatchRow> = futures::stream::iter(files)
+        .map(|path| {
+            let (llm, settings, done) = (llm.clone(), &settings, &done);
+            async move {
+                let row = analyze_file(llm, &path, settings).await;
+                let count = done.fetch_add(1, Ordering::Relaxed) + 1;
+                match &row.error {
+                    None => println!("✅ [{}/{}] {}", count, total, row.file),
+                    Some(e) => eprintln!("❌ [{}/{}] {}: {}", count, total, row.file, e),
+                }
+                row
+            }
+        })
+        .buffer_unordered(settings.concurrency)
+        .collect()
+        .await;
+
+    // Completion order depends on the LLM, the summary follows the input order
+    rows.sort_by(|a, b| a.file.cmp(&b.file));
+    let summary = std::iter::once(SUMMARY_HEADER.to_string())
+        .chain(rows.iter().map(BatchRow::to_csv))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let summary_path = settings.output_dir.join(SUMMARY_FILE);
+    fs::write(&summary_path, summary + "\n")?;
+
+    let failed = rows.iter().filter(|row| row.error.is_some()).count();
+    println!(
+        "📊 {} of {} file(s) analyzed, {} failed. Summary: {}",
+        total - failed,
+        total,
+        failed,
+        summary_path.display()
+    );
+    Ok(failed)
+}
+
+async fn analyze_file(
+    llm: Option<Arc<dyn LLMProvider>>,
+    path: &Path,
+    settings: &BatchSettings,
+) -> BatchRow {
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut row = BatchRow {
+        file,
+        rhythm: None,
+        report: None,
+        error: None,
+    };
+
+    let rhythm = match fs::read_to_string(path)
+        .map_err(|e| format!("could not read the file: {}", e))
+        .and_then(|content| rhythm::parse_samples(&content))
+        .and_then(|samples| rhythm::classify(&samples, settings.sample_rate_hz))
+    {
+        Ok(rhythm) => rhythm,
+        Err(e) => {
+            row.error = Some(e);
+            return row;
+        }
+    };
+    row.rhythm = Some(rhythm.clone());
+
+    let local_estimate = format!("## Rhythm Estimate\n{}", rhythm.describe());
+    let report = match llm.filter(|_| !settings.dry_run) {
+        None => format!("# ECG Report: {}\n\n{}\n", row.file, local_estimate),
+        Some(llm) => {
+            let query = format!(
+                "Analyze the ECG recording {}. {}",
+                path.display(),
+                local_estimate
+            );
+            match agents::analyze_standalone(llm, &query).await {
+                Ok(analysis) => format!("# ECG Report: {}\n\n{}\n", row.file, analysis),
+                Err(e) => {
+                    row.error = Some(format!("analysis failed: {}", e));
+                    return row;
+                }
+            }
+        }
+    };
+
+    // The full file name is kept, so bed4.csv and bed4.txt don't share bed4.md
+    let report_path = settings.output_dir.join(format!("{}.md", row.file));
+    match fs::write(&report_path, report) {
+        Ok(()) => row.report = Some(report_path),
+        Err(e) => row.error = Some(format!("could not write the report: {}", e)),
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beats(rate_per_sec: usize, seconds: usize) -> String {
+        let rate = rhythm::DEFAULT_SAMPLE_RATE_HZ as usize;
+        (0..rate * seconds)
+            .map(|i| {
+                if i % (rate / rate_per_sec) == 0 {
+                    "1.0"
+                } else {
+                    "0.0"
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[tokio::test]
+    async fn dry_run_writes_reports_and_a_summary_without_stopping_on_bad_files() {
+        let dir = std::env::temp_dir().join(format!("batch_test_{}", uuid::Uuid::new_v4()));
+        let output_dir = dir.join("reports");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a_good.csv"), beats(1, 10)).unwrap();
+        fs::write(dir.join("a_good.txt"), beats(1, 10)).unwrap();
+        fs::write(dir.join("b_bad.csv"), "1.0, oops").unwrap();
+        fs::write(dir.join("notes.md"), "not an ECG").unwrap();
+
+        let settings = BatchSettings {
+            output_dir: output_dir.clone(),
+            concurrency: 2,
+            sample_rate_hz: rhythm::DEFAULT_SAMPLE_RATE_HZ,
+            dry_run: true,
+        };
+        let failed = run(None, &dir, settings).await.unwrap();
+        assert_eq!(failed, 1);
+
+        let summary = fs::read_to_string(output_dir.join(SUMMARY_FILE)).unwrap();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], SUMMARY_HEADER);
+        assert!(lines[1].starts_with("a_good.csv,ok,60.0,normal,true,"));
+        assert!(lines[2].starts_with("a_good.txt,ok,"));
+        assert!(lines[3].starts_with("b_bad.csv,error,"));
+        // Same stem, separate reports
+        let csv_report = fs::read_to_string(output_dir.join("a_good.csv.md")).unwrap();
+        let txt_report = fs::read_to_string(output_dir.join("a_good.txt.md")).unwrap();
+        assert!(csv_report.contains("# ECG Report: a_good.csv"));
+        assert!(txt_report.contains("# ECG Report: a_good.txt"));
+        assert!(!output_dir.join("b_bad.csv.md").exists());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn csv_fields_with_separators_are_quoted() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(
+            csv_field("'x, y' is not \"numeric\""),
+            "\"'x, y' is not \"\"numeric\"\"\""
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// Named GUI conversations, one JSON file each under ~/.liquidos/conversations/. Attached images
+// aren't stored; everything else about a message is.
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// Conversation shown when the GUI starts
+pub const DEFAULT_CONVERSATION: &str = "General";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct StoredMessage {
+    pub content: String,
+    pub is_user: bool,
+    pub reasoning: Vec<String>,
+    pub agent_source: Option<String>,
+    pub is_pinned: bool,
+    pub is_error: bool,
+    pub in_reply_to: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Conversation {
+    name: String, // As typed; the file name is a sanitized form of it
+    messages: Vec<StoredMessage>,
+}
+
+// ~/.liquidos/conversations, falling back to %USERPROFILE% on Windows
+fn conversations_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".liquidos").join("conversations"))
+}
+
+// Letters, digits, '-' and '_' kept; anything else becomes '_'
+fn file_stem(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Another conversation whose name maps to the same file, like "Bed 4" and "Bed_4"
+pub fn clashes_with<'a>(name: &str, existing: &'a [String]) -> Option<&'a str> {
+    let stem = file_stem(name);
+    existing
+        .iter()
+        .find(|other| other.as_str() != name.trim() && file_stem(other) == stem)
+        .map(String::as_str)
+}
+
+fn conversation_path(name: &str) -> Option<PathBuf> {
+    Some(conversations_dir()?.join(format!("{}.json", file_stem(name))))
+}
+
+// Names of the saved conversations, sorted, always including the default one
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = conversations_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.ok()?.path()).ok())
+        .filter_map(|content| serde_json::from_str::<Conversation>(&content).ok())
+        .map(|conversation| conversation.name)
+        .collect();
+    if !names.iter().any(|name| name == DEFAULT_CONVERSATION) {
+        names.push(DEFAULT_CONVERSATION.to_string());
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+// Messages of a conversation; empty when it was never saved or can't be read
+pub fn load(name: &str) -> Vec<StoredMessage> {
+    conversation_path(name)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str::<Conversation>(&content).ok())
+        .map(|conversation| conversation.messages)
+        .unwrap_or_default()
+}
+
+pub fn save(name: &str, messages: &[StoredMessage]) {
+    let Some(path) = conversation_path(name) else {
+        return;
+    };
+    let conversation = Conversation {
+        name: name.trim().to_string(),
+        messages: messages.to_vec(),
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| {
+            fs::write(
+                &path,
+                serde_json::to_string_pretty(&conversation).unwrap_or_default(),
+            )
+        });
+    if let Err(e) = result {
+        eprintln!(
+            "⚠️ Could not save conversation to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversation_names_become_safe_file_names() {
+        assert_eq!(file_stem("Patient 12 / ICU"), "Patient_12___ICU");
+        assert_eq!(file_stem("../../etc/passwd"), "______etc_passwd");
+        assert_eq!(file_stem(" bed-4_night "), "bed-4_night");
+    }
+
+    #[test]
+    fn names_sharing_a_file_clash() {
+        let existing = vec!["Bed 4".to_string(), "General".to_string()];
+        assert_eq!(clashes_with("Bed_4", &existing), Some("Bed 4"));
+        assert_eq!(clashes_with(" Bed 4 ", &existing), None);
+        assert_eq!(clashes_with("Bed 5", &existing), None);
+    }
+
+    #[test]
+    fn messages_from_older_files_fill_missing_fields() {
+        let conversation: Conversation = serde_json::from_str(
+            r#"{"name":"Bed 4","messages":[{"content":"Hi","is_user":true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(conversation.messages[0].content, "Hi");
+        assert!(!conversation.messages[0].is_pinned);
+        assert!(conversation.messages[0].reasoning.is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+use uuid::Uuid;
+
+// Correlation ids travel inside the task prompt as a leading tag so they survive every cluster hop
+const TAG_PREFIX: &str = "[correlation_id=";
+const TAG_SUFFIX: &str = "] ";
+
+pub type CorrelationId = Uuid;
+
+pub fn new_id() -> CorrelationId {
+    Uuid::new_v4()
+}
+
+pub fn tag(id: CorrelationId, text: &str) -> String {
+    format!("{}{}{}{}", TAG_PREFIX, id, TAG_SUFFIX, text)
+}
+
+// Split a tagged prompt into its correlation id and the untagged text
+pub fn split(text: &str) -> (Option<CorrelationId>, &str) {
+    text.strip_prefix(TAG_PREFIX)
+        .and_then(|rest| rest.split_once(TAG_SUFFIX))
+        .and_then(|(id, body)| Uuid::parse_str(id).ok().map(|id| (Some(id), body)))
+        .unwrap_or((None, text))
+}
+
+pub fn display(id: Option<CorrelationId>) -> String {
+    id.map(|id| id.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// Echo the id of the originating request back on a response, if the request carried one
+pub fn tag_if_present(id: Option<CorrelationId>, text: &str) -> String {
+    match id {
+        Some(id) => tag(id, text),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,331 @@
+// At-least-once delivery of analysis requests. The doctor keeps every request it publishes
+// until a report with the same correlation id comes back, saved in
+// ~/.liquidos/pending_analyses.json, and publishes the unanswered ones again when the analysis
+// node reconnects. Only requests from this run are sent again: nobody is waiting for the
+// report of an earlier one. The analysis node remembers the ids it has seen, so a request
+// delivered twice is analyzed once, and answers a repeat that is still running so the doctor
+// doesn't count it as lost.
+use crate::correlation::CorrelationId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Publishes of one request, the first included, before the doctor gives up on it
+pub const MAX_DELIVERY_ATTEMPTS: u32 = 3;
+// Older requests aren't sent again; the user has long moved on
+const PENDING_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PendingRequest {
+    pub prompt: String, // As published, correlation tag included
+    pub sent_at: u64,   // Unix seconds of the first publish
+    pub attempts: u32,  // Publishes that went unanswered; a repeat still running doesn't count
+    #[serde(default)]
+    session: String, // The run that published it
+}
+
+// Unanswered requests by correlation id
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct Ledger {
+    requests: BTreeMap<String, PendingRequest>,
+}
+
+impl Ledger {
+    fn track(&mut self, id: CorrelationId, prompt: &str, now: u64, session: &str) {
+        self.requests.insert(
+            id.to_string(),
+            PendingRequest {
+                prompt: prompt.to_string(),
+                sent_at: now,
+                attempts: 1,
+                session: session.to_string(),
+            },
+        );
+    }
+
+    // Requests saved by earlier runs; their reports would answer questions nobody has open
+    fn forget_other_sessions(&mut self, session: &str) -> usize {
+        let before = self.requests.len();
+        self.requests
+            .retain(|_, request| request.session == session);
+        before - self.requests.len()
+    }
+
+    // The analysis node is still working on it, so the last publish wasn't lost
+    fn still_running(&mut self, id: CorrelationId) {
+        if let Some(request) = self.requests.get_mut(&id.to_string()) {
+            request.attempts = request.attempts.saturating_sub(1).max(1);
+        }
+    }
+
+    fn resolve(&mut self, id: CorrelationId) -> bool {
+        self.requests.remove(&id.to_string()).is_some()
+    }
+
+    // Prompts to publish again, and the requests dropped for being too old or out of attempts
+    fn redeliver(&mut self, now: u64) -> (Vec<String>, Vec<PendingRequest>) {
+        let (mut again, mut dropped) = (Vec::new(), Vec::new());
+        self.requests.retain(|_, request| {
+            let expired = now.saturating_sub(request.sent_at) > PENDING_EXPIRY.as_secs();
+            if expired || request.attempts >= MAX_DELIVERY_ATTEMPTS {
+                dropped.push(request.clone());
+                return false;
+            }
+            request.attempts += 1;
+            again.push(request.prompt.clone());
+            true
+        });
+        (again, dropped)
+    }
+}
+
+// Loaded from the file on first use
+static PENDING: Mutex<Option<Ledger>> = Mutex::new(None);
+
+// Identifies this run of the process in the saved requests
+fn session() -> &'static str {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    SESSION.get_or_init(|| uuid::Uuid::new_v4().to_string())
+}
+
+fn pending_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(
+        PathBuf::from(home)
+            .join(".liquidos")
+            .join("pending_analyses.json"),
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn with_ledger<T>(change: impl FnOnce(&mut Ledger) -> T) -> T {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let ledger = pending.get_or_insert_with(|| {
+        let mut ledger: Ledger = pending_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let stale = ledger.forget_other_sessions(session());
+        if stale > 0 {
+            println!(
+                "🧹 Not re-sending {} unanswered analysis request(s) from an earlier run",
+                stale
+            );
+        }
+        ledger
+    });
+    let result = change(ledger);
+    save(ledger);
+    result
+}
+
+fn save(ledger: &Ledger) {
+    let Some(path) = pending_path() else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| {
+            fs::write(
+                &path,
+                serde_json::to_string_pretty(ledger).unwrap_or_default(),
+            )
+        });
+    if let Err(e) = result {
+        eprintln!(
+            "⚠️ Could not save pending analysis requests to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// Doctor side: a request is about to be published
+pub fn track(id: CorrelationId, prompt: &str) {
+    with_ledger(|ledger| ledger.track(id, prompt, unix_now(), session()));
+}
+
+// Doctor side: a report (or error report) arrived, or the publish failed
+pub fn resolve(id: CorrelationId) -> bool {
+    with_ledger(|ledger| ledger.resolve(id))
+}
+
+// Doctor side: the analysis node says a re-sent request is still running
+pub fn still_running(id: CorrelationId) {
+    with_ledger(|ledger| ledger.still_running(id));
+}
+
+// Doctor side: the analysis node (re)connected
+pub fn redeliver() -> (Vec<String>, Vec<PendingRequest>) {
+    with_ledger(|ledger| ledger.redeliver(unix_now()))
+}
+
+// What the analysis node knows about a request id
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seen {
+    New,
+    InProgress,
+    Answered(String), // The report already sent; sent again since the doctor may have missed it
+}
+
+// Ids remembered by the analysis node; the oldest are forgotten first
+const MAX_SEEN: usize = 256;
+
+struct SeenRequests {
+    order: VecDeque<String>,
+    reports: BTreeMap<String, Option<String>>, // None while the analysis runs
+}
+
+impl SeenRequests {
+    const fn new() -> Self {
+        SeenRequests {
+            order: VecDeque::new(),
+            reports: BTreeMap::new(),
+        }
+    }
+
+    fn begin(&mut self, id: CorrelationId) -> Seen {
+        let id = id.to_string();
+        match self.reports.get(&id) {
+            Some(Some(report)) => return Seen::Answered(report.clone()),
+            Some(None) => return Seen::InProgress,
+            None => {}
+        }
+        if self.order.len() >= MAX_SEEN {
+            if let Some(oldest) = self.order.pop_front() {
+                self.reports.remove(&oldest);
+            }
+        }
+        self.order.push_back(id.clone());
+        self.reports.insert(id, None);
+        Seen::New
+    }
+
+    fn finish(&mut self, id: CorrelationId, report: &str) {
+        if let Some(entry) = self.reports.get_mut(&id.to_string()) {
+            *entry = Some(report.to_string());
+        }
+    }
+
+    // A failed analysis is forgotten so a repeat of the request tries again
+    fn abandon(&mut self, id: CorrelationId) {
+        let id = id.to_string();
+        self.reports.remove(&id);
+        self.order.retain(|seen| *seen != id);
+    }
+}
+
+static SEEN: Mutex<SeenRequests> = Mutex::new(SeenRequests::new());
+
+// Analysis side: called when a request arrives
+pub fn begin(id: CorrelationId) -> Seen {
+    SEEN.lock().unwrap_or_else(|e| e.into_inner()).begin(id)
+}
+
+pub fn finish(id: CorrelationId, report: &str) {
+    SEEN.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .finish(id, report);
+}
+
+pub fn abandon(id: CorrelationId) {
+    SEEN.lock().unwrap_or_else(|e| e.into_inner()).abandon(id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::correlation;
+
+    #[test]
+    fn unanswered_requests_are_redelivered_until_they_run_out_of_attempts() {
+        let mut ledger = Ledger::default();
+        let (answered, lost) = (correlation::new_id(), correlation::new_id());
+        ledger.track(answered, "analyze bed 4", 1_000, "run");
+        ledger.track(lost, "analyze bed 7", 1_000, "run");
+        assert!(ledger.resolve(answered));
+        assert!(!ledger.resolve(answered));
+
+        for _ in 1..MAX_DELIVERY_ATTEMPTS {
+            let (again, dropped) = ledger.redeliver(1_010);
+            assert_eq!(again, vec!["analyze bed 7".to_string()]);
+            assert!(dropped.is_empty());
+        }
+        let (again, dropped) = ledger.redeliver(1_020);
+        assert!(again.is_empty());
+        assert_eq!(dropped[0].attempts, MAX_DELIVERY_ATTEMPTS);
+        assert!(ledger.requests.is_empty());
+
+        // Too old to be worth sending again
+        ledger.track(lost, "analyze bed 7", 1_000, "run");
+        let (again, dropped) = ledger.redeliver(1_000 + PENDING_EXPIRY.as_secs() + 1);
+        assert!(again.is_empty());
+        assert_eq!(dropped.len(), 1);
+    }
+
+    #[test]
+    fn requests_still_running_are_not_given_up_on() {
+        let mut ledger = Ledger::default();
+        let id = correlation::new_id();
+        ledger.track(id, "analyze bed 4", 1_000, "run");
+        for _ in 0..MAX_DELIVERY_ATTEMPTS * 2 {
+            let (again, dropped) = ledger.redeliver(1_010);
+            assert_eq!(again.len(), 1);
+            assert!(dropped.is_empty());
+            ledger.still_running(id);
+        }
+        assert_eq!(ledger.requests[&id.to_string()].attempts, 1);
+    }
+
+    #[test]
+    fn requests_from_earlier_runs_are_not_sent_again() {
+        let mut ledger = Ledger::default();
+        let (earlier, current) = (correlation::new_id(), correlation::new_id());
+        ledger.track(earlier, "analyze bed 4", 1_000, "earlier run");
+        ledger.track(current, "analyze bed 7", 1_000, "this run");
+        assert_eq!(ledger.forget_other_sessions("this run"), 1);
+        let (again, _) = ledger.redeliver(1_010);
+        assert_eq!(again.len(), 1);
+        assert!(again[0].contains("bed 7"));
+    }
+
+    #[test]
+    fn repeated_requests_are_analyzed_once() {
+        let mut seen = SeenRequests::new();
+        let id = correlation::new_id();
+        assert_eq!(seen.begin(id), Seen::New);
+        assert_eq!(seen.begin(id), Seen::InProgress);
+        seen.finish(id, "### ECG Analysis Report");
+        assert_eq!(
+            seen.begin(id),
+            Seen::Answered("### ECG Analysis Report".to_string())
+        );
+
+        let failed = correlation::new_id();
+        assert_eq!(seen.begin(failed), Seen::New);
+        seen.abandon(failed);
+        assert_eq!(seen.begin(failed), Seen::New);
+    }
+
+    #[test]
+    fn only_the_most_recent_ids_are_remembered() {
+        let mut seen = SeenRequests::new();
+        let first = correlation::new_id();
+        seen.begin(first);
+        for _ in 0..MAX_SEEN {
+            seen.begin(correlation::new_id());
+        }
+        assert_eq!(seen.order.len(), MAX_SEEN);
+        assert_eq!(seen.begin(first), Seen::New);
+    }
+}
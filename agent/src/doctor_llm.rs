@@ -0,0 +1,134 @@
+// The LLM behind the DoctorAgent's ReAct loop. The executor runs inside the runtime, out of
+// reach of the query queue, so the request's cancellation token is checked here: once the user
+// stops a query, the LLM call in progress and every later one fail, which ends the loop. Its
+// chat calls are also counted in the usage summary, under "doctor". A call that times out ends
+// the query with "The AI service timed out" instead of leaving the user waiting for the query
+// timeout. With --disable-camera the camera instructions are taken out of the system prompt
+// here too.
+use crate::agents::{self, AgentResponse};
+use crate::usage;
+use async_trait::async_trait;
+use autoagents::llm::LLMProvider;
+use autoagents::llm::chat::{
+    ChatMessage, ChatProvider, ChatResponse, ChatRole, StructuredOutputFormat, Tool,
+};
+use autoagents::llm::completion::{CompletionProvider, CompletionRequest, CompletionResponse};
+use autoagents::llm::embedding::EmbeddingProvider;
+use autoagents::llm::error::LLMError;
+use autoagents::llm::models::ModelsProvider;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+pub struct DoctorLlm {
+    inner: Arc<dyn LLMProvider>,
+    current_request: Arc<Mutex<CancellationToken>>, // Replaced by the queue for every query
+    failure_tx: mpsc::UnboundedSender<AgentResponse>, // The doctor's response gate
+}
+
+impl DoctorLlm {
+    pub fn new(
+        inner: Arc<dyn LLMProvider>,
+        current_request: Arc<Mutex<CancellationToken>>,
+        failure_tx: mpsc::UnboundedSender<AgentResponse>,
+    ) -> Self {
+        Self {
+            inner,
+            current_request,
+            failure_tx,
+        }
+    }
+
+    // Tell the user and end the query; the error also ends the ReAct loop
+    fn timed_out(&self, limit: Duration) -> LLMError {
+        let message = agents::llm_timeout_message(limit);
+        eprintln!("⏰ [DoctorAgent] {}", message);
+        let _ = self.failure_tx.send(AgentResponse {
+            agent_source: Some("doctor".to_string()),
+            is_final: true,
+            ..AgentResponse::error(format!("⏰ {}. Please try again.", message))
+        });
+        LLMError::ProviderError(message)
+    }
+
+    fn request(&self) -> CancellationToken {
+        self.current_request
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+fn cancelled() -> LLMError {
+    LLMError::ProviderError("the request was cancelled".to_string())
+}
+
+#[async_trait]
+impl ChatProvider for DoctorLlm {
+    async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        tools: Option<&[Tool]>,
+        json_schema: Option<StructuredOutputFormat>,
+    ) -> Result<Box<dyn ChatResponse>, LLMError> {
+        let request = self.request();
+        let site_messages: Vec<ChatMessage>;
+        let messages = if agents::camera_disabled() {
+            site_messages = messages
+                .iter()
+                .map(|message| {
+                    let mut message = message.clone();
+                    if matches!(message.role, ChatRole::System) {
+                        message.content = agents::without_camera_instructions(&message.content);
+                    }
+                    message
+                })
+                .collect();
+            &site_messages[..]
+        } else {
+            messages
+        };
+        // The provider's HTTP client has the same limit; this is the backstop
+        let limit = agents::llm_timeout();
+        let chat = tokio::time::timeout(limit, self.inner.chat(messages, tools, json_schema));
+        let response = tokio::select! {
+            response = chat => {
+                match response {
+                    Ok(Ok(response)) => response,
+                    Ok(Err(e)) if !agents::is_timeout_error(&e.to_string()) => return Err(e),
+                    _ => return Err(self.timed_out(limit)),
+                }
+            }
+            _ = request.cancelled() => return Err(cancelled()),
+        };
+        usage::record("doctor", response.as_ref());
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for DoctorLlm {
+    async fn complete(
+        &self,
+        req: &CompletionRequest,
+        json_schema: Option<StructuredOutputFormat>,
+    ) -> Result<CompletionResponse, LLMError> {
+        let request = self.request();
+        tokio::select! {
+            response = self.inner.complete(req, json_schema) => response,
+            _ = request.cancelled() => Err(cancelled()),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for DoctorLlm {
+    async fn embed(&self, input: Vec<String>) -> Result<Vec<Vec<f32>>, LLMError> {
+        self.inner.embed(input).await
+    }
+}
+
+impl ModelsProvider for DoctorLlm {}
+
+impl LLMProvider for DoctorLlm {}
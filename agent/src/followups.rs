@@ -0,0 +1,134 @@
+// Follow-up analyses the doctor scheduled for later. The delayed tasks themselves run on the
+// doctor runtime; this keeps the book of pending jobs so they can be listed and cancelled, and
+// armed again on the new runtime when the doctor restarts.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+// Longest delay accepted, so a typo can't park a job for weeks
+pub const MAX_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowUp {
+    pub id: u64,
+    pub query: String,
+    pub due_at: SystemTime,
+}
+
+impl FollowUp {
+    pub fn describe(&self) -> String {
+        let due_in = self
+            .due_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+            .as_secs();
+        let due_at = self
+            .due_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        format!(
+            "#{} due in {}s (unix {}): {}",
+            self.id, due_in, due_at, self.query
+        )
+    }
+}
+
+struct Schedule {
+    next_id: u64,
+    jobs: BTreeMap<u64, (FollowUp, CancellationToken)>,
+}
+
+static SCHEDULE: Mutex<Schedule> = Mutex::new(Schedule {
+    next_id: 0,
+    jobs: BTreeMap::new(),
+});
+
+// Book a follow-up; the token is cancelled if the job is cancelled before it fires
+pub fn add(query: &str, delay: Duration) -> Result<(FollowUp, CancellationToken), String> {
+    if delay.is_zero() || delay > MAX_DELAY {
+        return Err(format!(
+            "the delay must be between 1 and {} seconds",
+            MAX_DELAY.as_secs()
+        ));
+    }
+    let mut schedule = SCHEDULE.lock().unwrap();
+    schedule.next_id += 1;
+    let follow_up = FollowUp {
+        id: schedule.next_id,
+        query: query.to_string(),
+        due_at: SystemTime::now() + delay,
+    };
+    let token = CancellationToken::new();
+    schedule
+        .jobs
+        .insert(follow_up.id, (follow_up.clone(), token.clone()));
+    Ok((follow_up, token))
+}
+
+pub fn cancel(id: u64) -> Option<FollowUp> {
+    let (follow_up, token) = SCHEDULE.lock().unwrap().jobs.remove(&id)?;
+    token.cancel();
+    Some(follow_up)
+}
+
+// Take a job off the book once it fired; None when it was cancelled meanwhile
+pub fn fired(id: u64) -> Option<FollowUp> {
+    let (follow_up, _) = SCHEDULE.lock().unwrap().jobs.remove(&id)?;
+    Some(follow_up)
+}
+
+// Pending follow-ups with their tokens, for arming their timers again after a restart
+pub fn armed() -> Vec<(FollowUp, CancellationToken)> {
+    SCHEDULE.lock().unwrap().jobs.values().cloned().collect()
+}
+
+// Pending follow-ups, soonest first
+pub fn pending() -> Vec<FollowUp> {
+    let mut jobs: Vec<FollowUp> = SCHEDULE
+        .lock()
+        .unwrap()
+        .jobs
+        .values()
+        .map(|(follow_up, _)| follow_up.clone())
+        .collect();
+    jobs.sort_by_key(|follow_up| follow_up.due_at);
+    jobs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follow_ups_can_be_listed_cancelled_and_fired() {
+        assert!(add("too soon", Duration::ZERO).is_err());
+        assert!(add("too late", MAX_DELAY + Duration::from_secs(1)).is_err());
+
+        let (later, later_token) = add("re-check rhythm", Duration::from_secs(600)).unwrap();
+        let (sooner, _) = add("re-check rate", Duration::from_secs(60)).unwrap();
+        let ids: Vec<u64> = pending().iter().map(|f| f.id).collect();
+        let sooner_at = ids.iter().position(|id| *id == sooner.id).unwrap();
+        let later_at = ids.iter().position(|id| *id == later.id).unwrap();
+        assert!(sooner_at < later_at);
+
+        assert_eq!(cancel(later.id), Some(later.clone()));
+        assert!(later_token.is_cancelled());
+        assert!(cancel(later.id).is_none());
+        assert!(fired(later.id).is_none());
+
+        assert!(armed().iter().any(|(f, _)| f.id == sooner.id));
+        assert!(armed().iter().all(|(f, _)| f.id != later.id));
+
+        assert_eq!(
+            fired(sooner.id).map(|f| f.query),
+            Some("re-check rate".to_string())
+        );
+        assert!(
+            pending()
+                .iter()
+                .all(|f| f.id != sooner.id && f.id != later.id)
+        );
+    }
+}
@@ -1,7 +1,23 @@
-use iced::widget::{Column, button, column, container, row, scrollable, text, text_input};
-use iced::{Alignment, Element, Length, Task, Theme};
-use std::sync::{Arc, Mutex};
+use crate::agents::{
+    AgentResponse, CameraConfirmRequest, QueueStatus, SHUTDOWN_MESSAGE, UploadedImage,
+};
+use crate::conversations::{self, StoredMessage};
+use crate::patient::{self, PatientContext};
+use crate::prefs::{self, LlmSettings, QuickAction};
+use crate::registry;
+#[cfg(feature = "voice")]
+use crate::voice;
+use iced::keyboard::{self, Key, key::Named};
+use iced::widget::scrollable::{RelativeOffset, Viewport};
+use iced::widget::{
+    Column, button, center, column, container, image, mouse_area, opaque, row, scrollable, slider,
+    stack, text, text_input,
+};
+use iced::{Alignment, Element, Length, Subscription, Task, Theme};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -9,39 +25,753 @@ pub enum Message {
     SendMessage,
     ReceivedDoctorResponse(String),
     Tick,
+    ChatScrolled(Viewport),
+    ToggleTheme,
+    ClearInput,
+    RecallLastMessage,
+    ClearConversation,
+    ToggleStatusPanel,
+    CancelRequest,
+    ConfirmCamera(bool),
+    CopyMessage(usize),
+    ToggleReasoning(usize),
+    ToggleSearch,
+    SearchChanged(String),
+    AttachImage,
+    ImageAttached(Option<Attachment>),
+    RemoveAttachment,
+    WindowFocusChanged(bool),
+    KeyInputChanged(String),
+    RetryKey,
+    Regenerate,
+    TogglePin(usize),
+    TogglePinnedOnly,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    SwitchConversation(String),
+    NewConversationNameChanged(String),
+    NewConversation,
+    CloseRequested,
+    ConfirmClose(bool),
+    ToggleSettings,
+    SettingsModelChanged(String),
+    SettingsTemperatureChanged(f32),
+    SettingsMemoryWindowChanged(String),
+    ApplySettings,
+    QuickAction(usize),
+    #[cfg(feature = "voice")]
+    StartRecording,
+    #[cfg(feature = "voice")]
+    StopRecording,
+    #[cfg(feature = "voice")]
+    Transcribed(Result<String, String>),
 }
 
+// Image picked with the Attach button, waiting to be sent with the next message
 #[derive(Debug, Clone)]
+pub struct Attachment {
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+// Channels connecting the GUI to the doctor agent thread
+pub struct AgentChannels {
+    pub user_sender: mpsc::Sender<String>, // Bounded; Send is disabled while it is full
+    pub response_receiver: mpsc::Receiver<AgentResponse>,
+    pub ack_receiver: mpsc::UnboundedReceiver<String>, // Queries the doctor started handling
+    pub cancel_sender: mpsc::UnboundedSender<()>,      // Aborts the request in flight
+    pub camera_confirm_receiver: mpsc::UnboundedReceiver<CameraConfirmRequest>,
+    pub queue_receiver: mpsc::UnboundedReceiver<QueueStatus>, // Active query and queue depth
+    pub reasoning_receiver: mpsc::UnboundedReceiver<String>,  // ReAct steps for the next answer
+    pub upload_sender: mpsc::UnboundedSender<UploadedImage>,  // Images attached to a query
+    pub control_receiver: mpsc::UnboundedReceiver<AgentControl>, // Restarts of the agent thread
+}
+
+// Sent by the supervisor of the doctor thread when it exits and is restarted
+pub enum AgentControl {
+    Reconnecting { attempt: u32, max_attempts: u32 },
+    Restarted(AgentChannels), // Fresh channels to the new thread, replacing the closed ones
+    GaveUp,
+    Failed(String), // A connect started by the GUI couldn't start the agent
+}
+
+// Spawns (or respawns) the agent side with an LLM built from the given settings, remembering
+// the given conversation, and returns fresh channels to talk to it
+pub type AgentConnector =
+    Arc<dyn Fn(&LlmSettings, &[StoredMessage]) -> Result<AgentChannels, String> + Send + Sync>;
+
+// Shown instead of the chat when the LLM API key is missing or invalid at startup
+pub struct KeySetup {
+    pub key_var: &'static str,
+    pub error: String,
+    // Builds the connector from the key typed in the dialog, or from the environment when None
+    pub retry: Arc<dyn Fn(Option<String>) -> Result<AgentConnector, String> + Send + Sync>,
+}
+
+// Agents shown in the status panel, as (heartbeat kind, display name)
+const KNOWN_AGENTS: [(&str, &str); 2] =
+    [("analysis", "Analysis Agent"), ("camera", "Camera Agent")];
+
+// Send stays disabled while a query is answered, but comes back after this in case the
+// answer was lost
+const SEND_LOCK_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Frames of the spinner on the disabled Send button, advanced by Tick
+const SPINNER_FRAMES: [&str; 4] = ["◐", "◓", "◑", "◒"];
+const SPINNER_FRAME_MS: u128 = 150;
+
+// Characters of a response shown in the desktop notification
+const NOTIFICATION_PREVIEW_CHARS: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    Dark,
+    Light,
+}
+
+// Colors used throughout the chat view, one set per theme
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub bg_primary: iced::Color,
+    pub bg_secondary: iced::Color,
+    pub bg_input: iced::Color,
+    pub user_bubble: iced::Color,
+    pub ai_bubble: iced::Color,
+    pub text_primary: iced::Color,
+    pub text_secondary: iced::Color,
+    pub user_text: iced::Color,
+    pub accent_green: iced::Color,
+    pub accent_hovered: iced::Color,
+    pub accent_pressed: iced::Color,
+    pub border: iced::Color,
+    pub divider: iced::Color,
+    pub scroller: iced::Color,
+    pub selection: iced::Color,
+    pub danger: iced::Color,
+}
+
+impl Palette {
+    pub fn dark() -> Self {
+        Self {
+            bg_primary: iced::Color::from_rgb(0.1, 0.1, 0.12), // Very dark blue-gray
+            bg_secondary: iced::Color::from_rgb(0.15, 0.15, 0.18), // Slightly lighter
+            bg_input: iced::Color::from_rgb(0.18, 0.18, 0.22), // Input background
+            user_bubble: iced::Color::from_rgb(0.2, 0.4, 0.8), // User message blue
+            ai_bubble: iced::Color::from_rgb(0.25, 0.25, 0.3), // AI message gray
+            text_primary: iced::Color::WHITE,
+            text_secondary: iced::Color::from_rgb(0.9, 0.9, 0.9),
+            user_text: iced::Color::WHITE,
+            accent_green: iced::Color::from_rgb(0.2, 0.8, 0.4),
+            accent_hovered: iced::Color::from_rgb(0.25, 0.85, 0.45),
+            accent_pressed: iced::Color::from_rgb(0.15, 0.75, 0.35),
+            border: iced::Color::from_rgb(0.3, 0.3, 0.4),
+            divider: iced::Color::from_rgb(0.2, 0.2, 0.25),
+            scroller: iced::Color::from_rgb(0.4, 0.4, 0.5),
+            selection: iced::Color::from_rgb(0.3, 0.5, 0.9),
+            danger: iced::Color::from_rgb(0.85, 0.25, 0.25),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            bg_primary: iced::Color::from_rgb(0.97, 0.97, 0.98), // Near white
+            bg_secondary: iced::Color::from_rgb(0.91, 0.92, 0.94), // Light gray
+            bg_input: iced::Color::WHITE,
+            user_bubble: iced::Color::from_rgb(0.2, 0.45, 0.85), // User message blue
+            ai_bubble: iced::Color::from_rgb(0.86, 0.87, 0.9),   // AI message light gray
+            text_primary: iced::Color::from_rgb(0.1, 0.1, 0.12),
+            text_secondary: iced::Color::from_rgb(0.4, 0.4, 0.45),
+            user_text: iced::Color::WHITE,
+            accent_green: iced::Color::from_rgb(0.1, 0.65, 0.3),
+            accent_hovered: iced::Color::from_rgb(0.15, 0.7, 0.35),
+            accent_pressed: iced::Color::from_rgb(0.05, 0.6, 0.25),
+            border: iced::Color::from_rgb(0.75, 0.76, 0.8),
+            divider: iced::Color::from_rgb(0.82, 0.83, 0.86),
+            scroller: iced::Color::from_rgb(0.65, 0.66, 0.7),
+            selection: iced::Color::from_rgb(0.6, 0.75, 1.0),
+            danger: iced::Color::from_rgb(0.8, 0.15, 0.15),
+        }
+    }
+
+    pub fn for_mode(mode: ThemeMode) -> Self {
+        match mode {
+            ThemeMode::Dark => Self::dark(),
+            ThemeMode::Light => Self::light(),
+        }
+    }
+}
+
+pub const DEFAULT_WINDOW_TITLE: &str = "LiquidOS - AI Medical Assistant";
+
+// Size and title of the window, from --window-width, --window-height and --window-title
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowOptions {
+    pub width: f32,
+    pub height: f32,
+    pub title: String,
+}
+
+const WELCOME_MESSAGE: &str = "Hello! I'm your ECG analysis assistant. I can help you analyze ECG data and provide medical recommendations. How can I assist you today?";
+
+// How close (in pixels) to the end of the chat still counts as following new messages
+const SCROLL_FOLLOW_THRESHOLD: f32 = 40.0;
+
+// Query sent with an attached image when the user didn't type one
+const DEFAULT_IMAGE_QUERY: &str = "Please analyze this image.";
+
+// Width of attached images in the chat bubbles
+const IMAGE_PREVIEW_WIDTH: f32 = 240.0;
+
+// Font zoom range and step (Ctrl+Plus / Ctrl+Minus, Ctrl+0 resets)
+const MIN_ZOOM: f32 = 0.75;
+const MAX_ZOOM: f32 = 2.0;
+const ZOOM_STEP: f32 = 0.1;
+
+// Marker color for pinned messages, readable on both themes
+const PIN_COLOR: iced::Color = iced::Color::from_rgb(0.95, 0.75, 0.2);
+
+// How long the "Copied" confirmation stays on screen
+const COPIED_TOAST_DURATION: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default)]
 pub struct ChatMessage {
     pub content: String,
     pub is_user: bool,
+    pub reasoning: Vec<String>, // ReAct steps that led to this answer
+    pub show_reasoning: bool,
+    pub image: Option<image::Handle>, // Image the user attached, or an ECG waveform plot
+    pub agent_source: Option<String>, // Agent that wrote an answer: "analysis", "camera" or "doctor"
+    pub is_pinned: bool,              // Bookmarked by the user; kept visible by "Pinned only"
+    pub is_error: bool,               // A tool failure reported by the agent node
+    pub received: bool,               // The doctor acknowledged this query and is working on it
+    pub in_reply_to: Option<String>,  // Question a camera result answers, quoted above it
+}
+
+// A query was sent and not answered yet, or the doctor is still working through its queue
+fn request_in_flight(waiting: bool, queue: &QueueStatus) -> bool {
+    waiting || queue.active.is_some() || queue.queued > 0
+}
+
+// The doctor works through queries in order, so an ack belongs to the oldest user message with
+// that text that wasn't acknowledged yet
+fn mark_received(messages: &mut [ChatMessage], query: &str) {
+    if let Some(message) = messages
+        .iter_mut()
+        .find(|m| m.is_user && !m.received && m.content == query)
+    {
+        message.received = true;
+    }
+}
+
+// Tag text and color shown next to an answer, by the agent that wrote it
+fn agent_tag(agent_source: Option<&str>, palette: Palette) -> (&'static str, iced::Color) {
+    match agent_source {
+        Some("analysis") => ("ECG", iced::Color::from_rgb(0.8, 0.3, 0.35)),
+        Some("camera") => ("CAM", iced::Color::from_rgb(0.85, 0.55, 0.15)),
+        Some("doctor") => ("DR", palette.user_bubble),
+        _ => ("AI", palette.accent_green),
+    }
 }
 
 pub struct ChatApp {
     messages: Vec<ChatMessage>,
     input_value: String,
-    user_sender: Arc<Mutex<Option<mpsc::UnboundedSender<String>>>>,
-    response_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<String>>>>,
+    user_sender: Arc<Mutex<Option<mpsc::Sender<String>>>>,
+    response_receiver: Arc<Mutex<Option<mpsc::Receiver<AgentResponse>>>>,
+    chat_scroll_id: scrollable::Id,
+    follow_new_messages: bool,
+    theme_mode: ThemeMode,
+    palette: Palette,
+    connector: Option<AgentConnector>, // None until a usable API key is configured
+    key_setup: Option<KeySetup>,
+    key_input: String,
+    control_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<AgentControl>>>>,
+    reconnecting: bool, // The doctor thread exited and the supervisor is bringing it back
+    connecting: bool,   // A connect from the GUI (start, new key, new settings) is under way
+    ack_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<String>>>>,
+    show_status_panel: bool,
+    cancel_sender: Option<mpsc::UnboundedSender<()>>,
+    waiting: bool,
+    waiting_since: Option<Instant>, // When this window last sent a query; drives the spinner
+    camera_confirm_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<CameraConfirmRequest>>>>,
+    pending_camera_confirm: Option<CameraConfirmRequest>,
+    copied_at: Option<Instant>,
+    queue_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<QueueStatus>>>>,
+    queue_status: QueueStatus,
+    reasoning_receiver: Arc<Mutex<Option<mpsc::UnboundedReceiver<String>>>>,
+    pending_reasoning: Vec<String>, // Steps received since the last answer
+    show_search: bool,
+    show_pinned_only: bool,
+    search_query: String,
+    search_input_id: text_input::Id,
+    input_id: text_input::Id, // Message input, focused on launch and after every send
+    upload_sender: Option<mpsc::UnboundedSender<UploadedImage>>,
+    attachment: Option<Attachment>,
+    notifications: bool, // Desktop notifications for responses arriving while unfocused
+    window_focused: bool,
+    poll_interval: Duration,    // How often Tick checks the agent channels
+    last_query: Option<String>, // Most recent user query, re-run by Regenerate
+    regenerate_index: Option<usize>, // AI message the next response replaces
+    zoom: f32,                  // Font size multiplier, saved in the GUI preferences
+    started_at: Instant,        // Shown as the session duration in the footer
+    conversations: Vec<String>, // Saved conversation names, listed in the sidebar
+    active_conversation: String, // Whose messages are shown; saved after every change
+    new_conversation_name: String,
+    confirm_close: bool, // The window was closed while a request runs; asking before quitting
+    llm_settings: LlmSettings, // Model settings the running doctor agent was built with
+    show_settings: bool,
+    settings_model: String, // Settings panel fields, applied together by ApplySettings
+    settings_temperature: f32,
+    settings_memory_window: String,
+    settings_error: Option<String>,
+    quick_actions: Vec<QuickAction>, // From the GUI preferences file
+    window_title: String,
+    #[cfg(feature = "voice")]
+    recorder: Option<voice::Recorder>, // Dictation in progress
+    #[cfg(feature = "voice")]
+    transcribing: bool,
 }
 
 impl ChatApp {
+    // `theme_mode` and `memory_window` are set when given on the command line, and then win
+    // over the saved preferences
     pub fn new(
-        user_sender: mpsc::UnboundedSender<String>,
-        response_receiver: mpsc::UnboundedReceiver<String>,
+        theme_mode: Option<ThemeMode>,
+        notifications: bool,
+        poll_interval: Duration,
+        llm_defaults: LlmSettings,
+        memory_window: Option<usize>,
+        window_title: String,
     ) -> Self {
+        let saved = prefs::load();
+        let theme_mode = theme_mode.or(saved.theme).unwrap_or(ThemeMode::Dark);
+        let llm_settings = prefs::llm_settings(&saved, llm_defaults, memory_window);
         Self {
-            messages: vec![ChatMessage {
-                content: "Hello! I'm your ECG analysis assistant. I can help you analyze ECG data and provide medical recommendations. How can I assist you today?".to_string(),
-                is_user: false,
-            }],
+            messages: load_messages(conversations::DEFAULT_CONVERSATION),
             input_value: String::new(),
-            user_sender: Arc::new(Mutex::new(Some(user_sender))),
-            response_receiver: Arc::new(Mutex::new(Some(response_receiver))),
+            user_sender: Arc::new(Mutex::new(None)),
+            response_receiver: Arc::new(Mutex::new(None)),
+            chat_scroll_id: scrollable::Id::new("chat"),
+            follow_new_messages: true,
+            theme_mode,
+            palette: Palette::for_mode(theme_mode),
+            connector: None,
+            key_setup: None,
+            key_input: String::new(),
+            control_receiver: Arc::new(Mutex::new(None)),
+            reconnecting: false,
+            connecting: false,
+            ack_receiver: Arc::new(Mutex::new(None)),
+            show_status_panel: true,
+            cancel_sender: None,
+            waiting: false,
+            waiting_since: None,
+            camera_confirm_receiver: Arc::new(Mutex::new(None)),
+            pending_camera_confirm: None,
+            copied_at: None,
+            queue_receiver: Arc::new(Mutex::new(None)),
+            queue_status: QueueStatus::default(),
+            reasoning_receiver: Arc::new(Mutex::new(None)),
+            pending_reasoning: Vec::new(),
+            show_search: false,
+            show_pinned_only: false,
+            search_query: String::new(),
+            search_input_id: text_input::Id::new("search"),
+            input_id: text_input::Id::new("input"),
+            upload_sender: None,
+            attachment: None,
+            notifications,
+            window_focused: true,
+            poll_interval,
+            last_query: None,
+            regenerate_index: None,
+            zoom: saved.zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+            started_at: Instant::now(),
+            conversations: conversations::list(),
+            active_conversation: conversations::DEFAULT_CONVERSATION.to_string(),
+            new_conversation_name: String::new(),
+            confirm_close: false,
+            settings_model: llm_settings.model.clone(),
+            settings_temperature: llm_settings.temperature,
+            settings_memory_window: llm_settings.memory_window.to_string(),
+            llm_settings,
+            show_settings: false,
+            settings_error: None,
+            quick_actions: prefs::quick_actions(&saved),
+            window_title,
+            #[cfg(feature = "voice")]
+            recorder: None,
+            #[cfg(feature = "voice")]
+            transcribing: false,
+        }
+    }
+
+    // Font size scaled by the zoom level
+    fn text_size(&self, size: f32) -> f32 {
+        size * self.zoom
+    }
+
+    fn set_zoom(&mut self, zoom: f32) {
+        // Round so repeated steps land exactly on 1.0, 1.1, ...
+        let zoom = ((zoom * 100.0).round() / 100.0).clamp(MIN_ZOOM, MAX_ZOOM);
+        if zoom != self.zoom {
+            self.zoom = zoom;
+            prefs::update(|prefs| prefs.zoom = zoom);
+        }
+    }
+
+    // Spawn the agent side and start talking to it. The connector blocks until a previous
+    // doctor released the cluster port, so it runs on its own thread; the channels arrive on
+    // the control channel, like after a supervisor restart, and are installed on the next Tick.
+    fn connect(&mut self, connector: AgentConnector) {
+        let (control_tx, control_rx) = mpsc::unbounded_channel::<AgentControl>();
+        *lock_or_recover(&self.control_receiver, "control receiver") = Some(control_rx);
+        self.connector = Some(connector.clone());
+        self.connecting = true;
+        let settings = self.llm_settings.clone();
+        let history: Vec<StoredMessage> = self.messages.iter().map(to_stored).collect();
+        std::thread::spawn(move || {
+            let event = match connector(&settings, &history) {
+                Ok(channels) => AgentControl::Restarted(channels),
+                Err(e) => AgentControl::Failed(e),
+            };
+            let _ = control_tx.send(event);
+        });
+    }
+
+    // Settings panel fields as settings for the running provider, checked before anything
+    // is stopped
+    fn settings_from_panel(&self) -> Result<LlmSettings, String> {
+        let memory_window = self
+            .settings_memory_window
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| {
+                format!(
+                    "memory window '{}' is not a whole number",
+                    self.settings_memory_window.trim()
+                )
+            })?;
+        let settings = LlmSettings {
+            provider: self.llm_settings.provider.clone(),
+            model: self.settings_model.trim().to_string(),
+            // The slider steps in 0.05; keep 0.2 from turning into 0.20000000298
+            temperature: (self.settings_temperature * 100.0).round() / 100.0,
+            memory_window,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn install_channels(&mut self, channels: AgentChannels) {
+        *lock_or_recover(&self.user_sender, "user sender") = Some(channels.user_sender);
+        *lock_or_recover(&self.response_receiver, "response receiver") =
+            Some(channels.response_receiver);
+        *lock_or_recover(&self.ack_receiver, "ack receiver") = Some(channels.ack_receiver);
+        self.cancel_sender = Some(channels.cancel_sender);
+        self.upload_sender = Some(channels.upload_sender);
+        *lock_or_recover(&self.camera_confirm_receiver, "camera confirm receiver") =
+            Some(channels.camera_confirm_receiver);
+        self.pending_camera_confirm = None;
+        *lock_or_recover(&self.queue_receiver, "queue receiver") = Some(channels.queue_receiver);
+        self.queue_status = QueueStatus::default();
+        *lock_or_recover(&self.reasoning_receiver, "reasoning receiver") =
+            Some(channels.reasoning_receiver);
+        self.pending_reasoning.clear();
+        *lock_or_recover(&self.control_receiver, "control receiver") =
+            Some(channels.control_receiver);
+        self.waiting = false;
+        self.regenerate_index = None;
+    }
+
+    // Send is disabled while the last query is being answered, up to SEND_LOCK_TIMEOUT
+    fn send_locked(&self) -> bool {
+        self.waiting
+            && self
+                .waiting_since
+                .is_none_or(|since| since.elapsed() < SEND_LOCK_TIMEOUT)
+    }
+
+    // The newest message, when it is an AI answer to a query that can be re-run
+    fn regenerate_target(&self) -> Option<usize> {
+        let index = self.messages.len().checked_sub(1)?;
+        let answered = index > 0 && !self.messages[index].is_user;
+        (answered && self.last_query.is_some() && !self.waiting).then_some(index)
+    }
+
+    // Let the doctor know a response is ready when they are working in another window
+    fn notify_if_unfocused(&self, response: &str) {
+        if self.notifications && !self.window_focused {
+            show_notification(notification_preview(response));
+        }
+    }
+
+    // The doctor node records heartbeats in the registry, in this process
+    fn is_agent_online(&self, kind: &str) -> bool {
+        registry::is_online(kind)
+    }
+
+    fn is_doctor_online(&self) -> bool {
+        lock_or_recover(&self.user_sender, "user sender")
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed())
+    }
+
+    // The agent hasn't taken the queries already sent; sending is paused until it catches up
+    fn is_send_channel_full(&self) -> bool {
+        lock_or_recover(&self.user_sender, "user sender")
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed() && sender.capacity() == 0)
+    }
+
+    // Hand a query to the doctor agent. Fails when the channel is full or while the agent
+    // thread is being restarted.
+    fn send_to_doctor(&self, query: &str) -> Result<(), String> {
+        let guard = lock_or_recover(&self.user_sender, "user sender");
+        let reconnecting = "🔄 Reconnecting to agent… Please send again once it is back.";
+        let Some(sender) = guard.as_ref() else {
+            return if self.reconnecting {
+                Err(reconnecting.to_string())
+            } else {
+                Ok(())
+            };
+        };
+        match sender.try_send(format!("USER_SEND:{}", query)) {
+            Err(TrySendError::Full(_)) => {
+                println!("⏳ User channel is full, not sending: {}", query);
+                Err("⏳ The doctor agent is still catching up on earlier messages. Please wait a moment and send again.".to_string())
+            }
+            Err(TrySendError::Closed(_)) => Err(reconnecting.to_string()),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    // Warn before sending a query that needs an agent which is currently offline
+    fn offline_agent_warning(&self, query: &str) -> Option<String> {
+        let query = query.to_lowercase();
+        let needs_camera = ["camera", "room", "image", "photo", "picture"]
+            .iter()
+            .any(|word| query.contains(word));
+        let needs_analysis = ["ecg", "ekg", "analysis", "analyze", "rhythm"]
+            .iter()
+            .any(|word| query.contains(word));
+
+        if needs_camera && !self.is_agent_online("camera") {
+            Some(
+                "⚠️ The Camera Agent appears to be offline - camera analysis may not arrive."
+                    .to_string(),
+            )
+        } else if needs_analysis && !self.is_agent_online("analysis") {
+            Some(
+                "⚠️ The Analysis Agent appears to be offline - ECG analysis may not arrive."
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    }
+
+    fn save_conversation(&self) {
+        let stored: Vec<StoredMessage> = self.messages.iter().map(to_stored).collect();
+        conversations::save(&self.active_conversation, &stored);
+    }
+
+    // Save the open conversation and show another one. Its name becomes the patient context,
+    // so the doctor's requests to other agents keep the threads apart, and the doctor restarts
+    // with the other conversation's memory.
+    fn switch_conversation(&mut self, name: String) -> Task<Message> {
+        let name = name.trim().to_string();
+        if name.is_empty() || name == self.active_conversation {
+            return Task::none();
+        }
+        // The answer would land in the other conversation
+        if request_in_flight(self.waiting, &self.queue_status) {
+            self.push_system_message(
+                "⚠️ Wait for the running request to finish (or stop it) before switching conversations.",
+            );
+            return self.scroll_to_latest();
+        }
+        if let Some(existing) = conversations::clashes_with(&name, &self.conversations) {
+            self.push_system_message(&format!(
+                "⚠️ \"{}\" would be saved to the same file as \"{}\". Please pick another name.",
+                name, existing
+            ));
+            return self.scroll_to_latest();
+        }
+        self.save_conversation();
+        println!("💬 Switching to conversation: {}", name);
+        self.messages = load_messages(&name);
+        if !self.conversations.contains(&name) {
+            self.conversations.push(name.clone());
+            self.conversations.sort();
+        }
+        if name == conversations::DEFAULT_CONVERSATION {
+            patient::clear();
+        } else {
+            patient::set_active(PatientContext {
+                patient_id: name.clone(),
+                age: None,
+                notes: None,
+            });
+        }
+        self.active_conversation = name;
+        self.pending_reasoning.clear();
+        self.last_query = None;
+        self.regenerate_index = None;
+        self.show_pinned_only = false;
+        self.follow_new_messages = true;
+        if let Some(connector) = self.connector.clone() {
+            self.stop_agent();
+            self.connect(connector);
+        }
+        Task::batch([self.scroll_to_latest(), self.focus_input()])
+    }
+
+    // Stop the doctor node and quit. The control channel goes first so the supervisor doesn't
+    // take the stopping thread for a crash and restart it.
+    fn shut_down(&mut self) -> Task<Message> {
+        println!("🚪 Window closed, shutting down the doctor agent");
+        self.stop_agent();
+        iced::exit()
+    }
+
+    fn stop_agent(&mut self) {
+        *lock_or_recover(&self.control_receiver, "control receiver") = None;
+        if let Some(sender) = lock_or_recover(&self.user_sender, "user sender").take() {
+            // Dropping the sender stops the doctor as well when its queue is full
+            let _ = sender.try_send(SHUTDOWN_MESSAGE.to_string());
         }
     }
 
+    fn push_system_message(&mut self, content: &str) {
+        self.messages.push(ChatMessage {
+            content: content.to_string(),
+            is_user: false,
+            ..Default::default()
+        });
+    }
+
+    // The doctor thread exited; its supervisor restarts it and sends the new channels
+    fn start_reconnecting(&mut self) {
+        // Drop the dead channels so they read as offline until the new ones arrive
+        *lock_or_recover(&self.response_receiver, "response receiver") = None;
+        *lock_or_recover(&self.user_sender, "user sender") = None;
+        if !self.reconnecting {
+            self.reconnecting = true;
+            self.push_system_message("⚠️ Lost connection to agent");
+            self.push_system_message("🔄 Reconnecting to agent…");
+        }
+    }
+
+    // Apply restart notices from the supervisor; returns true when the chat changed
+    fn handle_control_events(&mut self) -> bool {
+        let events: Vec<AgentControl> = {
+            let mut guard = lock_or_recover(&self.control_receiver, "control receiver");
+            let Some(receiver) = guard.as_mut() else {
+                return false;
+            };
+            std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+        };
+
+        let mut changed = false;
+        for event in events {
+            match event {
+                AgentControl::Reconnecting {
+                    attempt,
+                    max_attempts,
+                } => {
+                    println!(
+                        "🔄 Reconnecting to agent (attempt {}/{})",
+                        attempt, max_attempts
+                    );
+                    changed |= !self.reconnecting;
+                    self.start_reconnecting();
+                }
+                AgentControl::Restarted(channels) => {
+                    println!("🔌 Installing channels to the restarted agent");
+                    self.connecting = false;
+                    self.install_channels(channels);
+                }
+                AgentControl::Failed(e) => {
+                    eprintln!("❌ Could not start the doctor agent: {}", e);
+                    self.connecting = false;
+                    self.push_system_message(&format!(
+                        "⚠️ Could not start the doctor agent: {}",
+                        e
+                    ));
+                    changed = true;
+                }
+                AgentControl::GaveUp => {
+                    self.reconnecting = false;
+                    self.push_system_message(
+                        "❌ Could not re-establish the agent connection. Please restart the application.",
+                    );
+                    changed = true;
+                }
+            }
+        }
+
+        // The new thread is taking queries again
+        if self.reconnecting && self.is_doctor_online() {
+            self.reconnecting = false;
+            self.push_system_message("✅ Reconnected to agent");
+            changed = true;
+        }
+        changed
+    }
+
+    // Put the cursor back in the message input so the next query can be typed right away
+    fn focus_input(&self) -> Task<Message> {
+        text_input::focus(self.input_id.clone())
+    }
+
+    // Snap the chat to the newest message unless the user scrolled up to read
+    fn scroll_to_latest(&self) -> Task<Message> {
+        if self.follow_new_messages {
+            scrollable::snap_to(self.chat_scroll_id.clone(), RelativeOffset::END)
+        } else {
+            Task::none()
+        }
+    }
+
+    // Keyboard shortcuts: Esc clears the input, Up recalls the last sent message, Ctrl+L clears the chat.
+    // Tick polls the agent channels; this timer is the only thing that sends it, so there is
+    // exactly one polling loop however many queries are sent.
+    pub fn subscription(&self) -> Subscription<Message> {
+        let poll = iced::time::every(self.poll_interval).map(|_| Message::Tick);
+        let events = iced::event::listen_with(|event, _status, _window| match event {
+            iced::Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                match key.as_ref() {
+                    Key::Named(Named::Escape) => Some(Message::ClearInput),
+                    Key::Named(Named::ArrowUp) if modifiers.is_empty() => {
+                        Some(Message::RecallLastMessage)
+                    }
+                    Key::Character("l") if modifiers.control() => Some(Message::ClearConversation),
+                    Key::Character("f") if modifiers.control() => Some(Message::ToggleSearch),
+                    Key::Character("=" | "+") if modifiers.control() => Some(Message::ZoomIn),
+                    Key::Character("-") if modifiers.control() => Some(Message::ZoomOut),
+                    Key::Character("0") if modifiers.control() => Some(Message::ResetZoom),
+                    _ => None,
+                }
+            }
+            iced::Event::Window(iced::window::Event::CloseRequested) => {
+                Some(Message::CloseRequested)
+            }
+            iced::Event::Window(iced::window::Event::Focused) => {
+                Some(Message::WindowFocusChanged(true))
+            }
+            iced::Event::Window(iced::window::Event::Unfocused) => {
+                Some(Message::WindowFocusChanged(false))
+            }
+            _ => None,
+        });
+        Subscription::batch([poll, events])
+    }
+
     pub fn title(&self) -> String {
-        String::from("LiquidOS - AI Medical Assistant")
+        self.window_title.clone()
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
@@ -50,163 +780,792 @@ impl ChatApp {
                 self.input_value = value;
             }
             Message::SendMessage => {
+                if self.send_locked() {
+                    return Task::none();
+                }
+                if let Some(attachment) = self.attachment.take() {
+                    return self.send_attachment(attachment);
+                }
                 if !self.input_value.trim().is_empty() {
                     let content = self.input_value.clone();
 
+                    // Send message to doctor agent with USER_SEND prefix to identify actual send events.
+                    // When the agent can't take it yet, the text stays in the input for a retry.
+                    if let Err(busy) = self.send_to_doctor(&content) {
+                        self.push_system_message(&busy);
+                        return self.scroll_to_latest();
+                    }
+
                     // Add user message to chat
                     self.messages.push(ChatMessage {
                         content: content.clone(),
                         is_user: true,
+                        ..Default::default()
                     });
 
-                    // Send message to doctor agent with USER_SEND prefix to identify actual send events
-                    if let Some(sender) = self.user_sender.lock().unwrap().as_ref() {
-                        let _ = sender.send(format!("USER_SEND:{}", content));
+                    if let Some(warning) = self.offline_agent_warning(&content) {
+                        self.push_system_message(&warning);
                     }
+                    self.save_conversation();
+                    self.last_query = Some(content);
+                    self.regenerate_index = None;
 
                     self.input_value.clear();
+                    self.waiting = true;
+                    self.waiting_since = Some(Instant::now());
 
-                    // Immediately check for responses after sending
-                    return Task::done(Message::Tick);
+                    // Sending a message always brings the conversation back into view
+                    self.follow_new_messages = true;
+
+                    return Task::batch([self.scroll_to_latest(), self.focus_input()]);
                 }
             }
             Message::ReceivedDoctorResponse(response) => {
+                self.notify_if_unfocused(&response);
                 self.messages.push(ChatMessage {
                     content: response,
                     is_user: false,
+                    ..Default::default()
                 });
+                return self.scroll_to_latest();
+            }
+            Message::KeyInputChanged(value) => {
+                self.key_input = value;
+            }
+            Message::RetryKey => {
+                let Some(setup) = &self.key_setup else {
+                    return Task::none();
+                };
+                let entered = Some(self.key_input.trim().to_string()).filter(|k| !k.is_empty());
+                match (setup.retry)(entered) {
+                    Ok(connector) => {
+                        println!("🔑 API key accepted, starting the doctor agent");
+                        self.key_setup = None;
+                        self.key_input.clear();
+                        self.connect(connector);
+                        return self.focus_input();
+                    }
+                    Err(e) => {
+                        println!("❌ API key still unusable: {}", e);
+                        if let Some(setup) = &mut self.key_setup {
+                            setup.error = e;
+                        }
+                    }
+                }
+            }
+            Message::WindowFocusChanged(focused) => {
+                self.window_focused = focused;
+            }
+            Message::ClearInput => {
+                self.input_value.clear();
+            }
+            Message::QuickAction(index) => {
+                let Some(action) = self.quick_actions.get(index) else {
+                    return Task::none();
+                };
+                self.input_value = action.query.clone();
+                if action.auto_send {
+                    return self.update(Message::SendMessage);
+                }
+                // Cursor at the end so the query can be completed before sending
+                return text_input::move_cursor_to_end(self.input_id.clone());
+            }
+            Message::RecallLastMessage => {
+                // Only recall into an empty input so we never overwrite what the user is typing
+                if !self.input_value.is_empty() {
+                    return Task::none();
+                }
+                if let Some(last) = self.messages.iter().rev().find(|m| m.is_user) {
+                    self.input_value = last.content.clone();
+                }
+            }
+            Message::ClearConversation => {
+                self.messages = load_messages_from(Vec::new());
+                self.pending_reasoning.clear();
+                self.last_query = None;
+                self.regenerate_index = None;
+                self.show_pinned_only = false;
+                self.follow_new_messages = true;
+                self.save_conversation();
+            }
+            Message::SwitchConversation(name) => {
+                return self.switch_conversation(name);
+            }
+            Message::NewConversationNameChanged(name) => {
+                self.new_conversation_name = name;
+            }
+            Message::NewConversation => {
+                let name = std::mem::take(&mut self.new_conversation_name);
+                return self.switch_conversation(name);
+            }
+            Message::CancelRequest => {
+                if self.waiting {
+                    if let Some(sender) = &self.cancel_sender {
+                        let _ = sender.send(());
+                    }
+                    self.waiting = false;
+                    self.push_system_message("Request cancelled.");
+                    return self.scroll_to_latest();
+                }
+            }
+            Message::CloseRequested => {
+                // Closing would abandon the running request, so ask first
+                if request_in_flight(self.waiting, &self.queue_status) {
+                    self.confirm_close = true;
+                } else {
+                    return self.shut_down();
+                }
+            }
+            Message::ConfirmClose(close) => {
+                self.confirm_close = false;
+                if close {
+                    return self.shut_down();
+                }
+            }
+            Message::ConfirmCamera(approved) => {
+                if let Some(request) = self.pending_camera_confirm.take() {
+                    println!(
+                        "📷 Camera capture {} for: {}",
+                        if approved { "approved" } else { "declined" },
+                        request.query
+                    );
+                    let _ = request.reply.send(approved);
+                }
+            }
+            Message::CopyMessage(index) => {
+                if let Some(msg) = self.messages.get(index) {
+                    self.copied_at = Some(Instant::now());
+                    return iced::clipboard::write(msg.content.clone());
+                }
+            }
+            Message::Regenerate => {
+                let (Some(index), Some(query)) =
+                    (self.regenerate_target(), self.last_query.clone())
+                else {
+                    return Task::none();
+                };
+                if let Err(busy) = self.send_to_doctor(&query) {
+                    self.push_system_message(&busy);
+                    return self.scroll_to_latest();
+                }
+                println!("🔄 Regenerating response for: {}", query);
+                self.regenerate_index = Some(index);
+                self.waiting = true;
+                self.waiting_since = Some(Instant::now());
+            }
+            Message::TogglePin(index) => {
+                if let Some(msg) = self.messages.get_mut(index) {
+                    msg.is_pinned = !msg.is_pinned;
+                }
+            }
+            Message::TogglePinnedOnly => {
+                self.show_pinned_only = !self.show_pinned_only;
+            }
+            Message::ZoomIn => self.set_zoom(self.zoom + ZOOM_STEP),
+            Message::ZoomOut => self.set_zoom(self.zoom - ZOOM_STEP),
+            Message::ResetZoom => self.set_zoom(1.0),
+            Message::ToggleReasoning(index) => {
+                if let Some(msg) = self.messages.get_mut(index) {
+                    msg.show_reasoning = !msg.show_reasoning;
+                }
+            }
+            Message::ToggleSearch => {
+                self.show_search = !self.show_search;
+                if self.show_search {
+                    return text_input::focus(self.search_input_id.clone());
+                }
+                self.search_query.clear();
+                return self.focus_input();
+            }
+            Message::SearchChanged(query) => {
+                self.search_query = query;
+            }
+            Message::AttachImage => {
+                return Task::perform(pick_image(), Message::ImageAttached);
+            }
+            Message::ImageAttached(attachment) => {
+                if attachment.is_some() {
+                    self.attachment = attachment;
+                }
+            }
+            Message::RemoveAttachment => {
+                self.attachment = None;
+            }
+            #[cfg(feature = "voice")]
+            Message::StartRecording => match voice::Recorder::start() {
+                Ok(recorder) => self.recorder = Some(recorder),
+                Err(e) => {
+                    self.push_system_message(&format!("🎤 Couldn't start recording: {}", e));
+                    return self.scroll_to_latest();
+                }
+            },
+            #[cfg(feature = "voice")]
+            Message::StopRecording => {
+                if let Some(recorder) = self.recorder.take() {
+                    self.transcribing = true;
+                    return Task::perform(
+                        async move { voice::transcribe(recorder.finish().await?).await },
+                        Message::Transcribed,
+                    );
+                }
+            }
+            #[cfg(feature = "voice")]
+            Message::Transcribed(result) => {
+                self.transcribing = false;
+                match result {
+                    // Dictation lands in the input so it can be checked before sending
+                    Ok(transcript) => {
+                        if !self.input_value.trim().is_empty() {
+                            self.input_value.push(' ');
+                        }
+                        self.input_value.push_str(&transcript);
+                    }
+                    Err(e) => {
+                        self.push_system_message(&format!("🎤 Couldn't transcribe: {}", e));
+                        return self.scroll_to_latest();
+                    }
+                }
+            }
+            Message::ToggleStatusPanel => {
+                self.show_status_panel = !self.show_status_panel;
+            }
+            Message::ToggleTheme => {
+                self.theme_mode = match self.theme_mode {
+                    ThemeMode::Dark => ThemeMode::Light,
+                    ThemeMode::Light => ThemeMode::Dark,
+                };
+                self.palette = Palette::for_mode(self.theme_mode);
+                let theme_mode = self.theme_mode;
+                prefs::update(|prefs| prefs.theme = Some(theme_mode));
+            }
+            Message::ToggleSettings => {
+                self.show_settings = !self.show_settings;
+                // Reopening starts from what is running, not from an abandoned edit
+                self.settings_model = self.llm_settings.model.clone();
+                self.settings_temperature = self.llm_settings.temperature;
+                self.settings_memory_window = self.llm_settings.memory_window.to_string();
+                self.settings_error = None;
+            }
+            Message::SettingsModelChanged(model) => self.settings_model = model,
+            Message::SettingsTemperatureChanged(temperature) => {
+                self.settings_temperature = temperature;
+            }
+            Message::SettingsMemoryWindowChanged(memory_window) => {
+                self.settings_memory_window = memory_window;
+            }
+            Message::ApplySettings => {
+                let settings = match self.settings_from_panel() {
+                    Ok(settings) => settings,
+                    Err(e) => {
+                        self.settings_error = Some(e);
+                        return Task::none();
+                    }
+                };
+                if settings != self.llm_settings
+                    && request_in_flight(self.waiting, &self.queue_status)
+                {
+                    self.settings_error = Some(
+                        "Wait for the running request to finish; applying restarts the doctor agent"
+                            .to_string(),
+                    );
+                    return Task::none();
+                }
+                self.show_settings = false;
+                self.settings_error = None;
+                if settings == self.llm_settings {
+                    return Task::none();
+                }
+                // The LLM and memory are fixed when the agents are built, so the doctor node
+                // restarts with the new settings
+                println!("⚙️ Applying settings: {:?}", settings);
+                let saved = settings.clone();
+                prefs::update(|prefs| prefs.llm = Some(saved));
+                self.llm_settings = settings;
+                self.push_system_message(&format!(
+                    "⚙️ Settings applied: model {}, temperature {}, memory window {}. The doctor agent restarts with them.",
+                    self.llm_settings.model,
+                    self.llm_settings.temperature,
+                    self.llm_settings.memory_window
+                ));
+                let Some(connector) = self.connector.clone() else {
+                    return self.scroll_to_latest();
+                };
+                self.stop_agent();
+                self.connect(connector);
+                return self.scroll_to_latest();
+            }
+            Message::ChatScrolled(viewport) => {
+                let max_offset = viewport.content_bounds().height - viewport.bounds().height;
+                self.follow_new_messages =
+                    max_offset - viewport.absolute_offset().y <= SCROLL_FOLLOW_THRESHOLD;
             }
             Message::Tick => {
+                // Collect reasoning steps first so they attach to the answer they led to
+                if let Some(receiver) =
+                    lock_or_recover(&self.reasoning_receiver, "reasoning receiver").as_mut()
+                {
+                    while let Ok(step) = receiver.try_recv() {
+                        self.pending_reasoning.push(step);
+                    }
+                }
+
                 // Check for new responses from the doctor agent
                 let mut found_messages = false;
-                if let Ok(mut guard) = self.response_receiver.lock() {
-                    if let Some(receiver) = guard.as_mut() {
-                        while let Ok(msg) = receiver.try_recv() {
-                            println!("📱 GUI successfully received response: {}", msg);
-                            self.messages.push(ChatMessage {
-                                content: msg,
-                                is_user: false,
-                            });
-                            found_messages = true;
+                let mut disconnected = false;
+                if let Some(receiver) =
+                    lock_or_recover(&self.response_receiver, "response receiver").as_mut()
+                {
+                    loop {
+                        match receiver.try_recv() {
+                            Ok(msg) => {
+                                println!("📱 GUI successfully received response: {}", msg.content);
+                                self.notify_if_unfocused(&msg.content);
+                                // Waveform plots arrive beside the answer and don't take its steps
+                                let reasoning = if msg.image.is_some() {
+                                    Vec::new()
+                                } else {
+                                    std::mem::take(&mut self.pending_reasoning)
+                                };
+                                let (is_final, is_error) = (msg.is_final, msg.is_error);
+                                let answer = ChatMessage {
+                                    content: msg.content,
+                                    is_user: false,
+                                    agent_source: msg.agent_source,
+                                    is_error: msg.is_error,
+                                    image: msg.image.map(image::Handle::from_bytes),
+                                    in_reply_to: msg.in_reply_to,
+                                    reasoning,
+                                    ..Default::default()
+                                };
+                                // A regenerated answer replaces the one it was asked for once
+                                // the final one arrives; notices, reports and the "request
+                                // submitted" step before it are added below as usual. A final
+                                // error (e.g. an AI service timeout) keeps the old answer.
+                                let replace = if is_final {
+                                    self.regenerate_index.take().filter(|_| !is_error)
+                                } else {
+                                    None
+                                };
+                                match replace.and_then(|index| self.messages.get_mut(index)) {
+                                    Some(previous) => *previous = answer,
+                                    None => self.messages.push(answer),
+                                }
+                                found_messages = true;
+                            }
+                            Err(TryRecvError::Empty) => break,
+                            Err(TryRecvError::Disconnected) => {
+                                println!("⚠️ Response channel closed - agent thread exited");
+                                disconnected = true;
+                                break;
+                            }
                         }
                     }
-                } else {
-                    println!("⚠️ Failed to acquire lock on response receiver");
                 }
 
-                // Schedule another check in 1 second
-                return Task::perform(
-                    async {
-                        async_std::task::sleep(std::time::Duration::from_secs(1)).await;
-                    },
-                    |_| Message::Tick,
-                );
+                // Surface the next camera capture waiting for approval
+                if self.pending_camera_confirm.is_none() {
+                    if let Some(receiver) =
+                        lock_or_recover(&self.camera_confirm_receiver, "camera confirm receiver")
+                            .as_mut()
+                    {
+                        self.pending_camera_confirm = receiver.try_recv().ok();
+                    }
+                }
+
+                // Keep only the latest queue snapshot; the Stop button follows the active query
+                if let Some(receiver) =
+                    lock_or_recover(&self.queue_receiver, "queue receiver").as_mut()
+                {
+                    while let Ok(status) = receiver.try_recv() {
+                        self.waiting = status.active.is_some() || status.queued > 0;
+                        self.queue_status = status;
+                    }
+                }
+
+                // Tick off user messages the doctor picked up
+                if let Some(receiver) = lock_or_recover(&self.ack_receiver, "ack receiver").as_mut()
+                {
+                    while let Ok(query) = receiver.try_recv() {
+                        mark_received(&mut self.messages, &query);
+                    }
+                }
+
+                if found_messages {
+                    self.waiting = false;
+                    self.save_conversation();
+                }
+                if disconnected {
+                    self.start_reconnecting();
+                    found_messages = true;
+                }
+                found_messages |= self.handle_control_events();
+
+                if found_messages {
+                    return self.scroll_to_latest();
+                }
             }
         }
         Task::none()
     }
 
-    pub fn view(&self) -> Element<Message> {
-        // Dark theme colors
-        let bg_primary = iced::Color::from_rgb(0.1, 0.1, 0.12); // Very dark blue-gray
-        let bg_secondary = iced::Color::from_rgb(0.15, 0.15, 0.18); // Slightly lighter
-        let bg_input = iced::Color::from_rgb(0.18, 0.18, 0.22); // Input background
-        let user_bubble = iced::Color::from_rgb(0.2, 0.4, 0.8); // User message blue
-        let ai_bubble = iced::Color::from_rgb(0.25, 0.25, 0.3); // AI message gray
-        let text_primary = iced::Color::WHITE;
-        let text_secondary = iced::Color::from_rgb(0.9, 0.9, 0.9);
-        let accent_green = iced::Color::from_rgb(0.2, 0.8, 0.4);
-
-        let messages_view =
-            self.messages
-                .iter()
-                .fold(Column::new().spacing(12).padding(20), |column, msg| {
-                    let message_content = text(&msg.content).size(15).color(text_primary);
+    // Side panel listing the saved conversations, with a field to start a new one
+    fn conversation_sidebar(&self) -> Element<Message> {
+        let palette = self.palette;
 
-                    let message_bubble = if msg.is_user {
-                        // User message - right aligned, blue bubble
-                        container(message_content)
-                            .padding([12, 16])
-                            .style(move |_theme: &Theme| container::Style {
-                                background: Some(iced::Background::Color(user_bubble)),
-                                text_color: Some(text_primary),
-                                border: iced::Border {
-                                    radius: 16.0.into(),
-                                    width: 0.0,
-                                    color: iced::Color::TRANSPARENT,
-                                },
-                                shadow: iced::Shadow {
-                                    color: iced::Color::BLACK,
-                                    offset: iced::Vector::new(0.0, 2.0),
-                                    blur_radius: 8.0,
-                                },
-                            })
-                            .max_width(500)
-                    } else {
-                        // AI message - left aligned, gray bubble
-                        container(message_content)
-                            .padding([12, 16])
-                            .style(move |_theme: &Theme| container::Style {
-                                background: Some(iced::Background::Color(ai_bubble)),
-                                text_color: Some(text_primary),
-                                border: iced::Border {
-                                    radius: 16.0.into(),
-                                    width: 0.0,
-                                    color: iced::Color::TRANSPARENT,
-                                },
-                                shadow: iced::Shadow {
-                                    color: iced::Color::BLACK,
-                                    offset: iced::Vector::new(0.0, 2.0),
-                                    blur_radius: 8.0,
-                                },
-                            })
-                            .max_width(500)
-                    };
+        let list = self.conversations.iter().fold(
+            Column::new().spacing(4).push(
+                text("Conversations")
+                    .size(self.text_size(14.0))
+                    .color(palette.text_primary),
+            ),
+            |column, name| {
+                let active = *name == self.active_conversation;
+                column.push(
+                    button(
+                        text(name.clone())
+                            .size(self.text_size(13.0))
+                            .color(if active {
+                                palette.text_primary
+                            } else {
+                                palette.text_secondary
+                            }),
+                    )
+                    .on_press(Message::SwitchConversation(name.clone()))
+                    .width(Length::Fill)
+                    .padding([6, 8])
+                    .style(move |_theme: &Theme, status| button::Style {
+                        background: (active || matches!(status, button::Status::Hovered))
+                            .then_some(iced::Background::Color(palette.bg_input)),
+                        text_color: palette.text_primary,
+                        border: iced::Border {
+                            radius: 6.0.into(),
+                            width: 0.0,
+                            color: iced::Color::TRANSPARENT,
+                        },
+                        ..Default::default()
+                    }),
+                )
+            },
+        );
 
-                    let message_row = if msg.is_user {
-                        row![]
-                            .push(iced::widget::Space::with_width(Length::Fill))
-                            .push(message_bubble)
-                            .spacing(8)
-                    } else {
-                        row![]
-                            .push(container(text("AI").size(12)).padding([6, 10]).style(
-                                move |_theme: &Theme| container::Style {
-                                    background: Some(iced::Background::Color(accent_green)),
-                                    text_color: Some(text_primary),
-                                    border: iced::Border {
-                                        radius: 12.0.into(),
-                                        width: 0.0,
-                                        color: iced::Color::TRANSPARENT,
-                                    },
-                                    ..Default::default()
-                                },
-                            ))
+        let new_name = text_input("New conversation…", &self.new_conversation_name)
+            .on_input(Message::NewConversationNameChanged)
+            .on_submit(Message::NewConversation)
+            .padding(8)
+            .size(self.text_size(13.0))
+            .style(move |_theme: &Theme, _status| text_input::Style {
+                background: iced::Background::Color(palette.bg_input),
+                border: iced::Border {
+                    radius: 6.0.into(),
+                    width: 1.0,
+                    color: palette.border,
+                },
+                icon: palette.text_secondary,
+                placeholder: palette.text_secondary,
+                value: palette.text_primary,
+                selection: palette.selection,
+            });
+
+        container(
+            column![
+                scrollable(list).height(Length::Fill),
+                new_name,
+                header_button(
+                    self.zoom,
+                    "+ New conversation",
+                    Message::NewConversation,
+                    palette
+                ),
+            ]
+            .spacing(8),
+        )
+        .padding(16)
+        .width(Length::Fixed(200.0))
+        .height(Length::Fill)
+        .style(move |_theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(palette.bg_secondary)),
+            border: iced::Border {
+                radius: 0.0.into(),
+                width: 1.0,
+                color: palette.divider,
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+
+    // Side panel listing each known agent with an online/offline dot
+    fn status_panel(&self) -> Element<Message> {
+        let palette = self.palette;
+
+        let mut agents = vec![("Doctor Agent", self.is_doctor_online())];
+        agents.extend(
+            KNOWN_AGENTS
+                .iter()
+                .map(|(kind, label)| (*label, self.is_agent_online(kind))),
+        );
+
+        let panel = agents.into_iter().fold(
+            Column::new().spacing(10).push(
+                text("Cluster agents")
+                    .size(self.text_size(14.0))
+                    .color(palette.text_primary),
+            ),
+            |column, (label, online)| {
+                column.push(
+                    row![
+                        text("●").size(self.text_size(14.0)).color(if online {
+                            palette.accent_green
+                        } else {
+                            palette.danger
+                        }),
+                        text(label)
+                            .size(self.text_size(13.0))
+                            .color(palette.text_secondary),
+                    ]
+                    .spacing(8)
+                    .align_y(Alignment::Center),
+                )
+            },
+        );
+
+        container(panel)
+            .padding(16)
+            .width(Length::Fixed(190.0))
+            .height(Length::Fill)
+            .style(move |_theme: &Theme| container::Style {
+                background: Some(iced::Background::Color(palette.bg_secondary)),
+                border: iced::Border {
+                    radius: 0.0.into(),
+                    width: 1.0,
+                    color: palette.divider,
+                },
+                ..Default::default()
+            })
+            .into()
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let palette = self.palette;
+
+        // While searching, only matching messages are shown and they get an accent outline
+        let searching = self.show_search && !self.search_query.trim().is_empty();
+        let highlight = if searching {
+            iced::Border {
+                radius: 16.0.into(),
+                width: 2.0,
+                color: palette.accent_green,
+            }
+        } else {
+            iced::Border {
+                radius: 16.0.into(),
+                width: 0.0,
+                color: iced::Color::TRANSPARENT,
+            }
+        };
+
+        let regenerate_target = self.regenerate_target();
+        let messages_view = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| !searching || message_matches(&msg.content, &self.search_query))
+            .filter(|(_, msg)| !self.show_pinned_only || msg.is_pinned)
+            .fold(
+                Column::new().spacing(12).padding(20),
+                |column, (index, msg)| {
+                    let content_color = if msg.is_user {
+                        palette.user_text
+                    } else {
+                        palette.text_primary
+                    };
+                    let mut message_content = column![].spacing(8);
+                    if msg.is_pinned {
+                        message_content = message_content
+                            .push(text("★ Pinned").size(self.text_size(12.0)).color(PIN_COLOR));
+                    }
+                    // Pinned bubbles keep a gold outline, which takes precedence over the error
+                    // outline and search
+                    let highlight = if msg.is_pinned {
+                        iced::Border {
+                            radius: 16.0.into(),
+                            width: 2.0,
+                            color: PIN_COLOR,
+                        }
+                    } else if msg.is_error {
+                        iced::Border {
+                            radius: 16.0.into(),
+                            width: 2.0,
+                            color: palette.danger,
+                        }
+                    } else {
+                        highlight
+                    };
+                    if let Some(question) = &msg.in_reply_to {
+                        message_content = message_content.push(
+                            text(format!("↪ Re: {}", reply_preview(question)))
+                                .size(self.text_size(12.0))
+                                .color(palette.text_secondary),
+                        );
+                    }
+                    if let Some(handle) = &msg.image {
+                        message_content = message_content
+                            .push(image(handle.clone()).width(Length::Fixed(IMAGE_PREVIEW_WIDTH)));
+                    }
+                    message_content = message_content.push(
+                        text(with_soft_breaks(&msg.content))
+                            .size(self.text_size(15.0))
+                            .color(content_color),
+                    );
+                    if msg.is_user && msg.received {
+                        message_content = message_content.push(
+                            text("✓ received")
+                                .size(self.text_size(11.0))
+                                .color(iced::Color {
+                                    a: 0.7,
+                                    ..palette.user_text
+                                }),
+                        );
+                    }
+                    if !msg.reasoning.is_empty() {
+                        let label = if msg.show_reasoning {
+                            "▾ Hide reasoning".to_string()
+                        } else {
+                            format!("▸ Reasoning ({} steps)", msg.reasoning.len())
+                        };
+                        message_content = message_content.push(
+                            button(
+                                text(label)
+                                    .size(self.text_size(12.0))
+                                    .color(palette.text_secondary),
+                            )
+                            .on_press(Message::ToggleReasoning(index))
+                            .padding(0)
+                            .style(move |_theme: &Theme, _status| button::Style {
+                                background: None,
+                                text_color: palette.text_secondary,
+                                ..Default::default()
+                            }),
+                        );
+                        if msg.show_reasoning {
+                            message_content =
+                                msg.reasoning.iter().fold(message_content, |steps, step| {
+                                    steps.push(
+                                        text(step)
+                                            .size(self.text_size(13.0))
+                                            .color(palette.text_secondary),
+                                    )
+                                });
+                        }
+                    }
+
+                    let message_bubble = if msg.is_user {
+                        // User message - right aligned, blue bubble
+                        container(message_content)
+                            .padding([12, 16])
+                            .style(move |_theme: &Theme| container::Style {
+                                background: Some(iced::Background::Color(palette.user_bubble)),
+                                text_color: Some(palette.user_text),
+                                border: highlight,
+                                shadow: iced::Shadow {
+                                    color: iced::Color::BLACK,
+                                    offset: iced::Vector::new(0.0, 2.0),
+                                    blur_radius: 8.0,
+                                },
+                            })
+                            .max_width(500)
+                    } else {
+                        // AI message - left aligned, gray bubble
+                        container(message_content)
+                            .padding([12, 16])
+                            .style(move |_theme: &Theme| container::Style {
+                                background: Some(iced::Background::Color(palette.ai_bubble)),
+                                text_color: Some(palette.text_primary),
+                                border: highlight,
+                                shadow: iced::Shadow {
+                                    color: iced::Color::BLACK,
+                                    offset: iced::Vector::new(0.0, 2.0),
+                                    blur_radius: 8.0,
+                                },
+                            })
+                            .max_width(500)
+                    };
+
+                    let copy_button =
+                        icon_button(self.zoom, "⧉", Message::CopyMessage(index), palette);
+                    let pin_button = icon_button(
+                        self.zoom,
+                        if msg.is_pinned { "★" } else { "☆" },
+                        Message::TogglePin(index),
+                        palette,
+                    );
+
+                    let message_row = if msg.is_user {
+                        row![]
+                            .push(iced::widget::Space::with_width(Length::Fill))
+                            .push(pin_button)
+                            .push(copy_button)
+                            .push(message_bubble)
+                            .spacing(8)
+                    } else {
+                        let (tag, tag_color) = if msg.is_error {
+                            ("ERR", palette.danger)
+                        } else {
+                            agent_tag(msg.agent_source.as_deref(), palette)
+                        };
+                        row![]
+                            .push(
+                                container(text(tag).size(self.text_size(12.0)))
+                                    .padding([6, 10])
+                                    .style(move |_theme: &Theme| container::Style {
+                                        background: Some(iced::Background::Color(tag_color)),
+                                        text_color: Some(palette.user_text),
+                                        border: iced::Border {
+                                            radius: 12.0.into(),
+                                            width: 0.0,
+                                            color: iced::Color::TRANSPARENT,
+                                        },
+                                        ..Default::default()
+                                    }),
+                            )
                             .push(message_bubble)
+                            .push(copy_button)
+                            .push(pin_button)
+                            .push_maybe((regenerate_target == Some(index)).then(|| {
+                                icon_button(
+                                    self.zoom,
+                                    "🔄 Regenerate",
+                                    Message::Regenerate,
+                                    palette,
+                                )
+                            }))
                             .push(iced::widget::Space::with_width(Length::Fill))
                             .spacing(8)
                             .align_y(Alignment::Start)
                     };
 
                     column.push(message_row)
-                });
+                },
+            );
 
         let chat_area = scrollable(container(messages_view).width(Length::Fill).style(
             move |_theme: &Theme| container::Style {
-                background: Some(iced::Background::Color(bg_primary)),
+                background: Some(iced::Background::Color(palette.bg_primary)),
                 ..Default::default()
             },
         ))
+        .id(self.chat_scroll_id.clone())
+        .on_scroll(Message::ChatScrolled)
         .style(move |_theme: &Theme, _status| scrollable::Style {
             container: container::Style {
-                background: Some(iced::Background::Color(bg_primary)),
+                background: Some(iced::Background::Color(palette.bg_primary)),
                 ..Default::default()
             },
             vertical_rail: scrollable::Rail {
-                background: Some(iced::Background::Color(bg_secondary)),
+                background: Some(iced::Background::Color(palette.bg_secondary)),
                 border: iced::Border::default(),
                 scroller: scrollable::Scroller {
-                    color: iced::Color::from_rgb(0.4, 0.4, 0.5),
+                    color: palette.scroller,
                     border: iced::Border {
                         radius: 2.0.into(),
                         width: 0.0,
@@ -215,10 +1574,10 @@ impl ChatApp {
                 },
             },
             horizontal_rail: scrollable::Rail {
-                background: Some(iced::Background::Color(bg_secondary)),
+                background: Some(iced::Background::Color(palette.bg_secondary)),
                 border: iced::Border::default(),
                 scroller: scrollable::Scroller {
-                    color: iced::Color::from_rgb(0.4, 0.4, 0.5),
+                    color: palette.scroller,
                     border: iced::Border {
                         radius: 2.0.into(),
                         width: 0.0,
@@ -231,92 +1590,281 @@ impl ChatApp {
         .height(Length::FillPortion(4));
 
         let input_field = text_input("Type your message here...", &self.input_value)
+            .id(self.input_id.clone())
             .on_input(Message::InputChanged)
             .padding(16)
-            .size(16)
+            .size(self.text_size(16.0))
             .style(move |_theme: &Theme, _status| text_input::Style {
-                background: iced::Background::Color(bg_input),
+                background: iced::Background::Color(palette.bg_input),
                 border: iced::Border {
                     radius: 12.0.into(),
                     width: 1.0,
-                    color: iced::Color::from_rgb(0.3, 0.3, 0.4),
+                    color: palette.border,
                 },
-                icon: text_secondary,
-                placeholder: text_secondary,
-                value: text_primary,
-                selection: iced::Color::from_rgb(0.3, 0.5, 0.9),
+                icon: palette.text_secondary,
+                placeholder: palette.text_secondary,
+                value: palette.text_primary,
+                selection: palette.selection,
             });
 
-        let send_button = button(text("Send").size(15).color(text_primary))
-            .on_press(Message::SendMessage)
-            .padding([14, 20])
-            .style(move |_theme: &Theme, status| match status {
-                button::Status::Hovered => button::Style {
-                    background: Some(iced::Background::Color(iced::Color::from_rgb(
-                        0.25, 0.85, 0.45,
-                    ))),
-                    text_color: text_primary,
-                    border: iced::Border {
-                        radius: 8.0.into(),
-                        width: 0.0,
-                        color: iced::Color::TRANSPARENT,
-                    },
-                    shadow: iced::Shadow {
-                        color: iced::Color::BLACK,
-                        offset: iced::Vector::new(0.0, 4.0),
-                        blur_radius: 8.0,
-                    },
+        let channel_full = self.is_send_channel_full();
+        let send_locked = self.send_locked();
+        let send_label = if send_locked {
+            let elapsed = self
+                .waiting_since
+                .map(|since| since.elapsed())
+                .unwrap_or_default();
+            format!("{} Waiting", spinner_frame(elapsed))
+        } else if channel_full {
+            "Busy…".to_string()
+        } else {
+            "Send".to_string()
+        };
+        let send_button = button(
+            text(send_label)
+                .size(self.text_size(15.0))
+                .color(palette.user_text),
+        )
+        .on_press_maybe((!channel_full && !send_locked).then_some(Message::SendMessage))
+        .padding([14, 20])
+        .style(move |_theme: &Theme, status| match status {
+            button::Status::Hovered => button::Style {
+                background: Some(iced::Background::Color(palette.accent_hovered)),
+                text_color: palette.user_text,
+                border: iced::Border {
+                    radius: 8.0.into(),
+                    width: 0.0,
+                    color: iced::Color::TRANSPARENT,
                 },
-                button::Status::Pressed => button::Style {
-                    background: Some(iced::Background::Color(iced::Color::from_rgb(
-                        0.15, 0.75, 0.35,
-                    ))),
-                    text_color: text_primary,
-                    border: iced::Border {
-                        radius: 8.0.into(),
-                        width: 0.0,
-                        color: iced::Color::TRANSPARENT,
-                    },
-                    shadow: iced::Shadow {
-                        color: iced::Color::BLACK,
-                        offset: iced::Vector::new(0.0, 1.0),
-                        blur_radius: 2.0,
-                    },
+                shadow: iced::Shadow {
+                    color: iced::Color::BLACK,
+                    offset: iced::Vector::new(0.0, 4.0),
+                    blur_radius: 8.0,
                 },
-                _ => button::Style {
-                    background: Some(iced::Background::Color(accent_green)),
-                    text_color: text_primary,
-                    border: iced::Border {
-                        radius: 8.0.into(),
-                        width: 0.0,
-                        color: iced::Color::TRANSPARENT,
-                    },
-                    shadow: iced::Shadow {
-                        color: iced::Color::BLACK,
-                        offset: iced::Vector::new(0.0, 2.0),
-                        blur_radius: 4.0,
-                    },
+            },
+            button::Status::Pressed => button::Style {
+                background: Some(iced::Background::Color(palette.accent_pressed)),
+                text_color: palette.user_text,
+                border: iced::Border {
+                    radius: 8.0.into(),
+                    width: 0.0,
+                    color: iced::Color::TRANSPARENT,
                 },
-            });
+                shadow: iced::Shadow {
+                    color: iced::Color::BLACK,
+                    offset: iced::Vector::new(0.0, 1.0),
+                    blur_radius: 2.0,
+                },
+            },
+            button::Status::Disabled => button::Style {
+                background: Some(iced::Background::Color(palette.border)),
+                text_color: palette.user_text,
+                border: iced::Border {
+                    radius: 8.0.into(),
+                    width: 0.0,
+                    color: iced::Color::TRANSPARENT,
+                },
+                ..Default::default()
+            },
+            _ => button::Style {
+                background: Some(iced::Background::Color(palette.accent_green)),
+                text_color: palette.user_text,
+                border: iced::Border {
+                    radius: 8.0.into(),
+                    width: 0.0,
+                    color: iced::Color::TRANSPARENT,
+                },
+                shadow: iced::Shadow {
+                    color: iced::Color::BLACK,
+                    offset: iced::Vector::new(0.0, 2.0),
+                    blur_radius: 4.0,
+                },
+            },
+        });
+
+        let attach_button = button(
+            text("📎 Attach")
+                .size(self.text_size(15.0))
+                .color(palette.text_primary),
+        )
+        .on_press(Message::AttachImage)
+        .padding([14, 16])
+        .style(move |_theme: &Theme, _status| button::Style {
+            background: Some(iced::Background::Color(palette.bg_input)),
+            text_color: palette.text_primary,
+            border: iced::Border {
+                radius: 8.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        });
 
-        let input_area = row![input_field, send_button]
+        let mut input_area = row![attach_button]
             .spacing(12)
             .padding(20)
             .align_y(Alignment::Center);
+        #[cfg(feature = "voice")]
+        {
+            input_area = input_area.push(self.voice_button());
+        }
+        input_area = input_area.push(input_field).push(send_button);
+        if self.waiting {
+            let stop_button = button(
+                text("Stop")
+                    .size(self.text_size(15.0))
+                    .color(palette.user_text),
+            )
+            .on_press(Message::CancelRequest)
+            .padding([14, 20])
+            .style(move |_theme: &Theme, _status| button::Style {
+                background: Some(iced::Background::Color(palette.danger)),
+                text_color: palette.user_text,
+                border: iced::Border {
+                    radius: 8.0.into(),
+                    width: 0.0,
+                    color: iced::Color::TRANSPARENT,
+                },
+                ..Default::default()
+            });
+            input_area = input_area.push(stop_button);
+        }
+        let input_area: Element<Message> = if self.quick_actions.is_empty() {
+            input_area.into()
+        } else {
+            let locked = channel_full || send_locked;
+            let buttons = self
+                .quick_actions
+                .iter()
+                .enumerate()
+                .map(|(index, action)| {
+                    // Auto-sent actions wait like the Send button; the others only fill the input
+                    let message =
+                        (!(action.auto_send && locked)).then_some(Message::QuickAction(index));
+                    quick_action_button(self.zoom, &action.label, message, palette)
+                });
+            column![row(buttons).spacing(8).padding([0, 20]).wrap(), input_area]
+                .padding([8, 0])
+                .into()
+        };
+        let input_area: Element<Message> = match &self.attachment {
+            Some(attachment) => column![
+                row![
+                    text(format!("📎 {}", attachment.file_name))
+                        .size(self.text_size(13.0))
+                        .color(palette.text_secondary),
+                    header_button(self.zoom, "Remove", Message::RemoveAttachment, palette),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center)
+                .padding([0, 20]),
+                input_area
+            ]
+            .padding([8, 0])
+            .into(),
+            None => input_area.into(),
+        };
+        let input_area = match &self.queue_status.active {
+            Some(active) => {
+                let mut status = format!("⏳ Working on: {}", active);
+                if self.queue_status.queued > 0 {
+                    status.push_str(&format!(" · {} queued", self.queue_status.queued));
+                }
+                column![
+                    container(
+                        text(status)
+                            .size(self.text_size(13.0))
+                            .color(palette.text_secondary)
+                    )
+                    .padding([0, 20]),
+                    input_area
+                ]
+                .padding([8, 0])
+            }
+            None => column![input_area],
+        };
+        // Redrawn on every Tick, so the timer advances without its own subscription
+        let footer = container(
+            text(format!(
+                "{} messages · session {}",
+                self.messages.len(),
+                format_session_duration(self.started_at.elapsed())
+            ))
+            .size(self.text_size(11.0))
+            .color(palette.text_secondary),
+        )
+        .padding([4, 20]);
+        let input_area = input_area.push(footer);
 
         let header = container(
             row![
-                text("LiquidOS AI").size(20).color(text_primary),
+                text("LiquidOS AI")
+                    .size(self.text_size(20.0))
+                    .color(palette.text_primary),
+                text(
+                    patient::active()
+                        .map(|context| context.summary())
+                        .unwrap_or_default()
+                )
+                .size(self.text_size(14.0))
+                .color(palette.text_secondary),
                 iced::widget::Space::with_width(Length::Fill),
-                text("Online").size(14).color(accent_green)
+                if self.reconnecting {
+                    text("Reconnecting to agent…")
+                        .size(self.text_size(14.0))
+                        .color(PIN_COLOR)
+                } else if self.connecting {
+                    text("Starting agent…")
+                        .size(self.text_size(14.0))
+                        .color(PIN_COLOR)
+                } else {
+                    text("Online")
+                        .size(self.text_size(14.0))
+                        .color(palette.accent_green)
+                },
+                header_button(
+                    self.zoom,
+                    if self.show_pinned_only {
+                        "Show all"
+                    } else {
+                        "★ Pinned only"
+                    },
+                    Message::TogglePinnedOnly,
+                    palette,
+                ),
+                header_button(
+                    self.zoom,
+                    if self.show_status_panel {
+                        "Hide agents"
+                    } else {
+                        "Agents"
+                    },
+                    Message::ToggleStatusPanel,
+                    palette,
+                ),
+                header_button(self.zoom, "⚙ Settings", Message::ToggleSettings, palette),
+                header_button(self.zoom, "A−", Message::ZoomOut, palette),
+                text(format!("{:.0}%", self.zoom * 100.0))
+                    .size(self.text_size(13.0))
+                    .color(palette.text_secondary),
+                header_button(self.zoom, "A+", Message::ZoomIn, palette),
+                header_button(
+                    self.zoom,
+                    match self.theme_mode {
+                        ThemeMode::Dark => "☀ Light",
+                        ThemeMode::Light => "☾ Dark",
+                    },
+                    Message::ToggleTheme,
+                    palette,
+                ),
             ]
             .spacing(10)
             .align_y(Alignment::Center),
         )
         .padding(20)
         .style(move |_theme: &Theme| container::Style {
-            background: Some(iced::Background::Color(bg_secondary)),
-            text_color: Some(text_primary),
+            background: Some(iced::Background::Color(palette.bg_secondary)),
+            text_color: Some(palette.text_primary),
             border: iced::Border {
                 radius: 0.0.into(),
                 width: 0.0,
@@ -329,51 +1877,853 @@ impl ChatApp {
             },
         });
 
-        let content = column![
-            header,
+        let mut main_area = row![
+            self.conversation_sidebar(),
             container(chat_area)
-                .height(Length::FillPortion(4))
+                .height(Length::Fill)
                 .width(Length::Fill)
                 .style(move |_theme: &Theme| {
                     container::Style {
-                        background: Some(iced::Background::Color(bg_primary)),
+                        background: Some(iced::Background::Color(palette.bg_primary)),
                         ..Default::default()
                     }
-                }),
-            container(input_area)
-                .width(Length::Fill)
-                .style(move |_theme: &Theme| {
-                    container::Style {
-                        background: Some(iced::Background::Color(bg_secondary)),
+                })
+        ];
+        if self.show_status_panel {
+            main_area = main_area.push(self.status_panel());
+        }
+
+        let mut content = column![header];
+        if self.show_search {
+            content = content.push(self.search_bar());
+        }
+        let content = content
+            .push(
+                container(main_area)
+                    .height(Length::FillPortion(4))
+                    .width(Length::Fill),
+            )
+            .push(
+                container(input_area)
+                    .width(Length::Fill)
+                    .style(move |_theme: &Theme| container::Style {
+                        background: Some(iced::Background::Color(palette.bg_secondary)),
                         border: iced::Border {
                             radius: 0.0.into(),
                             width: 1.0,
-                            color: iced::Color::from_rgb(0.2, 0.2, 0.25),
+                            color: palette.divider,
                         },
                         ..Default::default()
-                    }
-                })
-        ];
+                    }),
+            );
 
-        container(content)
+        let base = container(content)
             .width(Length::Fill)
             .height(Length::Fill)
             .style(move |_theme: &Theme| container::Style {
-                background: Some(iced::Background::Color(bg_primary)),
+                background: Some(iced::Background::Color(palette.bg_primary)),
+                ..Default::default()
+            });
+
+        let base: Element<Message> = if self
+            .copied_at
+            .is_some_and(|at| at.elapsed() < COPIED_TOAST_DURATION)
+        {
+            stack![base, self.copied_toast()].into()
+        } else {
+            base.into()
+        };
+
+        if let Some(setup) = &self.key_setup {
+            return modal(base, self.key_setup_dialog(setup));
+        }
+        if self.confirm_close {
+            return modal(base, self.close_confirm_dialog());
+        }
+        if self.show_settings {
+            return modal(base, self.settings_dialog());
+        }
+        match &self.pending_camera_confirm {
+            Some(request) => modal(base, self.camera_confirm_dialog(&request.query)),
+            None => base,
+        }
+    }
+
+    // Explains how to provide the API key and lets the user paste one and retry
+    fn key_setup_dialog<'a>(&'a self, setup: &'a KeySetup) -> Element<'a, Message> {
+        let palette = self.palette;
+        container(
+            column![
+                text("🔑 LLM API key required")
+                    .size(self.text_size(18.0))
+                    .color(palette.text_primary),
+                text(&setup.error).size(self.text_size(14.0)).color(palette.danger),
+                text(format!(
+                    "Set {} in your environment before starting (e.g. `export {}=...`), or paste the key below. A pasted key is only used for this session.",
+                    setup.key_var, setup.key_var
+                ))
+                .size(self.text_size(14.0))
+                .color(palette.text_secondary),
+                text_input(setup.key_var, &self.key_input)
+                    .on_input(Message::KeyInputChanged)
+                    .on_submit(Message::RetryKey)
+                    .secure(true)
+                    .padding(10)
+                    .size(self.text_size(14.0)),
+                row![
+                    iced::widget::Space::with_width(Length::Fill),
+                    button(text("Retry").size(self.text_size(15.0)).color(palette.user_text))
+                        .on_press(Message::RetryKey)
+                        .padding([10, 20])
+                        .style(move |_theme: &Theme, _status| button::Style {
+                            background: Some(iced::Background::Color(palette.accent_green)),
+                            text_color: palette.user_text,
+                            border: iced::Border {
+                                radius: 8.0.into(),
+                                width: 0.0,
+                                color: iced::Color::TRANSPARENT,
+                            },
+                            ..Default::default()
+                        }),
+                ],
+            ]
+            .spacing(16),
+        )
+        .padding(24)
+        .max_width(520)
+        .style(move |_theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(palette.bg_secondary)),
+            border: iced::Border {
+                radius: 12.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+
+    // 🎤 starts dictation, ⏹ stops it; disabled while the recording is transcribed
+    #[cfg(feature = "voice")]
+    fn voice_button(&self) -> Element<Message> {
+        let palette = self.palette;
+        let (label, message, background) = if self.transcribing {
+            ("…", None, palette.bg_input)
+        } else if self.recorder.is_some() {
+            ("⏹", Some(Message::StopRecording), palette.danger)
+        } else {
+            ("🎤", Some(Message::StartRecording), palette.bg_input)
+        };
+        button(
+            text(label)
+                .size(self.text_size(15.0))
+                .color(palette.text_primary),
+        )
+        .on_press_maybe(message)
+        .padding([14, 16])
+        .style(move |_theme: &Theme, _status| button::Style {
+            background: Some(iced::Background::Color(background)),
+            text_color: palette.text_primary,
+            border: iced::Border {
+                radius: 8.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+
+    // Send an attached image (with the typed text as its query) to the vision path
+    fn send_attachment(&mut self, attachment: Attachment) -> Task<Message> {
+        let query = match self.input_value.trim() {
+            "" => DEFAULT_IMAGE_QUERY.to_string(),
+            typed => typed.to_string(),
+        };
+
+        self.messages.push(ChatMessage {
+            content: format!("📎 {}\n{}", attachment.file_name, query),
+            is_user: true,
+            image: Some(image::Handle::from_bytes(attachment.bytes.clone())),
+            ..Default::default()
+        });
+        if !self.is_agent_online("camera") {
+            self.push_system_message(
+                "⚠️ Camera Agent appears offline - the image may not be analyzed until it reconnects.",
+            );
+        }
+
+        if let Some(sender) = &self.upload_sender {
+            let _ = sender.send(UploadedImage {
+                query,
+                file_name: attachment.file_name,
+                bytes: attachment.bytes,
+            });
+        }
+
+        self.input_value.clear();
+        self.waiting = true;
+        self.waiting_since = Some(Instant::now());
+        self.follow_new_messages = true;
+        self.save_conversation();
+        Task::batch([self.scroll_to_latest(), self.focus_input()])
+    }
+
+    fn search_bar(&self) -> Element<'_, Message> {
+        let palette = self.palette;
+        let matches = self
+            .messages
+            .iter()
+            .filter(|msg| message_matches(&msg.content, &self.search_query))
+            .count();
+
+        container(
+            row![
+                text_input("Search messages...", &self.search_query)
+                    .id(self.search_input_id.clone())
+                    .on_input(Message::SearchChanged)
+                    .padding(10)
+                    .size(self.text_size(14.0))
+                    .style(move |_theme: &Theme, _status| text_input::Style {
+                        background: iced::Background::Color(palette.bg_input),
+                        border: iced::Border {
+                            radius: 8.0.into(),
+                            width: 1.0,
+                            color: palette.border,
+                        },
+                        icon: palette.text_secondary,
+                        placeholder: palette.text_secondary,
+                        value: palette.text_primary,
+                        selection: palette.selection,
+                    }),
+                text(format!("{} matches", matches))
+                    .size(self.text_size(13.0))
+                    .color(palette.text_secondary),
+                header_button(self.zoom, "Close", Message::ToggleSearch, palette),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+        )
+        .padding([8, 20])
+        .style(move |_theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(palette.bg_secondary)),
+            ..Default::default()
+        })
+        .into()
+    }
+
+    fn copied_toast(&self) -> Element<'_, Message> {
+        let palette = self.palette;
+        container(
+            container(
+                text("✅ Copied")
+                    .size(self.text_size(14.0))
+                    .color(palette.user_text),
+            )
+            .padding([8, 16])
+            .style(move |_theme: &Theme| container::Style {
+                background: Some(iced::Background::Color(palette.accent_green)),
+                border: iced::Border {
+                    radius: 12.0.into(),
+                    width: 0.0,
+                    color: iced::Color::TRANSPARENT,
+                },
+                ..Default::default()
+            }),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(Alignment::Center)
+        .align_y(Alignment::End)
+        .padding(110)
+        .into()
+    }
+
+    // Filled button for the choices in the confirmation dialogs
+    fn dialog_choice<'a>(
+        &self,
+        label: &'a str,
+        message: Message,
+        color: iced::Color,
+    ) -> iced::widget::Button<'a, Message> {
+        let palette = self.palette;
+        button(
+            text(label)
+                .size(self.text_size(15.0))
+                .color(palette.user_text),
+        )
+        .on_press(message)
+        .padding([10, 20])
+        .style(move |_theme: &Theme, _status| button::Style {
+            background: Some(iced::Background::Color(color)),
+            text_color: palette.user_text,
+            border: iced::Border {
+                radius: 8.0.into(),
+                width: 0.0,
+                color: iced::Color::TRANSPARENT,
+            },
+            ..Default::default()
+        })
+    }
+
+    // Model settings of the doctor agent; the theme applies immediately, the rest on Apply
+    fn settings_dialog(&self) -> Element<'_, Message> {
+        let palette = self.palette;
+        let label = |content: &'static str| {
+            text(content)
+                .size(self.text_size(14.0))
+                .color(palette.text_secondary)
+        };
+        let mut content = column![
+            text("⚙ Settings")
+                .size(self.text_size(18.0))
+                .color(palette.text_primary),
+            label("Model"),
+            text_input(&self.llm_settings.model, &self.settings_model)
+                .on_input(Message::SettingsModelChanged)
+                .on_submit(Message::ApplySettings)
+                .padding(10)
+                .size(self.text_size(14.0)),
+            label("Temperature"),
+            row![
+                slider(
+                    0.0..=prefs::MAX_TEMPERATURE,
+                    self.settings_temperature,
+                    Message::SettingsTemperatureChanged
+                )
+                .step(0.05),
+                text(format!("{:.2}", self.settings_temperature))
+                    .size(self.text_size(14.0))
+                    .color(palette.text_primary),
+            ]
+            .spacing(12)
+            .align_y(Alignment::Center),
+            label("Memory window (messages)"),
+            text_input("50", &self.settings_memory_window)
+                .on_input(Message::SettingsMemoryWindowChanged)
+                .on_submit(Message::ApplySettings)
+                .padding(10)
+                .size(self.text_size(14.0)),
+            row![
+                label("Theme"),
+                iced::widget::Space::with_width(Length::Fill),
+                header_button(
+                    self.zoom,
+                    match self.theme_mode {
+                        ThemeMode::Dark => "☀ Light",
+                        ThemeMode::Light => "☾ Dark",
+                    },
+                    Message::ToggleTheme,
+                    palette,
+                ),
+            ]
+            .align_y(Alignment::Center),
+        ]
+        .spacing(12);
+        if let Some(error) = &self.settings_error {
+            content = content.push(text(error).size(self.text_size(14.0)).color(palette.danger));
+        }
+        content = content.push(
+            row![
+                iced::widget::Space::with_width(Length::Fill),
+                self.dialog_choice("Cancel", Message::ToggleSettings, palette.danger),
+                self.dialog_choice("Apply", Message::ApplySettings, palette.accent_green),
+            ]
+            .spacing(12),
+        );
+
+        container(content)
+            .padding(24)
+            .max_width(460)
+            .style(move |_theme: &Theme| container::Style {
+                background: Some(iced::Background::Color(palette.bg_secondary)),
+                border: iced::Border {
+                    radius: 12.0.into(),
+                    width: 1.0,
+                    color: palette.border,
+                },
                 ..Default::default()
             })
             .into()
     }
+
+    fn close_confirm_dialog(&self) -> Element<'_, Message> {
+        let palette = self.palette;
+        container(
+            column![
+                text("🚪 Close while a request is running?")
+                    .size(self.text_size(18.0))
+                    .color(palette.text_primary),
+                text("The doctor agent stops when the window closes, so the answer will be lost.")
+                    .size(self.text_size(15.0))
+                    .color(palette.text_secondary),
+                row![
+                    iced::widget::Space::with_width(Length::Fill),
+                    self.dialog_choice(
+                        "Keep open",
+                        Message::ConfirmClose(false),
+                        palette.accent_green
+                    ),
+                    self.dialog_choice("Close", Message::ConfirmClose(true), palette.danger),
+                ]
+                .spacing(12),
+            ]
+            .spacing(16),
+        )
+        .padding(24)
+        .max_width(460)
+        .style(move |_theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(palette.bg_secondary)),
+            border: iced::Border {
+                radius: 12.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+
+    fn camera_confirm_dialog<'a>(&self, query: &'a str) -> Element<'a, Message> {
+        let palette = self.palette;
+
+        container(
+            column![
+                text("📷 Camera capture requested")
+                    .size(self.text_size(18.0))
+                    .color(palette.text_primary),
+                text(format!("Allow camera capture for: {}?", query))
+                    .size(self.text_size(15.0))
+                    .color(palette.text_secondary),
+                row![
+                    iced::widget::Space::with_width(Length::Fill),
+                    self.dialog_choice("No", Message::ConfirmCamera(false), palette.danger),
+                    self.dialog_choice("Yes", Message::ConfirmCamera(true), palette.accent_green),
+                ]
+                .spacing(12),
+            ]
+            .spacing(16),
+        )
+        .padding(24)
+        .max_width(460)
+        .style(move |_theme: &Theme| container::Style {
+            background: Some(iced::Background::Color(palette.bg_secondary)),
+            border: iced::Border {
+                radius: 12.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        })
+        .into()
+    }
+}
+
+// Let the user pick an image file to attach; None if the dialog was cancelled
+async fn pick_image() -> Option<Attachment> {
+    let file = rfd::AsyncFileDialog::new()
+        .add_filter("Images", &["jpg", "jpeg", "png", "bmp", "webp"])
+        .pick_file()
+        .await?;
+    Some(Attachment {
+        file_name: file.file_name(),
+        bytes: file.read().await,
+    })
+}
+
+// Longest run of non-whitespace characters shown without a break opportunity. At the chat
+// font size this stays well inside the 500px bubble even for wide glyphs.
+const MAX_UNBROKEN_CHARS: usize = 30;
+
+// Text only wraps at word boundaries, so a long URL or base64 blob would overflow its bubble.
+// Insert zero-width spaces into long runs for display; the stored (and copied) text is untouched.
+fn with_soft_breaks(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut run = 0;
+    for c in content.chars() {
+        if c.is_whitespace() {
+            run = 0;
+        } else {
+            if run == MAX_UNBROKEN_CHARS {
+                result.push('\u{200B}');
+                run = 0;
+            }
+            run += 1;
+        }
+        result.push(c);
+    }
+    result
+}
+
+// Case-insensitive substring match used by the conversation search
+fn message_matches(content: &str, query: &str) -> bool {
+    content
+        .to_lowercase()
+        .contains(&query.trim().to_lowercase())
+}
+
+// Lock a channel slot, recovering the guard if a panicking thread poisoned it. The slots
+// only hold channel handles, so the data is still usable and the UI should keep running.
+fn lock_or_recover<'a, T>(mutex: &'a Mutex<T>, name: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        println!("⚠️ Lock on {} was poisoned, recovering", name);
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+// Overlay content on top of a dimmed base view, blocking interaction with the base
+fn modal<'a>(
+    base: impl Into<Element<'a, Message>>,
+    content: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message> {
+    stack![
+        base.into(),
+        opaque(mouse_area(center(opaque(content)).style(
+            |_theme: &Theme| {
+                container::Style {
+                    background: Some(iced::Background::Color(iced::Color {
+                        a: 0.6,
+                        ..iced::Color::BLACK
+                    })),
+                    ..Default::default()
+                }
+            }
+        )))
+    ]
+    .into()
+}
+
+// Borderless button shown beside a message bubble, highlighted on hover
+fn icon_button(zoom: f32, label: &str, message: Message, palette: Palette) -> Element<'_, Message> {
+    button(text(label).size(12.0 * zoom).color(palette.text_secondary))
+        .on_press(message)
+        .padding([4, 6])
+        .style(move |_theme: &Theme, status| button::Style {
+            background: match status {
+                button::Status::Hovered => Some(iced::Background::Color(palette.bg_input)),
+                _ => None,
+            },
+            text_color: palette.text_secondary,
+            border: iced::Border {
+                radius: 6.0.into(),
+                width: 0.0,
+                color: iced::Color::TRANSPARENT,
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+// Small bordered button used for the header controls
+fn header_button(
+    zoom: f32,
+    label: &str,
+    message: Message,
+    palette: Palette,
+) -> Element<'_, Message> {
+    button(text(label).size(13.0 * zoom).color(palette.text_secondary))
+        .on_press(message)
+        .padding([6, 10])
+        .style(move |_theme: &Theme, _status| button::Style {
+            background: Some(iced::Background::Color(palette.bg_input)),
+            text_color: palette.text_secondary,
+            border: iced::Border {
+                radius: 8.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+// Header-style button for a quick action; without a message it is shown dimmed
+fn quick_action_button(
+    zoom: f32,
+    label: &str,
+    message: Option<Message>,
+    palette: Palette,
+) -> Element<'_, Message> {
+    button(text(label).size(13.0 * zoom))
+        .on_press_maybe(message)
+        .padding([6, 10])
+        .style(move |_theme: &Theme, status| button::Style {
+            background: Some(iced::Background::Color(match status {
+                button::Status::Hovered => palette.border,
+                _ => palette.bg_input,
+            })),
+            text_color: match status {
+                button::Status::Disabled => palette.border,
+                _ => palette.text_primary,
+            },
+            border: iced::Border {
+                radius: 8.0.into(),
+                width: 1.0,
+                color: palette.border,
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+// Longest quoted question shown above a reply
+const REPLY_PREVIEW_CHARS: usize = 80;
+
+fn reply_preview(question: &str) -> String {
+    let question = question.trim();
+    if question.chars().count() > REPLY_PREVIEW_CHARS {
+        let clipped: String = question.chars().take(REPLY_PREVIEW_CHARS).collect();
+        format!("{}…", clipped.trim_end())
+    } else {
+        question.to_string()
+    }
+}
+
+// First line of a response, shortened for the notification body
+fn notification_preview(response: &str) -> String {
+    let first_line = response
+        .lines()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if first_line.chars().count() > NOTIFICATION_PREVIEW_CHARS {
+        let clipped: String = first_line
+            .chars()
+            .take(NOTIFICATION_PREVIEW_CHARS)
+            .collect();
+        format!("{}…", clipped)
+    } else {
+        first_line.to_string()
+    }
+}
+
+fn spinner_frame(elapsed: Duration) -> &'static str {
+    SPINNER_FRAMES[(elapsed.as_millis() / SPINNER_FRAME_MS) as usize % SPINNER_FRAMES.len()]
+}
+
+// Only the text of a message is kept; attached images aren't saved with the conversation
+fn to_stored(message: &ChatMessage) -> StoredMessage {
+    StoredMessage {
+        content: message.content.clone(),
+        is_user: message.is_user,
+        reasoning: message.reasoning.clone(),
+        agent_source: message.agent_source.clone(),
+        is_pinned: message.is_pinned,
+        is_error: message.is_error,
+        in_reply_to: message.in_reply_to.clone(),
+    }
+}
+
+fn from_stored(message: StoredMessage) -> ChatMessage {
+    ChatMessage {
+        content: message.content,
+        is_user: message.is_user,
+        reasoning: message.reasoning,
+        agent_source: message.agent_source,
+        is_pinned: message.is_pinned,
+        is_error: message.is_error,
+        in_reply_to: message.in_reply_to,
+        received: message.is_user, // Anything saved was already sent
+        ..Default::default()
+    }
+}
+
+// A saved conversation, or just the welcome message for a new one
+fn load_messages_from(stored: Vec<StoredMessage>) -> Vec<ChatMessage> {
+    if stored.is_empty() {
+        return vec![ChatMessage {
+            content: WELCOME_MESSAGE.to_string(),
+            is_user: false,
+            ..Default::default()
+        }];
+    }
+    stored.into_iter().map(from_stored).collect()
+}
+
+fn load_messages(name: &str) -> Vec<ChatMessage> {
+    load_messages_from(conversations::load(name))
+}
+
+// Elapsed session time as H:MM:SS
+fn format_session_duration(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// Shown from a separate thread: on Linux the D-Bus round trip would otherwise stall the UI
+fn show_notification(preview: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = notify_rust::Notification::new()
+            .summary("🩺 Doctor response ready")
+            .body(&preview)
+            .show()
+        {
+            println!("⚠️ Could not show desktop notification: {}", e);
+        }
+    });
 }
 
 pub fn run_chat_app(
-    user_tx: mpsc::UnboundedSender<String>,
-    response_rx: mpsc::UnboundedReceiver<String>,
+    launch: Result<AgentConnector, KeySetup>,
+    theme_mode: Option<ThemeMode>,
+    notifications: bool,
+    poll_interval: Duration,
+    llm_defaults: LlmSettings,
+    memory_window: Option<usize>,
+    window: WindowOptions,
 ) -> iced::Result {
-    iced::application(ChatApp::title, ChatApp::update, ChatApp::view).run_with(|| {
-        let app = ChatApp::new(user_tx, response_rx);
-        // Start the polling immediately
-        let initial_task = Task::done(Message::Tick);
-        (app, initial_task)
-    })
+    iced::application(ChatApp::title, ChatApp::update, ChatApp::view)
+        .subscription(ChatApp::subscription)
+        .window(iced::window::Settings {
+            size: iced::Size::new(window.width, window.height),
+            ..Default::default()
+        })
+        // Closing goes through Message::CloseRequested so the doctor node stops first
+        .exit_on_close_request(false)
+        .run_with(|| {
+            let mut app = ChatApp::new(
+                theme_mode,
+                notifications,
+                poll_interval,
+                llm_defaults,
+                memory_window,
+                window.title,
+            );
+            match launch {
+                Ok(connector) => app.connect(connector),
+                Err(setup) => app.key_setup = Some(setup),
+            }
+            // The cursor starts in the message input; polling runs from the subscription
+            let initial_task = app.focus_input();
+            (app, initial_task)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acks_mark_the_oldest_matching_user_message() {
+        let user = |content: &str| ChatMessage {
+            content: content.to_string(),
+            is_user: true,
+            ..Default::default()
+        };
+        let mut messages = vec![
+            ChatMessage {
+                content: "Check rhythm".to_string(),
+                ..Default::default()
+            },
+            user("Check rhythm"),
+            user("Check rate"),
+            user("Check rhythm"),
+        ];
+        mark_received(&mut messages, "Check rhythm");
+        let received: Vec<bool> = messages.iter().map(|m| m.received).collect();
+        assert_eq!(received, vec![false, true, false, false]);
+
+        mark_received(&mut messages, "Check rhythm");
+        mark_received(&mut messages, "Unknown query");
+        let received: Vec<bool> = messages.iter().map(|m| m.received).collect();
+        assert_eq!(received, vec![false, true, false, true]);
+    }
+
+    #[test]
+    fn closing_asks_first_only_while_a_request_runs() {
+        let idle = QueueStatus::default();
+        assert!(!request_in_flight(false, &idle));
+        assert!(request_in_flight(true, &idle));
+
+        let busy = QueueStatus {
+            active: Some("Check rhythm".to_string()),
+            queued: 0,
+        };
+        assert!(request_in_flight(false, &busy));
+        let queued = QueueStatus {
+            active: None,
+            queued: 2,
+        };
+        assert!(request_in_flight(false, &queued));
+    }
+
+    #[test]
+    fn saved_messages_round_trip_without_their_images() {
+        let message = ChatMessage {
+            content: "Sinus rhythm".to_string(),
+            agent_source: Some("analysis".to_string()),
+            is_pinned: true,
+            reasoning: vec!["🔧 Action: ecg_analysis_tool".to_string()],
+            in_reply_to: Some("Is the patient awake?".to_string()),
+            ..Default::default()
+        };
+        let restored = from_stored(to_stored(&message));
+        assert_eq!(restored.in_reply_to, message.in_reply_to);
+        assert_eq!(restored.content, message.content);
+        assert_eq!(restored.agent_source, message.agent_source);
+        assert!(restored.is_pinned);
+        assert_eq!(restored.reasoning, message.reasoning);
+
+        let fresh = load_messages_from(Vec::new());
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[0].content, WELCOME_MESSAGE);
+    }
+
+    #[test]
+    fn spinner_cycles_through_its_frames() {
+        assert_eq!(spinner_frame(Duration::ZERO), SPINNER_FRAMES[0]);
+        assert_eq!(spinner_frame(Duration::from_millis(160)), SPINNER_FRAMES[1]);
+        assert_eq!(
+            spinner_frame(Duration::from_millis(4 * 150)),
+            SPINNER_FRAMES[0]
+        );
+    }
+
+    #[test]
+    fn session_duration_is_shown_as_hours_minutes_seconds() {
+        assert_eq!(format_session_duration(Duration::from_secs(0)), "0:00:00");
+        assert_eq!(
+            format_session_duration(Duration::from_millis(61_900)),
+            "0:01:01"
+        );
+        assert_eq!(
+            format_session_duration(Duration::from_secs(3 * 3600 + 5)),
+            "3:00:05"
+        );
+    }
+
+    // Longest stretch the renderer can't break: no whitespace and no zero-width space
+    fn longest_unbroken_run(text: &str) -> usize {
+        text.split(|c: char| c.is_whitespace() || c == '\u{200B}')
+            .map(|run| run.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn single_2000_char_word_wraps_within_bubble() {
+        let blob = "A".repeat(2000);
+        let displayed = with_soft_breaks(&blob);
+
+        assert!(longest_unbroken_run(&displayed) <= MAX_UNBROKEN_CHARS);
+        // Only break opportunities are added; the visible characters are unchanged
+        assert_eq!(displayed.replace('\u{200B}', ""), blob);
+    }
+
+    #[test]
+    fn unknown_agents_fall_back_to_the_ai_tag() {
+        let palette = Palette::dark();
+        assert_eq!(agent_tag(Some("camera"), palette).0, "CAM");
+        assert_eq!(agent_tag(Some("analysis"), palette).0, "ECG");
+        assert_eq!(agent_tag(Some("doctor"), palette).0, "DR");
+        assert_eq!(agent_tag(Some("billing"), palette).0, "AI");
+        assert_eq!(agent_tag(None, palette).0, "AI");
+    }
+
+    #[test]
+    fn ordinary_sentences_are_left_alone() {
+        let sentence = "Normal sinus rhythm at 72 bpm, no ST elevation.";
+        assert_eq!(with_soft_breaks(sentence), sentence);
+    }
 }
@@ -0,0 +1,165 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use std::io::Cursor;
+
+// Shrink an image so its longest side is at most `max_dim`, re-encoding it as JPEG.
+// Images that are already small enough (or can't be decoded) are returned untouched.
+pub fn downscale_jpeg(buffer: Vec<u8>, max_dim: u32) -> Vec<u8> {
+    let image = match image::load_from_memory(&buffer) {
+        Ok(image) => image,
+        Err(e) => {
+            println!(
+                "⚠️ Could not decode image for downscaling, sending original: {}",
+                e
+            );
+            return buffer;
+        }
+    };
+
+    let (width, height) = image.dimensions();
+    if width <= max_dim && height <= max_dim {
+        println!(
+            "🖼️ Image is {}x{}, within {}px - no resize needed",
+            width, height, max_dim
+        );
+        return buffer;
+    }
+
+    // resize keeps the aspect ratio and fits the image within max_dim x max_dim
+    let resized = DynamicImage::ImageRgb8(
+        image
+            .resize(max_dim, max_dim, FilterType::Lanczos3)
+            .to_rgb8(),
+    );
+
+    let mut encoded = Vec::new();
+    if let Err(e) = resized.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Jpeg) {
+        println!(
+            "⚠️ Failed to re-encode resized image, sending original: {}",
+            e
+        );
+        return buffer;
+    }
+
+    println!(
+        "🖼️ Downscaled image {}x{} ({} KB) -> {}x{} ({} KB)",
+        width,
+        height,
+        buffer.len() / 1024,
+        resized.width(),
+        resized.height(),
+        encoded.len() / 1024
+    );
+    encoded
+}
+
+// Decode an image in any supported format and re-encode it as JPEG, shrinking it so its
+// longest side is at most `max_dim`
+pub fn to_jpeg(buffer: &[u8], max_dim: u32) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(buffer).map_err(|e| e.to_string())?;
+    encode_jpeg(&image, max_dim)
+}
+
+fn encode_jpeg(image: &DynamicImage, max_dim: u32) -> Result<Vec<u8>, String> {
+    let (width, height) = image.dimensions();
+    let image = if width > max_dim || height > max_dim {
+        image.resize(max_dim, max_dim, FilterType::Lanczos3)
+    } else {
+        image.clone()
+    };
+
+    let mut encoded = Vec::new();
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Jpeg)
+        .map_err(|e| e.to_string())?;
+    Ok(encoded)
+}
+
+// Smallest longest side an image is shrunk to while fitting it under the byte limit; below
+// this a monitor or ECG printout is no longer readable
+const MIN_FIT_DIM: u32 = 256;
+
+// Make sure an image is at most `max_bytes` before it goes to the LLM, downscaling it further
+// in steps when it isn't. Err explains the sizes when even MIN_FIT_DIM is too large.
+pub fn fit_within_bytes(buffer: Vec<u8>, max_bytes: usize) -> Result<Vec<u8>, String> {
+    if buffer.len() <= max_bytes {
+        return Ok(buffer);
+    }
+    let original = buffer.len();
+    let image = image::load_from_memory(&buffer).map_err(|e| {
+        format!(
+            "image is {} KB, over the {} KB limit, and could not be decoded to shrink it: {}",
+            original / 1024,
+            max_bytes / 1024,
+            e
+        )
+    })?;
+
+    let mut dim = image.width().max(image.height());
+    let mut smallest = original;
+    while dim > MIN_FIT_DIM {
+        dim = (dim * 3 / 4).max(MIN_FIT_DIM);
+        let encoded = encode_jpeg(&image, dim)?;
+        if encoded.len() <= max_bytes {
+            println!(
+                "🖼️ Shrank image from {} KB to {} KB ({}px) to fit the {} KB limit",
+                original / 1024,
+                encoded.len() / 1024,
+                dim,
+                max_bytes / 1024
+            );
+            return Ok(encoded);
+        }
+        smallest = encoded.len();
+    }
+    Err(format!(
+        "image is {} KB and still {} KB at {}px, over the {} KB limit",
+        original / 1024,
+        smallest / 1024,
+        MIN_FIT_DIM,
+        max_bytes / 1024
+    ))
+}
+
+// Decode the header of an image file to make sure a capture was written completely enough
+// to be usable. Returns the image dimensions.
+pub fn validate_image_file(path: &str) -> Result<(u32, u32), String> {
+    ImageReader::open(path)
+        .map_err(|e| format!("could not open {}: {}", path, e))?
+        .with_guessed_format()
+        .map_err(|e| format!("could not read {}: {}", path, e))?
+        .into_dimensions()
+        .map_err(|e| format!("could not decode {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    // Random-looking pixels so the JPEG doesn't compress to almost nothing
+    fn noisy_jpeg(size: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(size, size, |x, y| {
+            let v = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)) as u8;
+            Rgb([v, v.wrapping_mul(3), v.wrapping_mul(5)])
+        });
+        encode_jpeg(&DynamicImage::ImageRgb8(image), size).unwrap()
+    }
+
+    #[test]
+    fn oversized_images_are_shrunk_under_the_byte_limit_or_rejected() {
+        let original = noisy_jpeg(1024);
+        assert_eq!(
+            fit_within_bytes(original.clone(), original.len()),
+            Ok(original.clone())
+        );
+
+        let limit = original.len() / 2;
+        let shrunk = fit_within_bytes(original.clone(), limit).unwrap();
+        assert!(shrunk.len() <= limit);
+
+        let error = fit_within_bytes(original, 100).unwrap_err();
+        assert!(error.contains("at 256px"));
+        assert!(fit_within_bytes(vec![0; 2048], 1024).is_err());
+    }
+}
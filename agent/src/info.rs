@@ -0,0 +1,99 @@
+// Build and environment details for bug reports. Dependency versions come from the Cargo.lock
+// compiled into the binary, so they match what was actually built; build.rs supplies an empty
+// one when there is no lockfile.
+use crate::selftest::{CAMERA_TOOLS, find_command};
+
+const CARGO_LOCK: &str = include_str!(concat!(env!("OUT_DIR"), "/Cargo.lock"));
+
+// Dependencies worth naming in a bug report
+const REPORTED_DEPENDENCIES: [&str; 3] = ["autoagents", "iced", "tokio"];
+
+// "version" or "version (git <short rev>)" of a package in a Cargo.lock
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let package = lock.split("[[package]]").find(|package| {
+        package
+            .lines()
+            .any(|line| line.trim() == format!("name = \"{}\"", name))
+    })?;
+    let field = |key: &str| {
+        package.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix(key)
+                .and_then(|rest| rest.trim().strip_prefix('='))
+                .map(|value| value.trim().trim_matches('"').to_string())
+        })
+    };
+    let version = field("version")?;
+    match field("source").and_then(|source| {
+        source
+            .strip_prefix("git+")
+            .and_then(|git| git.rsplit_once('#'))
+            .map(|(_, rev)| rev.chars().take(8).collect::<String>())
+    }) {
+        Some(rev) => Some(format!("{} (git {})", version, rev)),
+        None => Some(version),
+    }
+}
+
+pub fn print(provider: &str, model: &str, key_set: bool) {
+    println!(
+        "🩺 {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    for name in REPORTED_DEPENDENCIES {
+        println!(
+            "   {:<12} {}",
+            name,
+            locked_version(CARGO_LOCK, name).unwrap_or_else(|| "unknown".to_string())
+        );
+    }
+    println!(
+        "   {:<12} {} / {} (API key {})",
+        "llm",
+        provider,
+        model,
+        if key_set { "set" } else { "not set" }
+    );
+    println!(
+        "   {:<12} {}-{}",
+        "target",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let backends: Vec<String> = CAMERA_TOOLS
+        .iter()
+        .map(|tool| match find_command(tool) {
+            Some(path) => format!("{} ({})", tool, path.display()),
+            None => format!("{} (missing)", tool),
+        })
+        .collect();
+    println!("   {:<12} {}", "camera", backends.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_read_from_the_lockfile() {
+        let lock = r#"
+[[package]]
+name = "autoagents"
+version = "0.2.2-alpha.0"
+source = "git+https://github.com/liquidos-ai/AutoAgents?branch=main#32ad2870132cb1ce"
+
+[[package]]
+name = "iced"
+version = "0.13.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+        assert_eq!(
+            locked_version(lock, "autoagents").as_deref(),
+            Some("0.2.2-alpha.0 (git 32ad2870)")
+        );
+        assert_eq!(locked_version(lock, "iced").as_deref(), Some("0.13.1"));
+        assert_eq!(locked_version(lock, "missing"), None);
+        assert!(locked_version(CARGO_LOCK, "autoagents").is_some());
+    }
+}
@@ -1,18 +1,109 @@
 mod agents;
+mod batch;
+mod conversations;
+mod correlation;
+mod delivery;
+mod doctor_llm;
+mod event_log;
+mod followups;
 mod gui;
+mod imaging;
+mod info;
+mod metrics;
+mod patient;
+mod prefs;
+mod registry;
+mod rhythm;
+mod runner;
+mod selftest;
+mod telemetry;
+#[cfg(test)]
+mod test_harness;
+mod tool_docs;
+mod topics;
+mod transport;
+mod usage;
+#[cfg(feature = "voice")]
+mod voice;
+#[cfg(feature = "waveform")]
+mod waveform;
+#[cfg(feature = "webhook")]
+mod webhook;
 
+use autoagents::llm::LLMProvider;
+use autoagents::llm::backends::anthropic::Anthropic;
 use autoagents::llm::{backends::openai::OpenAI, builder::LLMBuilder};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::mpsc;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// LLM provider used by the agents
+    #[arg(long, value_enum, default_value = "openai", global = true)]
+    provider: Provider,
+    /// Log output format
+    #[arg(long, value_enum, default_value = "pretty", global = true)]
+    log_format: telemetry::LogFormat,
+    /// Increase log verbosity (-v = debug, -vv = trace); RUST_LOG takes precedence when set
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Estimated cost in USD per 1,000 LLM tokens, used for the usage summary
+    #[arg(long, default_value_t = usage::DEFAULT_USD_PER_1K_TOKENS, global = true)]
+    usd_per_1k_tokens: f64,
+    /// TLS certificate (PEM) for the cluster host
+    #[arg(long, global = true, value_parser = parse_path)]
+    tls_cert: Option<std::path::PathBuf>,
+    /// TLS private key (PEM) for the cluster host
+    #[arg(long, global = true, value_parser = parse_path)]
+    tls_key: Option<std::path::PathBuf>,
+    /// CA bundle (PEM) clients use to verify the cluster host
+    #[arg(long, global = true, value_parser = parse_path)]
+    tls_ca: Option<std::path::PathBuf>,
+    /// Append every event this node handles, with its routing decision, to this JSONL file
+    #[arg(long, global = true, value_parser = parse_path)]
+    event_log: Option<std::path::PathBuf>,
+    /// Directory the analysis agent saves reports to and the doctor reads past reports from; shared storage when they run on different machines
+    #[arg(long, global = true, default_value = agents::DEFAULT_REPORTS_DIR, value_parser = parse_path)]
+    reports_dir: std::path::PathBuf,
+    /// JSON file mapping tool names to descriptions that replace the built-in ones shown to the LLM
+    #[arg(long, global = true, value_parser = parse_path)]
+    tool_descriptions: Option<std::path::PathBuf>,
+    /// OpenAI-compatible API base URL for Azure OpenAI or a gateway (falls back to OPENAI_BASE_URL)
+    #[arg(long, global = true, value_parser = parse_base_url)]
+    openai_base_url: Option<String>,
+    /// OpenAI organization ID (falls back to OPENAI_ORG)
+    #[arg(long, global = true)]
+    openai_org: Option<String>,
+    /// Seconds before a single LLM request is abandoned as "The AI service timed out"
+    #[arg(long, global = true, default_value_t = agents::DEFAULT_LLM_TIMEOUT_SECS, value_parser = clap::value_parser!(u64).range(1..))]
+    llm_timeout_secs: u64,
+    /// Shared cookie every node of this cluster must use; nodes with another cookie can't join
+    #[arg(long, global = true, default_value = runner::DEFAULT_CLUSTER_COOKIE)]
+    cluster_cookie: String,
+    /// Prefix for every cluster topic (e.g. ns1 -> ns1/analysis_agent) so clusters sharing a network stay apart
+    #[arg(long, global = true, value_parser = parse_cluster_namespace)]
+    cluster_namespace: Option<String>,
+    /// Allow unencrypted cluster traffic to or from non-localhost addresses
+    #[arg(long, global = true)]
+    insecure: bool,
+    /// Forbid any camera use: the doctor's camera tool refuses and the camera agent won't start
+    #[arg(long, global = true)]
+    disable_camera: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Provider {
+    /// OpenAI (reads OPENAI_API_KEY)
+    Openai,
+    /// Anthropic Claude (reads ANTHROPIC_API_KEY)
+    Anthropic,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Run ClusterHostRuntime for coordinating all client connections
@@ -23,9 +114,15 @@ enum Commands {
         /// Node name
         #[arg(short = 'n', long, default_value = "cluster_host")]
         name: String,
-        /// Host address
-        #[arg(long, default_value = "localhost")]
-        host: String,
+        /// Address to listen on, e.g. 0.0.0.0 inside a container
+        #[arg(long, alias = "host", default_value = "localhost", value_parser = parse_host)]
+        bind: String,
+        /// Serve per-topic task counts at GET /metrics and the agent registry at GET /cluster/state on this port
+        #[arg(long)]
+        metrics_port: Option<u16>,
+        /// Keep the agent registry in this file so a restarted host warns about agents that don't come back
+        #[arg(long, value_parser = parse_path)]
+        state_file: Option<std::path::PathBuf>,
     },
     /// Run DoctorAgent as cluster client with GUI
     Doctor {
@@ -33,14 +130,66 @@ enum Commands {
         #[arg(short = 'p', long, default_value = "9001")]
         port: u16,
         /// Cluster host address to connect to (e.g., localhost:9000)
-        #[arg(long, default_value = "localhost:9000")]
+        #[arg(long, default_value = "localhost:9000", value_parser = parse_host_addr)]
         host_addr: String,
         /// Node name
         #[arg(short = 'n', long, default_value = "doctor")]
         name: String,
-        /// Local host address
-        #[arg(long, default_value = "localhost")]
-        host: String,
+        /// Address to listen on, e.g. 0.0.0.0 inside a container
+        #[arg(long, alias = "host", default_value = "localhost", value_parser = parse_host)]
+        bind: String,
+        /// GUI color theme [default: dark, or the one saved from the GUI]
+        #[arg(long, value_enum)]
+        theme: Option<gui::ThemeMode>,
+        /// Number of messages kept in the agent's sliding-window memory (larger windows increase token usage) [default: 50, or the one saved from the GUI]
+        #[arg(long, value_parser = parse_memory_window)]
+        memory_window: Option<usize>,
+        /// Ask for approval in the GUI before every camera capture
+        #[arg(long)]
+        confirm_camera: bool,
+        /// Maximum user messages forwarded to the agent per minute
+        #[arg(long, default_value = "20", value_parser = clap::value_parser!(u32).range(1..))]
+        rate_limit_per_min: u32,
+        /// Queries longer than this many characters are condensed by the LLM before the agent sees them
+        #[arg(long, default_value = "4000", value_parser = clap::value_parser!(usize).range(100..))]
+        max_query_chars: usize,
+        /// Abort a query when the agent calls any single tool more than this many times for it
+        #[arg(long, default_value = "4", value_parser = clap::value_parser!(usize).range(1..))]
+        max_tool_calls: usize,
+        /// Reasoning iterations the agent may take per query, bounding its cost and latency
+        #[arg(long, default_value = "10", value_parser = clap::value_parser!(usize).range(1..))]
+        max_turns: usize,
+        /// Seconds a query may wait for the agent's final answer before the next queued one starts
+        #[arg(long, default_value = "120", value_parser = clap::value_parser!(u64).range(1..))]
+        query_timeout_secs: u64,
+        /// Messages buffered between the GUI and the agent in each direction; Send is disabled while full
+        #[arg(long, default_value = "256", value_parser = clap::value_parser!(usize).range(1..))]
+        channel_capacity: usize,
+        /// Run without the GUI: read queries from stdin, one per line, and print responses to stdout
+        #[arg(long)]
+        no_gui: bool,
+        /// Send this query once without the GUI, print the doctor's answer and exit (non-zero without one)
+        #[arg(long)]
+        replay_query: Option<String>,
+        /// Don't show a desktop notification when a response arrives while the window is unfocused
+        #[arg(long)]
+        no_notifications: bool,
+        /// How often the GUI checks for agent responses, in milliseconds
+        #[arg(long, default_value = "100", value_parser = clap::value_parser!(u64).range(10..))]
+        poll_interval_ms: u64,
+        /// GUI window width in pixels
+        #[arg(long, default_value = "1024", value_parser = parse_window_size)]
+        window_width: f32,
+        /// GUI window height in pixels
+        #[arg(long, default_value = "768", value_parser = parse_window_size)]
+        window_height: f32,
+        /// GUI window title
+        #[arg(long, default_value = gui::DEFAULT_WINDOW_TITLE, value_parser = parse_window_title)]
+        window_title: String,
+        /// Transcribe dictation with this local command (given the WAV path) instead of OpenAI Whisper
+        #[cfg(feature = "voice")]
+        #[arg(long)]
+        transcribe_command: Option<String>,
     },
     /// Run AnalysisAgent as cluster client
     Analysis {
@@ -48,14 +197,34 @@ enum Commands {
         #[arg(short = 'p', long, default_value = "9002")]
         port: u16,
         /// Cluster host address to connect to (e.g., localhost:9000)
-        #[arg(long, default_value = "localhost:9000")]
+        #[arg(long, default_value = "localhost:9000", value_parser = parse_host_addr)]
         host_addr: String,
         /// Node name
         #[arg(short = 'n', long, default_value = "analysis")]
         name: String,
-        /// Local host address
-        #[arg(long, default_value = "localhost")]
-        host: String,
+        /// Address to listen on, e.g. 0.0.0.0 inside a container
+        #[arg(long, alias = "host", default_value = "localhost", value_parser = parse_host)]
+        bind: String,
+        /// Number of messages kept in the agent's sliding-window memory (larger windows increase token usage)
+        #[arg(long, default_value = "10", value_parser = parse_memory_window)]
+        memory_window: usize,
+        /// Also publish each report as JSON on analysis_response_json and save it next to the markdown report
+        #[arg(long)]
+        emit_raw_json: bool,
+        /// Maximum number of analyses run in parallel; each runs with only its own query and patient context
+        #[arg(long, default_value = "1", value_parser = parse_analysis_concurrency)]
+        analysis_concurrency: usize,
+        /// Abort an analysis that takes longer than this many seconds and report the timeout to the doctor
+        #[arg(long, default_value = "300", value_parser = parse_task_timeout_secs)]
+        task_timeout_secs: u64,
+        /// Plot the ECG samples named in each query and send the PNG to the doctor GUI with the report
+        #[cfg(feature = "waveform")]
+        #[arg(long)]
+        render_waveform: bool,
+        /// POST each finished analysis as JSON to this https URL (plain http only to localhost; best-effort, retried with backoff)
+        #[cfg(feature = "webhook")]
+        #[arg(long, value_parser = webhook::parse_url)]
+        webhook_url: Option<String>,
     },
     /// Run CameraAgent as cluster client
     Camera {
@@ -63,116 +232,1425 @@ enum Commands {
         #[arg(short = 'p', long, default_value = "9003")]
         port: u16,
         /// Cluster host address to connect to (e.g., localhost:9000)
-        #[arg(long, default_value = "localhost:9000")]
+        #[arg(long, default_value = "localhost:9000", value_parser = parse_host_addr)]
         host_addr: String,
         /// Node name
         #[arg(short = 'n', long, default_value = "camera")]
         name: String,
-        /// Local host address
-        #[arg(long, default_value = "localhost")]
+        /// Address to listen on, e.g. 0.0.0.0 inside a container
+        #[arg(long, alias = "host", default_value = "localhost", value_parser = parse_host)]
+        bind: String,
+        /// Number of messages kept in the agent's sliding-window memory (larger windows increase token usage)
+        #[arg(long, default_value = "10", value_parser = parse_memory_window)]
+        memory_window: usize,
+        /// Downscale captures so their longest side is at most this many pixels before sending to the LLM
+        #[arg(long, default_value = "1024", value_parser = clap::value_parser!(u32).range(1..))]
+        max_image_dim: u32,
+        /// Largest image, in bytes, sent to the LLM; bigger ones are shrunk further or rejected with a clear error
+        #[arg(long, default_value = "3500000", value_parser = clap::value_parser!(usize).range(10_000..))]
+        max_image_bytes: usize,
+        /// When capture fails, still answer the query from text alone with a caveat instead of giving up
+        #[arg(long)]
+        camera_text_fallback: bool,
+        /// Give up on a capture when the camera doesn't respond within this many seconds
+        #[arg(long, default_value = "15", value_parser = clap::value_parser!(u64).range(1..))]
+        capture_timeout_secs: u64,
+        /// Resolution requested from the camera as WxH; higher values help read small monitor text
+        #[arg(long, default_value = "640x480", value_parser = parse_capture_resolution)]
+        capture_resolution: String,
+        /// Send camera results to the analysis agent, to be combined with the ECG analysis, instead of straight to the doctor
+        #[arg(long)]
+        camera_to_analysis: bool,
+    },
+    /// Run the cluster host and all agents in one process on the default ports, with the doctor GUI
+    All {
+        /// Address to listen on, e.g. 0.0.0.0 inside a container
+        #[arg(long, alias = "host", default_value = "localhost", value_parser = parse_host)]
+        bind: String,
+        /// GUI color theme [default: dark, or the one saved from the GUI]
+        #[arg(long, value_enum)]
+        theme: Option<gui::ThemeMode>,
+        /// GUI window width in pixels
+        #[arg(long, default_value = "1024", value_parser = parse_window_size)]
+        window_width: f32,
+        /// GUI window height in pixels
+        #[arg(long, default_value = "768", value_parser = parse_window_size)]
+        window_height: f32,
+        /// GUI window title
+        #[arg(long, default_value = gui::DEFAULT_WINDOW_TITLE, value_parser = parse_window_title)]
+        window_title: String,
+    },
+    /// Run a single ECG analysis locally without joining the cluster, then exit
+    AnalyzeOnce {
+        /// Analysis query to run
+        query: String,
+        /// Also write the report to this file
+        #[arg(short = 'o', long, value_parser = parse_path)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Analyze every ECG file in a directory, writing one report per file and a summary CSV
+    BatchAnalyze {
+        /// Directory with the ECG sample files (.csv, .txt or .dat)
+        #[arg(value_parser = parse_path)]
+        dir: std::path::PathBuf,
+        /// Directory the reports and summary.csv are written to
+        #[arg(short = 'o', long, default_value = "batch_reports", value_parser = parse_path)]
+        output_dir: std::path::PathBuf,
+        /// Number of files analyzed in parallel
+        #[arg(long, default_value = "2", value_parser = parse_analysis_concurrency)]
+        concurrency: usize,
+        /// Sampling rate of the recordings in Hz
+        #[arg(long, default_value_t = rhythm::DEFAULT_SAMPLE_RATE_HZ)]
+        sample_rate_hz: f64,
+        /// Only compute the local rhythm estimate; no LLM calls, so no API key is needed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print version, dependency, provider and platform details for bug reports
+    Info,
+    /// Check the API key, camera tools and cluster ports before a demo, then exit
+    SelfTest {
+        /// Local host address the cluster ports are checked on
+        #[arg(long, default_value = "localhost", value_parser = parse_host)]
         host: String,
+        /// Cluster ports that must be free (host, doctor, analysis, camera by default)
+        #[arg(long, value_delimiter = ',', default_value = "9000,9001,9002,9003")]
+        ports: Vec<u16>,
+    },
+    /// Re-run the routing decisions recorded with --event-log, without a live cluster
+    Replay {
+        /// JSONL event log to replay
+        #[arg(value_parser = parse_path)]
+        path: std::path::PathBuf,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-
     let args = Args::parse();
+    telemetry::init(args.log_format, args.command.node_name(), args.verbose);
+    usage::set_rate(args.usd_per_1k_tokens);
+    if let Some(path) = &args.event_log {
+        event_log::init(path, args.command.node_name())?;
+    }
+    if let Some(path) = &args.tool_descriptions {
+        if let Err(e) = tool_docs::load(path, agents::DOCTOR_TOOLS) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+    runner::set_cluster_cookie(args.cluster_cookie);
+    if let Some(namespace) = &args.cluster_namespace {
+        println!("🏷️ Using cluster namespace '{}'", namespace);
+        topics::set_namespace(namespace);
+    }
+    agents::set_llm_timeout(std::time::Duration::from_secs(args.llm_timeout_secs));
+    agents::set_reports_dir(args.reports_dir);
+    if let Err(e) = configure_openai_endpoint(args.openai_base_url, args.openai_org) {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+
+    if args.disable_camera {
+        if matches!(args.command, Commands::Camera { .. }) {
+            eprintln!(
+                "❌ The camera is disabled by --disable-camera; not starting the camera agent"
+            );
+            std::process::exit(1);
+        }
+        println!("🚫 Camera disabled by policy");
+        agents::disable_camera();
+    }
+
+    // The LLM is created per command: the host doesn't need a key, and the doctor GUI asks for
+    // a missing one instead of exiting
+    let provider = args.provider;
 
-    // Create LLM provider
-    let llm = create_llm_provider()?;
+    // Cross-host clusters carry patient data and need TLS (or an explicit --insecure)
+    let security = transport::TransportSecurity {
+        tls_cert: args.tls_cert,
+        tls_key: args.tls_key,
+        tls_ca: args.tls_ca,
+        insecure: args.insecure,
+    };
+    if let Err(e) = security.check(&args.command.cluster_addresses()) {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
 
     match args.command {
-        Commands::Host { port, name, host } => {
+        Commands::Host {
+            port,
+            name,
+            bind,
+            metrics_port,
+            state_file,
+        } => {
             println!(
                 "🏠 Starting Cluster Host on port {} with name {}",
                 port, name
             );
-            agents::run_cluster_host(name, port, host).await?;
+            registry::set_state_file(state_file);
+            agents::run_cluster_host(name, port, bind, metrics_port).await?;
         }
         Commands::Doctor {
             port,
             host_addr,
             name,
-            host,
+            bind,
+            theme,
+            memory_window,
+            confirm_camera,
+            rate_limit_per_min,
+            max_query_chars,
+            channel_capacity,
+            max_tool_calls,
+            max_turns,
+            query_timeout_secs,
+            no_gui,
+            replay_query,
+            no_notifications,
+            poll_interval_ms,
+            window_width,
+            window_height,
+            window_title,
+            #[cfg(feature = "voice")]
+            transcribe_command,
         } => {
+            let node = runner::ClusterNode {
+                node_name: name,
+                port,
+                host_addr,
+                bind,
+            };
+            if no_gui || replay_query.is_some() {
+                println!(
+                    "🔍 Starting headless Doctor Agent on port {} with name {}",
+                    node.port, node.node_name
+                );
+                if confirm_camera {
+                    println!("⚠️ --confirm-camera needs the GUI and is ignored with --no-gui");
+                }
+                let settings = agents::DoctorSettings {
+                    memory_window: memory_window.unwrap_or(DEFAULT_DOCTOR_MEMORY_WINDOW),
+                    confirm_camera: false,
+                    rate_limit_per_min,
+                    max_query_chars,
+                    channel_capacity,
+                    max_tool_calls,
+                    max_turns,
+                    query_timeout: std::time::Duration::from_secs(query_timeout_secs),
+                    history: Vec::new(),
+                };
+                let llm = require_llm(provider);
+                let doctor = spawn_doctor_agent(llm, node, settings);
+                match replay_query {
+                    Some(query) => {
+                        if !run_replay_query(doctor.channels, query).await {
+                            std::process::exit(1);
+                        }
+                    }
+                    None => run_headless(doctor.channels).await,
+                }
+                return Ok(());
+            }
+
             println!(
                 "🔍 Starting Doctor Agent with GUI on port {} with name {}",
-                port, name
+                node.port, node.node_name
             );
 
-            // Create channels for communication
-            let (response_tx, response_rx) = mpsc::unbounded_channel::<String>();
-            let (user_tx, user_rx) = mpsc::unbounded_channel::<String>();
-
-            // Start the agent in a separate thread
-            let llm_clone = llm.clone();
-            let name_clone = name.clone();
-            let host_addr_clone = host_addr.clone();
-            let host_clone = host.clone();
-            let response_tx_clone = response_tx.clone();
-
-            std::thread::spawn(move || {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(async move {
-                    if let Err(e) = agents::run_doctor_agent(
-                        llm_clone,
-                        name_clone,
-                        port,
-                        host_addr_clone,
-                        host_clone,
-                        user_rx,
-                        response_tx_clone,
-                    )
-                    .await
-                    {
-                        eprintln!("Agent error: {}", e);
-                    }
-                });
+            let settings = agents::DoctorSettings {
+                memory_window: memory_window.unwrap_or(DEFAULT_DOCTOR_MEMORY_WINDOW),
+                confirm_camera,
+                rate_limit_per_min,
+                max_query_chars,
+                channel_capacity,
+                max_tool_calls,
+                max_turns,
+                query_timeout: std::time::Duration::from_secs(query_timeout_secs),
+                history: Vec::new(),
+            };
+
+            // Dictation uses the OpenAI key when there is one, whichever provider runs the agents
+            #[cfg(feature = "voice")]
+            voice::configure(match transcribe_command {
+                Some(command) => Some(voice::Transcriber::Command(command)),
+                None => std::env::var("OPENAI_API_KEY")
+                    .ok()
+                    .filter(|key| !key.trim().is_empty())
+                    .map(|api_key| voice::Transcriber::OpenAi {
+                        api_key,
+                        base_url: OPENAI_BASE_URL.lock().unwrap().clone(),
+                        org: OPENAI_ORG.lock().unwrap().clone(),
+                    }),
             });
 
+            // Without a usable key the GUI opens on a dialog to enter one and retry
+            let launch = match validate_api_key(provider, std::env::var(provider.key_var()).ok()) {
+                Ok(api_key) => Ok(doctor_connector(provider, api_key, node, settings)),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    Err(gui::KeySetup {
+                        key_var: provider.key_var(),
+                        error: e.to_string(),
+                        retry: Arc::new(move |entered: Option<String>| {
+                            let raw = entered.or_else(|| std::env::var(provider.key_var()).ok());
+                            let api_key = validate_api_key(provider, raw)?;
+                            Ok(doctor_connector(
+                                provider,
+                                api_key,
+                                node.clone(),
+                                settings.clone(),
+                            ))
+                        }),
+                    })
+                }
+            };
+
             // Run the GUI
-            gui::run_chat_app(user_tx, response_rx)?;
+            gui::run_chat_app(
+                launch,
+                theme,
+                !no_notifications,
+                std::time::Duration::from_millis(poll_interval_ms),
+                provider.llm_settings(DEFAULT_DOCTOR_MEMORY_WINDOW),
+                memory_window,
+                gui::WindowOptions {
+                    width: window_width,
+                    height: window_height,
+                    title: window_title,
+                },
+            )?;
+            wait_for_doctor_shutdown();
         }
         Commands::Analysis {
             port,
             host_addr,
             name,
-            host,
+            bind,
+            memory_window,
+            emit_raw_json,
+            analysis_concurrency,
+            task_timeout_secs,
+            #[cfg(feature = "waveform")]
+            render_waveform,
+            #[cfg(feature = "webhook")]
+            webhook_url,
         } => {
             println!(
                 "🧠 Starting AnalysisAgent on port {} with name {}",
                 port, name
             );
-            agents::run_analysis_agent(llm, name, port, host_addr, host).await?;
+            #[cfg(feature = "waveform")]
+            if render_waveform {
+                waveform::enable();
+            }
+            #[cfg(feature = "webhook")]
+            webhook::configure(webhook_url);
+            agents::run_analysis_agent(
+                require_llm(provider),
+                runner::ClusterNode {
+                    node_name: name,
+                    port,
+                    host_addr,
+                    bind,
+                },
+                memory_window,
+                emit_raw_json,
+                analysis_concurrency,
+                std::time::Duration::from_secs(task_timeout_secs),
+            )
+            .await?;
+        }
+        Commands::All {
+            bind,
+            theme,
+            window_width,
+            window_height,
+            window_title,
+        } => {
+            println!("🚀 Starting cluster host and all agents in one process");
+            run_all(
+                provider,
+                require_api_key(provider),
+                bind,
+                theme,
+                gui::WindowOptions {
+                    width: window_width,
+                    height: window_height,
+                    title: window_title,
+                },
+            )
+            .await?;
+        }
+        Commands::AnalyzeOnce { query, output } => {
+            if let Err(e) = agents::run_analysis_once(require_llm(provider), query, output).await {
+                eprintln!("❌ Analysis failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::BatchAnalyze {
+            dir,
+            output_dir,
+            concurrency,
+            sample_rate_hz,
+            dry_run,
+        } => {
+            let llm = (!dry_run).then(|| require_llm(provider));
+            let settings = batch::BatchSettings {
+                output_dir,
+                concurrency,
+                sample_rate_hz,
+                dry_run,
+            };
+            let failed = batch::run(llm, &dir, settings).await?;
+            if !dry_run {
+                usage::print_final_summary();
+            }
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Info => {
+            info::print(
+                &format!("{:?}", provider).to_lowercase(),
+                provider.model(),
+                std::env::var(provider.key_var()).is_ok_and(|key| !key.trim().is_empty()),
+            );
+        }
+        Commands::SelfTest { host, ports } => {
+            let llm = create_llm_provider(provider).map_err(|e| e.to_string());
+            if !selftest::run(llm, provider.key_var(), &host, &ports).await {
+                std::process::exit(1);
+            }
+        }
+        Commands::Replay { path } => {
+            println!("📝 Replaying routing decisions from {}", path.display());
+            let mismatches = event_log::replay(&path)?;
+            if mismatches > 0 {
+                eprintln!(
+                    "❌ {} routing decision(s) differ from the recording",
+                    mismatches
+                );
+                std::process::exit(1);
+            }
+            println!("✅ Every replayed decision matches the recording");
         }
         Commands::Camera {
             port,
             host_addr,
             name,
-            host,
+            bind,
+            memory_window,
+            max_image_dim,
+            max_image_bytes,
+            camera_text_fallback,
+            capture_timeout_secs,
+            capture_resolution,
+            camera_to_analysis,
         } => {
             println!(
                 "📷 Starting CameraAgent on port {} with name {}",
                 port, name
             );
-            agents::run_camera_agent(llm, name, port, host_addr, host).await?;
+            let settings = agents::CameraSettings {
+                max_image_dim,
+                max_image_bytes,
+                text_fallback: camera_text_fallback,
+                capture_timeout: std::time::Duration::from_secs(capture_timeout_secs),
+                capture_resolution,
+                result_topic: if camera_to_analysis {
+                    topics::ANALYSIS_REQUESTS
+                } else {
+                    topics::CAMERA_RESPONSE
+                },
+            };
+            let node = runner::ClusterNode {
+                node_name: name,
+                port,
+                host_addr,
+                bind,
+            };
+            agents::run_camera_agent(require_llm(provider), node, memory_window, settings).await?;
         }
     }
     Ok(())
 }
 
-fn create_llm_provider() -> Result<Arc<OpenAI>, Box<dyn std::error::Error>> {
-    let api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
+// Path arguments may start with ~ and contain $VAR or ${VAR}, which a quoted argument or a
+// service definition passes through unexpanded. An unset variable is an error rather than an
+// empty path segment.
+fn parse_path(value: &str) -> Result<std::path::PathBuf, String> {
+    expand_path(value, |name| std::env::var(name).ok())
+}
 
-    let llm: Arc<OpenAI> = LLMBuilder::<OpenAI>::new()
-        .api_key(api_key)
-        .model("gpt-4o-mini")
-        .max_tokens(512)
-        .temperature(0.2)
-        .build()
-        .expect("Failed to build LLM");
+fn expand_path(
+    value: &str,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<std::path::PathBuf, String> {
+    let unset = |name: &str| {
+        format!(
+            "cannot expand '{}': environment variable {} is not set",
+            value, name
+        )
+    };
+    // Only "~" on its own or before a separator; "~user" is left alone
+    let (mut expanded, rest) = match value.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => (
+            var("HOME")
+                .or_else(|| var("USERPROFILE"))
+                .ok_or_else(|| unset("HOME"))?,
+            rest,
+        ),
+        _ => (String::new(), value),
+    };
+
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        if braced && (name.is_empty() || chars.next_if_eq(&'}').is_none()) {
+            return Err(format!(
+                "cannot expand '{}': expected ${{NAME}} with a closing brace",
+                value
+            ));
+        }
+        if name.is_empty() {
+            // A '$' that doesn't start a name is kept as is
+            expanded.push('$');
+            continue;
+        }
+        expanded.push_str(&var(&name).ok_or_else(|| unset(&name))?);
+    }
+    Ok(std::path::PathBuf::from(expanded))
+}
+
+// Doctor memory window when neither --memory-window nor the GUI settings give one
+const DEFAULT_DOCTOR_MEMORY_WINDOW: usize = 50;
+
+fn parse_memory_window(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(n),
+        Ok(_) => Err("memory window must be at least 1".to_string()),
+        Err(e) => Err(format!("invalid memory window '{}': {}", value, e)),
+    }
+}
+
+// Smaller windows cut off the header controls; larger ones exceed any display
+const MIN_WINDOW_SIZE: f32 = 320.0;
+const MAX_WINDOW_SIZE: f32 = 7680.0;
+
+fn parse_window_size(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(n) if (MIN_WINDOW_SIZE..=MAX_WINDOW_SIZE).contains(&n) => Ok(n),
+        Ok(_) => Err(format!(
+            "window size must be between {} and {} pixels",
+            MIN_WINDOW_SIZE, MAX_WINDOW_SIZE
+        )),
+        Err(e) => Err(format!("invalid window size '{}': {}", value, e)),
+    }
+}
+
+fn parse_window_title(value: &str) -> Result<String, String> {
+    let title = value.trim();
+    if title.is_empty() {
+        return Err("window title must not be empty".to_string());
+    }
+    Ok(title.to_string())
+}
+
+fn parse_analysis_concurrency(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(n),
+        Ok(_) => Err("analysis concurrency must be at least 1".to_string()),
+        Err(e) => Err(format!("invalid analysis concurrency '{}': {}", value, e)),
+    }
+}
+
+fn parse_task_timeout_secs(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(n) if n >= 1 => Ok(n),
+        Ok(_) => Err("task timeout must be at least 1 second".to_string()),
+        Err(e) => Err(format!("invalid task timeout '{}': {}", value, e)),
+    }
+}
+
+// Letters, digits, '-', '_' and '.', so the prefix can't be confused with the '/' separator
+fn parse_cluster_namespace(value: &str) -> Result<String, String> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if value.is_empty() || !value.chars().all(valid) {
+        return Err(format!(
+            "invalid cluster namespace '{}': use letters, digits, '-', '_' or '.'",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_host_addr(value: &str) -> Result<String, String> {
+    runner::parse_host_addr(value).map(|_| value.to_string())
+}
+
+fn parse_host(value: &str) -> Result<String, String> {
+    runner::validate_host(value).map(|_| value.to_string())
+}
+
+// Accepts "WxH" with positive dimensions, e.g. 1920x1080
+fn parse_capture_resolution(value: &str) -> Result<String, String> {
+    let invalid = || {
+        format!(
+            "invalid capture resolution '{}': expected WxH, e.g. 1280x720",
+            value
+        )
+    };
+    let (width, height) = value.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (width.parse::<u32>(), height.parse::<u32>()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok(format!("{}x{}", w, h)),
+        _ => Err(invalid()),
+    }
+}
+
+impl Commands {
+    fn node_name(&self) -> &str {
+        match self {
+            Commands::Host { name, .. }
+            | Commands::Doctor { name, .. }
+            | Commands::Analysis { name, .. }
+            | Commands::Camera { name, .. } => name,
+            Commands::All { .. } => "all",
+            Commands::AnalyzeOnce { .. } => "analysis_once",
+            Commands::BatchAnalyze { .. } => "batch_analyze",
+            Commands::Replay { .. } => "replay",
+            Commands::SelfTest { .. } => "self_test",
+            Commands::Info => "info",
+        }
+    }
+
+    // Addresses this node binds to or connects to on the cluster transport
+    fn cluster_addresses(&self) -> Vec<&str> {
+        match self {
+            Commands::Host { bind, .. } | Commands::All { bind, .. } => vec![bind.as_str()],
+            Commands::Doctor {
+                bind, host_addr, ..
+            }
+            | Commands::Analysis {
+                bind, host_addr, ..
+            }
+            | Commands::Camera {
+                bind, host_addr, ..
+            } => vec![bind.as_str(), host_addr.as_str()],
+            Commands::AnalyzeOnce { .. }
+            | Commands::BatchAnalyze { .. }
+            | Commands::Replay { .. }
+            | Commands::SelfTest { .. }
+            | Commands::Info => Vec::new(),
+        }
+    }
+}
+
+// Run host, analysis and camera as background tasks and the doctor GUI in the foreground.
+// Every node listens for Ctrl+C and stops its runtime; once they are done the process exits,
+// since the GUI event loop would otherwise keep it alive.
+async fn run_all(
+    provider: Provider,
+    api_key: String,
+    bind: String,
+    theme: Option<gui::ThemeMode>,
+    window: gui::WindowOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let llm = build_llm(
+        provider,
+        api_key.clone(),
+        provider.model(),
+        DEFAULT_TEMPERATURE,
+    )?;
+    let host_addr = format!("{}:9000", bind);
+    let mut nodes = Vec::new();
+    let node = |node_name: &str, port: u16| runner::ClusterNode {
+        node_name: node_name.to_string(),
+        port,
+        host_addr: host_addr.clone(),
+        bind: bind.clone(),
+    };
+
+    let node_bind = bind.clone();
+    nodes.push(tokio::spawn(async move {
+        if let Err(e) =
+            agents::run_cluster_host("cluster_host".to_string(), 9000, node_bind, None).await
+        {
+            eprintln!("❌ Cluster host error: {}", e);
+        }
+    }));
+
+    // Give the host a moment to bind before the clients connect
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let (node_llm, analysis_node) = (llm.clone(), node("analysis", 9002));
+    nodes.push(tokio::spawn(async move {
+        if let Err(e) = agents::run_analysis_agent(
+            node_llm,
+            analysis_node,
+            10,
+            false,
+            1,
+            agents::DEFAULT_TASK_TIMEOUT,
+        )
+        .await
+        {
+            eprintln!("❌ AnalysisAgent error: {}", e);
+        }
+    }));
+
+    if !agents::camera_disabled() {
+        let (node_llm, camera_node) = (llm.clone(), node("camera", 9003));
+        nodes.push(tokio::spawn(async move {
+            let settings = agents::CameraSettings {
+                max_image_dim: 1024,
+                max_image_bytes: 3_500_000,
+                text_fallback: false,
+                capture_timeout: std::time::Duration::from_secs(15),
+                capture_resolution: "640x480".to_string(),
+                result_topic: topics::CAMERA_RESPONSE,
+            };
+            if let Err(e) = agents::run_camera_agent(node_llm, camera_node, 10, settings).await {
+                eprintln!("❌ CameraAgent error: {}", e);
+            }
+        }));
+    }
+
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        println!("🛑 Ctrl+C received, waiting for all nodes to shut down...");
+        for node in nodes {
+            let _ = tokio::time::timeout(std::time::Duration::from_secs(5), node).await;
+        }
+        println!("👋 All nodes stopped");
+        std::process::exit(0);
+    });
+
+    let settings = agents::DoctorSettings {
+        memory_window: DEFAULT_DOCTOR_MEMORY_WINDOW,
+        confirm_camera: false,
+        rate_limit_per_min: 20,
+        max_query_chars: 4000,
+        channel_capacity: 256,
+        max_tool_calls: 4,
+        max_turns: 10,
+        query_timeout: agents::DEFAULT_QUERY_TIMEOUT,
+        history: Vec::new(),
+    };
+    let llm_defaults = provider.llm_settings(settings.memory_window);
+    let connector = doctor_connector(provider, api_key, node("doctor", 9001), settings);
+
+    gui::run_chat_app(
+        Ok(connector),
+        theme,
+        true,
+        std::time::Duration::from_millis(100),
+        llm_defaults,
+        None,
+        window,
+    )?;
+    wait_for_doctor_shutdown();
+    Ok(())
+}
+
+// Line-based doctor session over stdin/stdout for servers without a display. Uses the same
+// channels as the GUI; after stdin closes it waits for the queued queries to be answered.
+async fn run_headless(channels: gui::AgentChannels) {
+    let gui::AgentChannels {
+        user_sender,
+        mut response_receiver,
+        mut queue_receiver,
+        ..
+    } = channels;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+    let mut busy = false;
+    let mut awaiting_status = false; // A query was sent and the queue hasn't reported on it yet
+
+    println!("🔍 Headless doctor ready - type a query and press Enter (Ctrl+D to finish)");
+    loop {
+        if !stdin_open && !busy && !awaiting_status {
+            break;
+        }
+        tokio::select! {
+            line = lines.next_line(), if stdin_open => match line {
+                Ok(Some(line)) if !line.trim().is_empty() => {
+                    // Waits while the agent's queue is full instead of dropping input
+                    let _ = user_sender.send(format!("USER_SEND:{}", line.trim())).await;
+                    awaiting_status = true;
+                }
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => stdin_open = false,
+            },
+            response = response_receiver.recv() => match response {
+                Some(response) => {
+                    println!("\n🩺 {}\n", response.content);
+                    awaiting_status = false;
+                }
+                None => {
+                    eprintln!("❌ Doctor agent exited");
+                    break;
+                }
+            },
+            Some(status) = queue_receiver.recv() => {
+                busy = status.active.is_some() || status.queued > 0;
+                awaiting_status = false;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+}
+
+// How long --replay-query waits for the doctor's answer; covers a full ReAct loop with tool calls
+const REPLAY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(180);
+
+// Send a single query through the same channels as the GUI and print everything that comes
+// back until the doctor's final answer, the one after any analysis or camera results. Returns
+// whether it arrived.
+async fn run_replay_query(channels: gui::AgentChannels, query: String) -> bool {
+    let gui::AgentChannels {
+        user_sender,
+        mut response_receiver,
+        ..
+    } = channels;
+
+    println!("🔁 Replaying query: {}", query);
+    if user_sender
+        .send(format!("USER_SEND:{}", query.trim()))
+        .await
+        .is_err()
+    {
+        eprintln!("❌ Doctor agent exited before the query could be sent");
+        return false;
+    }
+
+    let answer = tokio::time::timeout(REPLAY_TIMEOUT, async {
+        while let Some(response) = response_receiver.recv().await {
+            // Notices, tool errors, reports and the "request submitted" step are printed but
+            // don't count as the answer
+            if response.is_final {
+                println!("\n🩺 {}\n", response.content);
+                return true;
+            }
+            match &response.agent_source {
+                Some(source) if !response.is_error => {
+                    println!("📄 [{}] {}", source, response.content)
+                }
+                _ => println!("ℹ️ {}", response.content),
+            }
+        }
+        false
+    })
+    .await;
+    match answer {
+        Ok(true) => true,
+        Ok(false) => {
+            eprintln!("❌ Doctor agent exited before answering");
+            false
+        }
+        Err(_) => {
+            eprintln!("⏰ No answer within {:?}", REPLAY_TIMEOUT);
+            false
+        }
+    }
+}
+
+// Each call builds the LLM from the GUI's model settings and spawns a supervised agent thread
+// with fresh channels and the open conversation in its memory
+fn doctor_connector(
+    provider: Provider,
+    api_key: String,
+    node: runner::ClusterNode,
+    settings: agents::DoctorSettings,
+) -> gui::AgentConnector {
+    Arc::new(
+        move |llm_settings: &prefs::LlmSettings, history: &[conversations::StoredMessage]| {
+            let llm = build_llm(
+                provider,
+                api_key.clone(),
+                &llm_settings.model,
+                llm_settings.temperature,
+            )
+            .map_err(|e| e.to_string())?;
+            let settings = agents::DoctorSettings {
+                memory_window: llm_settings.memory_window,
+                history: history.to_vec(),
+                ..settings.clone()
+            };
+            let node = node.clone();
+            // A doctor stopped for new settings or another conversation has to release the
+            // cluster port first. This blocks, so the GUI calls the connector off its event loop.
+            wait_for_doctor_shutdown();
+            wait_for_port_release(&node.bind, node.port)?;
+            Ok(supervise_doctor_agent(move || {
+                spawn_doctor_agent(llm.clone(), node.clone(), settings.clone())
+            }))
+        },
+    )
+}
+
+// Restart the doctor thread at most this many times in a row before giving up
+const MAX_DOCTOR_RESTARTS: u32 = 3;
+// A doctor thread that ran at least this long counts as healthy and resets the restart count
+const DOCTOR_HEALTHY_AFTER: std::time::Duration = std::time::Duration::from_secs(60);
+// Lets the old runtime release the cluster port before the new one binds it
+const DOCTOR_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+// How long the process waits after the GUI closed for the doctor node to leave the cluster
+const DOCTOR_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Supervisor threads of every doctor started for the GUI; one finishes once its doctor has
+// stopped and won't be restarted
+static DOCTOR_SUPERVISORS: std::sync::Mutex<Vec<std::thread::JoinHandle<()>>> =
+    std::sync::Mutex::new(Vec::new());
+
+// Called after the GUI window closed, which tells the doctor node to stop its runtime
+fn wait_for_doctor_shutdown() {
+    let supervisors = std::mem::take(&mut *DOCTOR_SUPERVISORS.lock().unwrap());
+    let deadline = std::time::Instant::now() + DOCTOR_SHUTDOWN_TIMEOUT;
+    while supervisors
+        .iter()
+        .any(|supervisor| !supervisor.is_finished())
+    {
+        if std::time::Instant::now() >= deadline {
+            eprintln!(
+                "⚠️ Doctor agent didn't stop within {:?}, exiting anyway",
+                DOCTOR_SHUTDOWN_TIMEOUT
+            );
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    println!("👋 Doctor agent stopped");
+}
+
+// How long a new doctor waits for the previous one's cluster port to become free
+const DOCTOR_PORT_RELEASE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+// The supervisor finishing doesn't mean the OS let go of the listener yet; binding the port
+// ourselves is the only reliable check
+fn wait_for_port_release(bind: &str, port: u16) -> Result<(), String> {
+    let deadline = std::time::Instant::now() + DOCTOR_PORT_RELEASE_TIMEOUT;
+    loop {
+        match std::net::TcpListener::bind((bind, port)) {
+            Ok(_) => return Ok(()),
+            Err(e) if std::time::Instant::now() >= deadline => {
+                return Err(format!(
+                    "port {} on {} is still in use after {:?}: {}",
+                    port, bind, DOCTOR_PORT_RELEASE_TIMEOUT, e
+                ));
+            }
+            Err(_) => std::thread::sleep(std::time::Duration::from_millis(200)),
+        }
+    }
+}
+
+// A running doctor thread and the channels to it
+struct DoctorThread {
+    channels: gui::AgentChannels,
+    control_tx: mpsc::UnboundedSender<gui::AgentControl>,
+    handle: std::thread::JoinHandle<()>,
+}
+
+// Start the doctor thread and watch it: whenever it exits while the GUI still listens, spawn a
+// new one and hand its channels to the GUI over the control channel of the old ones
+fn supervise_doctor_agent(spawn: impl Fn() -> DoctorThread + Send + 'static) -> gui::AgentChannels {
+    let DoctorThread {
+        channels,
+        mut control_tx,
+        mut handle,
+    } = spawn();
+
+    let supervisor = std::thread::spawn(move || {
+        let mut restarts = 0;
+        let mut started = std::time::Instant::now();
+        loop {
+            match handle.join() {
+                Ok(()) => eprintln!("⚠️ Doctor agent thread exited"),
+                Err(_) => eprintln!("💥 Doctor agent thread panicked"),
+            }
+            // The GUI dropped these channels (app closing or a new API key), nothing to restore
+            if control_tx.is_closed() {
+                return;
+            }
+            if started.elapsed() >= DOCTOR_HEALTHY_AFTER {
+                restarts = 0;
+            }
+            if restarts >= MAX_DOCTOR_RESTARTS {
+                eprintln!(
+                    "❌ Doctor agent failed {} times in a row, giving up",
+                    MAX_DOCTOR_RESTARTS
+                );
+                let _ = control_tx.send(gui::AgentControl::GaveUp);
+                return;
+            }
+
+            restarts += 1;
+            println!(
+                "🔄 Restarting doctor agent (attempt {}/{})",
+                restarts, MAX_DOCTOR_RESTARTS
+            );
+            let _ = control_tx.send(gui::AgentControl::Reconnecting {
+                attempt: restarts,
+                max_attempts: MAX_DOCTOR_RESTARTS,
+            });
+            std::thread::sleep(DOCTOR_RESTART_DELAY);
+
+            let next = spawn();
+            started = std::time::Instant::now();
+            if control_tx
+                .send(gui::AgentControl::Restarted(next.channels))
+                .is_err()
+            {
+                return;
+            }
+            control_tx = next.control_tx;
+            handle = next.handle;
+        }
+    });
+    DOCTOR_SUPERVISORS.lock().unwrap().push(supervisor);
+
+    channels
+}
+
+// Start the doctor agent in a separate thread and return the channels used to talk to it
+fn spawn_doctor_agent(
+    llm: Arc<dyn LLMProvider>,
+    node: runner::ClusterNode,
+    settings: agents::DoctorSettings,
+) -> DoctorThread {
+    // Create channels for communication
+    // Bounded so a runaway producer on either side can't grow memory without limit
+    let (response_tx, response_rx) =
+        mpsc::channel::<agents::AgentResponse>(settings.channel_capacity);
+    let (user_tx, user_rx) = mpsc::channel::<String>(settings.channel_capacity);
+    let (ack_tx, ack_rx) = mpsc::unbounded_channel::<String>();
+    let (cancel_tx, cancel_rx) = mpsc::unbounded_channel::<()>();
+    let (camera_confirm_tx, camera_confirm_rx) =
+        mpsc::unbounded_channel::<agents::CameraConfirmRequest>();
+    let (queue_tx, queue_rx) = mpsc::unbounded_channel::<agents::QueueStatus>();
+    let (reasoning_tx, reasoning_rx) = mpsc::unbounded_channel::<String>();
+    let (upload_tx, upload_rx) = mpsc::unbounded_channel::<agents::UploadedImage>();
+    let (control_tx, control_rx) = mpsc::unbounded_channel::<gui::AgentControl>();
+
+    let handle = std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async move {
+            if let Err(e) = agents::run_doctor_agent(
+                llm,
+                node,
+                agents::DoctorChannels {
+                    user_rx,
+                    response_tx,
+                    ack_tx,
+                    cancel_rx,
+                    camera_confirm_tx: settings.confirm_camera.then_some(camera_confirm_tx),
+                    queue_tx,
+                    reasoning_tx,
+                    upload_rx,
+                },
+                settings,
+            )
+            .await
+            {
+                eprintln!("Agent error: {}", e);
+            }
+        });
+    });
+
+    DoctorThread {
+        channels: gui::AgentChannels {
+            user_sender: user_tx,
+            response_receiver: response_rx,
+            ack_receiver: ack_rx,
+            cancel_sender: cancel_tx,
+            camera_confirm_receiver: camera_confirm_rx,
+            queue_receiver: queue_rx,
+            reasoning_receiver: reasoning_rx,
+            upload_sender: upload_tx,
+            control_receiver: control_rx,
+        },
+        control_tx,
+        handle,
+    }
+}
+
+impl Provider {
+    fn key_var(self) -> &'static str {
+        match self {
+            Provider::Openai => "OPENAI_API_KEY",
+            Provider::Anthropic => "ANTHROPIC_API_KEY",
+        }
+    }
+
+    fn model(self) -> &'static str {
+        match self {
+            Provider::Openai => "gpt-4o-mini",
+            Provider::Anthropic => "claude-3-5-sonnet-latest",
+        }
+    }
+
+    // Model settings the GUI starts from unless it saved its own for this provider
+    fn llm_settings(self, memory_window: usize) -> prefs::LlmSettings {
+        prefs::LlmSettings {
+            provider: format!("{:?}", self).to_lowercase(),
+            model: self.model().to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            memory_window,
+        }
+    }
+
+    fn key_prefix(self) -> &'static str {
+        match self {
+            Provider::Openai => "sk-",
+            Provider::Anthropic => "sk-ant-",
+        }
+    }
+}
+
+// Custom OpenAI endpoint from --openai-base-url or OPENAI_BASE_URL; None uses the public API
+static OPENAI_BASE_URL: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+// From --openai-org or OPENAI_ORG; only dictation can send it
+static OPENAI_ORG: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+// Flags win over the environment. The base URL is checked here as well since env values skip
+// clap's parser.
+fn configure_openai_endpoint(base_url: Option<String>, org: Option<String>) -> Result<(), String> {
+    let env_setting = |var: &str| std::env::var(var).ok().filter(|v| !v.trim().is_empty());
+    let base_url = match base_url {
+        Some(base_url) => Some(base_url),
+        None => env_setting("OPENAI_BASE_URL")
+            .map(|value| parse_base_url(value.trim()))
+            .transpose()
+            .map_err(|e| format!("OPENAI_BASE_URL: {}", e))?,
+    };
+    if let Some(base_url) = &base_url {
+        println!("🌐 Using OpenAI endpoint {}", base_url);
+    }
+    *OPENAI_BASE_URL.lock().unwrap() = base_url;
+
+    // The LLM client has no organization setting, so LLM requests always go out under the
+    // key's default organization. Say so instead of silently ignoring it.
+    let org = org
+        .or_else(|| env_setting("OPENAI_ORG"))
+        .map(|org| org.trim().to_string());
+    if let Some(org) = &org {
+        eprintln!(
+            "⚠️ OpenAI organization '{}' is only applied to dictation: the LLM client can't send an organization header, LLM requests use the key's default organization",
+            org
+        );
+    }
+    *OPENAI_ORG.lock().unwrap() = org;
+    Ok(())
+}
+
+// http(s)://host[:port][/path], normalized to end in '/' so API paths are joined below it
+fn parse_base_url(value: &str) -> Result<String, String> {
+    let invalid = |reason: &str| format!("invalid base URL '{}': {}", value, reason);
+    let rest = value
+        .strip_prefix("https://")
+        .or_else(|| value.strip_prefix("http://"))
+        .ok_or_else(|| invalid("must start with http:// or https://"))?;
+    if value.chars().any(char::is_whitespace) {
+        return Err(invalid("must not contain whitespace"));
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    // Bracketed IPv6 hosts contain ':' themselves, so only look for a port after the ']'
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(v6) => match v6.split_once(']') {
+            Some((host, after)) => (host, after.strip_prefix(':')),
+            None => return Err(invalid("unclosed '[' in host")),
+        },
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    if let Some(port) = port {
+        port.parse::<u16>()
+            .map_err(|_| invalid(&format!("port '{}' is not a number", port)))?;
+    }
+    if host.is_empty() {
+        return Err(invalid("missing host"));
+    }
+    if rest.contains(['?', '#']) {
+        return Err(invalid("must not contain a query or fragment"));
+    }
+    Ok(if value.ends_with('/') {
+        value.to_string()
+    } else {
+        format!("{}/", value)
+    })
+}
+
+// Keys shorter than this are almost certainly truncated or placeholders
+const MIN_API_KEY_LEN: usize = 20;
+
+fn validate_api_key(provider: Provider, raw: Option<String>) -> Result<String, String> {
+    let key_var = provider.key_var();
+    let api_key = match raw {
+        Some(value) => value.trim().to_string(),
+        None => return Err(format!("Set {} to a valid key (variable not set)", key_var)),
+    };
+
+    if api_key.is_empty() {
+        return Err(format!("Set {} to a valid key (got empty value)", key_var));
+    }
+
+    // Only warn on unexpected shapes - proxies and gateways may use their own key formats
+    if !api_key.starts_with(provider.key_prefix()) || api_key.len() < MIN_API_KEY_LEN {
+        eprintln!(
+            "⚠️ {} does not look like a valid key (expected '{}' prefix and at least {} characters)",
+            key_var,
+            provider.key_prefix(),
+            MIN_API_KEY_LEN
+        );
+    }
+
+    Ok(api_key)
+}
+
+// LLM for the commands without a GUI: a missing or invalid key is reported and the process
+// exits non-zero instead of panicking
+fn require_llm(provider: Provider) -> Arc<dyn LLMProvider> {
+    create_llm_provider(provider).unwrap_or_else(|e| exit_without_key(provider, &e.to_string()))
+}
+
+// Same for commands that build their LLMs from the key themselves
+fn require_api_key(provider: Provider) -> String {
+    validate_api_key(provider, std::env::var(provider.key_var()).ok())
+        .unwrap_or_else(|e| exit_without_key(provider, &e))
+}
+
+fn exit_without_key(provider: Provider, error: &str) -> ! {
+    eprintln!("❌ {}", error);
+    eprintln!(
+        "   Export {} (e.g. `export {}=...`) and run the command again",
+        provider.key_var(),
+        provider.key_var()
+    );
+    std::process::exit(1);
+}
+
+fn create_llm_provider(
+    provider: Provider,
+) -> Result<Arc<dyn LLMProvider>, Box<dyn std::error::Error>> {
+    let api_key = validate_api_key(provider, std::env::var(provider.key_var()).ok())?;
+
+    build_llm(provider, api_key, provider.model(), DEFAULT_TEMPERATURE)
+}
+
+// Sampling temperature unless the GUI settings panel changed it; low for consistent reports
+const DEFAULT_TEMPERATURE: f32 = 0.2;
+
+// Each command builds one provider and shares it between all of its agents, so every chat call
+// goes through the same HTTP client and reuses its connection pool
+fn build_llm(
+    provider: Provider,
+    api_key: String,
+    model: &str,
+    temperature: f32,
+) -> Result<Arc<dyn LLMProvider>, Box<dyn std::error::Error>> {
+    let llm = build_provider(provider, api_key.clone(), model, temperature)?;
+    // Retries of an empty or malformed answer use the same settings at a higher temperature
+    let model = model.to_string();
+    agents::register_retry_llms(&llm, temperature, move |temperature| {
+        build_provider(provider, api_key.clone(), &model, temperature).map_err(|e| e.to_string())
+    });
+    Ok(llm)
+}
+
+fn build_provider(
+    provider: Provider,
+    api_key: String,
+    model: &str,
+    temperature: f32,
+) -> Result<Arc<dyn LLMProvider>, Box<dyn std::error::Error>> {
+    let timeout_secs = agents::llm_timeout().as_secs();
+    let llm: Arc<dyn LLMProvider> = match provider {
+        Provider::Openai => {
+            let builder = LLMBuilder::<OpenAI>::new()
+                .api_key(api_key)
+                .model(model)
+                .max_tokens(512)
+                .temperature(temperature)
+                .timeout_seconds(timeout_secs);
+            let builder = match OPENAI_BASE_URL.lock().unwrap().clone() {
+                Some(base_url) => builder.base_url(base_url),
+                None => builder,
+            };
+            builder
+                .build()
+                .map_err(|e| format!("Failed to build LLM: {}", e))?
+        }
+        Provider::Anthropic => LLMBuilder::<Anthropic>::new()
+            .api_key(api_key)
+            .model(model)
+            .max_tokens(512)
+            .temperature(temperature)
+            .timeout_seconds(timeout_secs)
+            .build()
+            .map_err(|e| format!("Failed to build LLM: {}", e))?,
+    };
 
     Ok(llm)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_provider_builds_without_network() {
+        for provider in [Provider::Openai, Provider::Anthropic] {
+            assert!(
+                build_llm(
+                    provider,
+                    "test-key".to_string(),
+                    provider.model(),
+                    DEFAULT_TEMPERATURE
+                )
+                .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn openai_base_urls_are_validated_and_normalized() {
+        assert_eq!(
+            parse_base_url("https://gateway.example.com/openai/v1"),
+            Ok("https://gateway.example.com/openai/v1/".to_string())
+        );
+        assert_eq!(
+            parse_base_url("http://localhost:8080/v1/"),
+            Ok("http://localhost:8080/v1/".to_string())
+        );
+        assert!(parse_base_url("http://[::1]:8080/v1").is_ok());
+        assert!(parse_base_url("gateway.example.com/v1").is_err());
+        assert!(parse_base_url("https:///v1").is_err());
+        assert!(parse_base_url("https://gateway:port/v1").is_err());
+        assert!(parse_base_url("https://gateway.example.com/v1?key=1").is_err());
+        assert!(parse_base_url("https://gateway example.com").is_err());
+    }
+
+    #[test]
+    fn window_size_and_title_are_validated() {
+        assert_eq!(parse_window_size("1280"), Ok(1280.0));
+        assert!(parse_window_size("100").is_err());
+        assert!(parse_window_size("wide").is_err());
+        assert_eq!(
+            parse_window_title("  Ward 3 monitor "),
+            Ok("Ward 3 monitor".to_string())
+        );
+        assert!(parse_window_title("   ").is_err());
+    }
+
+    #[test]
+    fn paths_expand_home_and_environment_variables() {
+        let var = |name: &str| match name {
+            "HOME" => Some("/home/nurse".to_string()),
+            "DATA_DIR" => Some("/srv/ecg".to_string()),
+            _ => None,
+        };
+        let expand = |value: &str| expand_path(value, var).map(|path| path.display().to_string());
+
+        assert_eq!(
+            expand("~/liquidos/app.log"),
+            Ok("/home/nurse/liquidos/app.log".to_string())
+        );
+        assert_eq!(expand("~"), Ok("/home/nurse".to_string()));
+        assert_eq!(
+            expand("$HOME/events.jsonl"),
+            Ok("/home/nurse/events.jsonl".to_string())
+        );
+        assert_eq!(
+            expand("${DATA_DIR}_2024/bed4.csv"),
+            Ok("/srv/ecg_2024/bed4.csv".to_string())
+        );
+        // Untouched: "~user", a '$' that starts no name, plain paths
+        assert_eq!(expand("~nurse/x"), Ok("~nurse/x".to_string()));
+        assert_eq!(expand("cost$/x"), Ok("cost$/x".to_string()));
+        assert_eq!(expand("reports/out.md"), Ok("reports/out.md".to_string()));
+
+        assert!(
+            expand("$MISSING/x")
+                .unwrap_err()
+                .contains("MISSING is not set")
+        );
+        assert!(expand("${DATA_DIR/x").is_err());
+        assert!(expand("${}/x").is_err());
+    }
+
+    #[test]
+    fn api_key_is_trimmed_and_empty_values_rejected() {
+        let key = "sk-abcdefghijklmnopqrstuvwxyz";
+        assert_eq!(
+            validate_api_key(Provider::Openai, Some(format!("  {}\n", key))),
+            Ok(key.to_string())
+        );
+        assert_eq!(
+            validate_api_key(Provider::Openai, Some("   ".to_string())),
+            Err("Set OPENAI_API_KEY to a valid key (got empty value)".to_string())
+        );
+        assert!(validate_api_key(Provider::Anthropic, None).is_err());
+    }
+
+    #[test]
+    fn host_addr_must_be_host_and_port() {
+        assert_eq!(
+            runner::parse_host_addr("localhost:9000"),
+            Ok(("localhost".to_string(), 9000))
+        );
+        assert_eq!(
+            runner::parse_host_addr("[::1]:9000"),
+            Ok(("::1".to_string(), 9000))
+        );
+        assert_eq!(
+            runner::parse_host_addr("localhost9000"),
+            Err("host-addr must be in host:port form, got 'localhost9000'".to_string())
+        );
+        assert!(runner::parse_host_addr(":9000").is_err());
+        assert!(runner::parse_host_addr("localhost:0").is_err());
+        assert!(runner::parse_host_addr("localhost:99999").is_err());
+
+        assert!(runner::validate_host("127.0.0.1").is_ok());
+        assert!(runner::validate_host("::1").is_ok());
+        assert!(runner::validate_host("localhost:9000").is_err());
+    }
+
+    #[test]
+    fn cluster_namespaces_are_plain_identifiers() {
+        assert_eq!(parse_cluster_namespace("ward-3"), Ok("ward-3".to_string()));
+        assert!(parse_cluster_namespace("icu_2.east").is_ok());
+        assert!(parse_cluster_namespace("").is_err());
+        assert!(parse_cluster_namespace("ns1/sub").is_err());
+        assert!(parse_cluster_namespace("ns 1").is_err());
+    }
+
+    #[test]
+    fn capture_resolution_requires_positive_width_and_height() {
+        assert_eq!(
+            parse_capture_resolution("1920x1080"),
+            Ok("1920x1080".to_string())
+        );
+        assert_eq!(
+            parse_capture_resolution("1280X720"),
+            Ok("1280x720".to_string())
+        );
+        assert!(parse_capture_resolution("0x480").is_err());
+        assert!(parse_capture_resolution("640").is_err());
+        assert!(parse_capture_resolution("640x-1").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// Per-topic task counters shared between the cluster host event handler and the metrics endpoint
+#[derive(Clone, Default)]
+pub struct TopicMetrics {
+    counts: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl TopicMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn increment(&self, topic: &str) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        *counts.entry(topic.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn summary_table(&self) -> String {
+        let counts = self.snapshot();
+        if counts.is_empty() {
+            return "📊 No tasks routed yet".to_string();
+        }
+
+        let width = counts.keys().map(|t| t.len()).max().unwrap_or(0).max(5);
+        let mut table = format!("📊 {:<width$}  {:>8}\n", "TOPIC", "TASKS", width = width);
+        for (topic, count) in &counts {
+            table.push_str(&format!(
+                "   {:<width$}  {:>8}\n",
+                topic,
+                count,
+                width = width
+            ));
+        }
+        table.push_str(&format!(
+            "   {:<width$}  {:>8}",
+            "TOTAL",
+            counts.values().sum::<u64>(),
+            width = width
+        ));
+        table
+    }
+
+    pub fn to_prometheus(&self) -> String {
+        let mut body = String::from(
+            "# HELP cluster_topic_tasks_total Tasks routed through the cluster host per topic\n\
+             # TYPE cluster_topic_tasks_total counter\n",
+        );
+        for (topic, count) in self.snapshot() {
+            body.push_str(&format!(
+                "cluster_topic_tasks_total{{topic=\"{}\"}} {}\n",
+                topic.replace('\\', "\\\\").replace('"', "\\\""),
+                count
+            ));
+        }
+        body
+    }
+}
+
+// Minimal HTTP endpoint serving `GET /metrics` in Prometheus text format and the agent
+// registry as JSON at `GET /cluster/state`
+pub async fn serve_metrics(metrics: TopicMetrics, port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    println!(
+        "📊 Metrics endpoint listening on http://0.0.0.0:{}/metrics",
+        port
+    );
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let n = match stream.read(&mut buf).await {
+                Ok(n) => n,
+                Err(_) => return,
+            };
+            let request = String::from_utf8_lossy(&buf[..n]);
+
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.to_prometheus();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else if request.starts_with("GET /cluster/state ") {
+                let body = crate::registry::state_json();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            };
+
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
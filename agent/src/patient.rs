@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// Line prefix used to carry the patient context inside analysis requests
+const CONTEXT_PREFIX: &str = "PATIENT CONTEXT: ";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PatientContext {
+    pub patient_id: String,
+    pub age: Option<u32>,
+    pub notes: Option<String>,
+}
+
+impl PatientContext {
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Patient {}", self.patient_id);
+        if let Some(age) = self.age {
+            summary.push_str(&format!(", age {}", age));
+        }
+        if let Some(notes) = &self.notes {
+            summary.push_str(&format!(", notes: {}", notes));
+        }
+        summary
+    }
+}
+
+// Active patient for this doctor session, shared by the tools and the GUI header
+static ACTIVE_PATIENT: Mutex<Option<PatientContext>> = Mutex::new(None);
+
+pub fn set_active(context: PatientContext) {
+    *ACTIVE_PATIENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(context);
+}
+
+pub fn clear() {
+    *ACTIVE_PATIENT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn active() -> Option<PatientContext> {
+    ACTIVE_PATIENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+// Prepend the active patient context (if any) to a query for the analysis agent
+pub fn attach(query: &str) -> String {
+    match active() {
+        Some(context) => format!("{}{}\n{}", CONTEXT_PREFIX, context.summary(), query),
+        None => query.to_string(),
+    }
+}
+
+// Patient id from a context line produced by `attach`, e.g. "Patient P-17, age 54" -> "P-17"
+pub fn id_from_summary(summary: &str) -> Option<&str> {
+    summary
+        .strip_prefix("Patient ")?
+        .split(',')
+        .next()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+}
+
+// Split a query into the attached patient context line and the query itself
+pub fn detach(query: &str) -> (Option<&str>, &str) {
+    query
+        .strip_prefix(CONTEXT_PREFIX)
+        .and_then(|rest| rest.split_once('\n'))
+        .map(|(context, body)| (Some(context), body))
+        .unwrap_or((None, query))
+}
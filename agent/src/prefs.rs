@@ -0,0 +1,264 @@
+// GUI preferences kept across sessions in a small JSON file in the user's config directory.
+// Missing or unreadable files fall back to the defaults.
+use crate::gui::ThemeMode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GuiPrefs {
+    pub zoom: f32,                       // Multiplier applied to every font size
+    pub theme: Option<ThemeMode>,        // Replaces --theme once toggled in the GUI
+    pub llm: Option<LlmSettings>,        // Set from the settings panel
+    pub quick_actions: Vec<QuickAction>, // Buttons above the message input, edited in the file
+}
+
+impl Default for GuiPrefs {
+    fn default() -> Self {
+        GuiPrefs {
+            zoom: 1.0,
+            theme: None,
+            llm: None,
+            quick_actions: default_quick_actions(),
+        }
+    }
+}
+
+// A common query one click away. Without auto_send the query is only put into the input
+// so it can be completed before sending.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuickAction {
+    pub label: String,
+    pub query: String,
+    #[serde(default)]
+    pub auto_send: bool,
+}
+
+fn default_quick_actions() -> Vec<QuickAction> {
+    let action = |label: &str, query: &str, auto_send| QuickAction {
+        label: label.to_string(),
+        query: query.to_string(),
+        auto_send,
+    };
+    vec![
+        action(
+            "Analyze current ECG",
+            "Analyze the patient's current ECG reading.",
+            true,
+        ),
+        action(
+            "Check patient room",
+            "Check the patient room camera: is the patient resting comfortably?",
+            true,
+        ),
+        action(
+            "Compare to last reading",
+            "Compare the latest ECG analysis to the previous report for this patient.",
+            true,
+        ),
+        action(
+            "Rhythm of file…",
+            "Classify the rhythm of the ECG file ",
+            false,
+        ),
+    ]
+}
+
+// Actions with a blank label or query can't do anything useful and get no button
+pub fn quick_actions(prefs: &GuiPrefs) -> Vec<QuickAction> {
+    prefs
+        .quick_actions
+        .iter()
+        .filter(|action| !action.label.trim().is_empty() && !action.query.trim().is_empty())
+        .cloned()
+        .collect()
+}
+
+// Highest temperature the providers accept
+pub const MAX_TEMPERATURE: f32 = 2.0;
+// Largest memory window offered in the GUI; larger windows mostly add token cost
+pub const MAX_MEMORY_WINDOW: usize = 200;
+
+// Model settings of the doctor agent, changed live from the settings panel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LlmSettings {
+    pub provider: String, // Saved settings only replace the defaults of this provider
+    pub model: String,
+    pub temperature: f32,
+    pub memory_window: usize,
+}
+
+impl LlmSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let model = self.model.trim();
+        if model.is_empty() {
+            return Err("the model name must not be empty".to_string());
+        }
+        if model.chars().any(char::is_whitespace) {
+            return Err(format!("model name '{}' must not contain spaces", model));
+        }
+        if !(0.0..=MAX_TEMPERATURE).contains(&self.temperature) {
+            return Err(format!(
+                "temperature must be between 0 and {}",
+                MAX_TEMPERATURE
+            ));
+        }
+        if !(1..=MAX_MEMORY_WINDOW).contains(&self.memory_window) {
+            return Err(format!(
+                "memory window must be between 1 and {}",
+                MAX_MEMORY_WINDOW
+            ));
+        }
+        Ok(())
+    }
+}
+
+// The saved settings when they were made for the same provider and are still valid,
+// otherwise the defaults. A memory window given on the command line wins over both.
+pub fn llm_settings(
+    prefs: &GuiPrefs,
+    defaults: LlmSettings,
+    memory_window: Option<usize>,
+) -> LlmSettings {
+    let mut settings = match &prefs.llm {
+        Some(saved) if saved.provider == defaults.provider && saved.validate().is_ok() => {
+            saved.clone()
+        }
+        _ => defaults,
+    };
+    if let Some(memory_window) = memory_window {
+        settings.memory_window = memory_window;
+    }
+    settings
+}
+
+// $XDG_CONFIG_HOME/liquidos/gui.json, falling back to ~/.config and then %APPDATA%
+fn prefs_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("liquidos").join("gui.json"))
+}
+
+pub fn load() -> GuiPrefs {
+    prefs_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Load, change and save, so each setting is saved without dropping the others
+pub fn update(change: impl FnOnce(&mut GuiPrefs)) {
+    let mut prefs = load();
+    change(&mut prefs);
+    save(&prefs);
+}
+
+fn save(prefs: &GuiPrefs) {
+    let Some(path) = prefs_path() else {
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| {
+            fs::write(
+                &path,
+                serde_json::to_string_pretty(prefs).unwrap_or_default(),
+            )
+        });
+    if let Err(e) = result {
+        eprintln!(
+            "⚠️ Could not save GUI preferences to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(provider: &str, model: &str) -> LlmSettings {
+        LlmSettings {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            temperature: 0.2,
+            memory_window: 50,
+        }
+    }
+
+    #[test]
+    fn saved_llm_settings_only_apply_to_their_provider() {
+        let defaults = settings("openai", "gpt-4o-mini");
+        let prefs = GuiPrefs {
+            llm: Some(settings("anthropic", "claude-3-5-sonnet-latest")),
+            ..Default::default()
+        };
+        assert_eq!(llm_settings(&prefs, defaults.clone(), None), defaults);
+
+        let prefs = GuiPrefs {
+            llm: Some(settings("openai", "gpt-4o")),
+            ..Default::default()
+        };
+        assert_eq!(llm_settings(&prefs, defaults.clone(), None).model, "gpt-4o");
+    }
+
+    #[test]
+    fn memory_window_from_the_command_line_wins_over_the_saved_one() {
+        let defaults = settings("openai", "gpt-4o-mini");
+        let prefs = GuiPrefs {
+            llm: Some(LlmSettings {
+                memory_window: 120,
+                ..settings("openai", "gpt-4o")
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            llm_settings(&prefs, defaults.clone(), None).memory_window,
+            120
+        );
+        let settings = llm_settings(&prefs, defaults, Some(20));
+        assert_eq!(settings.memory_window, 20);
+        assert_eq!(settings.model, "gpt-4o");
+    }
+
+    #[test]
+    fn quick_actions_come_from_the_file_and_skip_blank_entries() {
+        let prefs: GuiPrefs = serde_json::from_str(
+            r#"{"quick_actions": [
+                {"label": "Vitals", "query": "Summarize the latest vitals."},
+                {"label": " ", "query": "No button for this one", "auto_send": true}
+            ]}"#,
+        )
+        .unwrap();
+        let actions = quick_actions(&prefs);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].label, "Vitals");
+        assert!(!actions[0].auto_send);
+
+        // A file without the key gets the built-in buttons
+        let prefs: GuiPrefs = serde_json::from_str(r#"{"zoom": 1.2}"#).unwrap();
+        assert_eq!(quick_actions(&prefs), default_quick_actions());
+    }
+
+    #[test]
+    fn invalid_llm_settings_are_rejected() {
+        assert!(settings("openai", "gpt-4o").validate().is_ok());
+        assert!(settings("openai", "  ").validate().is_err());
+        assert!(settings("openai", "gpt 4o").validate().is_err());
+        let hot = LlmSettings {
+            temperature: 2.5,
+            ..settings("openai", "gpt-4o")
+        };
+        assert!(hot.validate().is_err());
+        let no_memory = LlmSettings {
+            memory_window: 0,
+            ..settings("openai", "gpt-4o")
+        };
+        assert!(no_memory.validate().is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Client nodes send a heartbeat this often; a kind silent for OFFLINE_AFTER is offline
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub const OFFLINE_AFTER: Duration = Duration::from_secs(15);
+
+// Nodes re-announce every minute; one that missed five announcements has left the cluster
+const FORGET_AFTER: Duration = Duration::from_secs(5 * 60);
+
+// How long a restarted host waits for the agents from its last run before warning. Nodes
+// re-announce every minute, so this covers one missed announcement.
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(90);
+
+// What a client node announces on agent_announce when it joins the cluster
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub name: String, // Node name, e.g. "camera"
+    #[serde(default)]
+    pub address: String, // The node's --bind host:port; empty from nodes that predate it
+    pub capabilities: Vec<String>, // e.g. ["camera", "image_upload"]
+    pub topics: Vec<String>, // Topics the node's agents subscribe to
+}
+
+impl Announcement {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn decode(payload: &str) -> Option<Self> {
+        serde_json::from_str(payload).ok()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} at {} (capabilities: {}; topics: {})",
+            self.name,
+            if self.address.is_empty() {
+                "unknown address"
+            } else {
+                &self.address
+            },
+            self.capabilities.join(", "),
+            self.topics.join(", ")
+        )
+    }
+}
+
+struct Registered {
+    announcement: Announcement,
+    seen: Instant, // Last announcement
+}
+
+// Capabilities announced in this process, keyed by node name. Kept by the cluster host for
+// visibility and by the doctor to check a capability before routing work to it.
+static REGISTRY: Mutex<BTreeMap<String, Registered>> = Mutex::new(BTreeMap::new());
+
+// Drop nodes that stopped announcing; returns true when any was dropped
+fn forget_silent(registry: &mut BTreeMap<String, Registered>, now: Instant) -> bool {
+    let before = registry.len();
+    registry.retain(|name, registered| {
+        let silent = now.duration_since(registered.seen) >= FORGET_AFTER;
+        if silent {
+            println!(
+                "🧭 Forgetting agent that stopped announcing itself: {}",
+                name
+            );
+        }
+        !silent
+    });
+    registry.len() != before
+}
+
+// Registered nodes that are still announcing themselves
+fn registered() -> Vec<Announcement> {
+    let (agents, forgot) = {
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        let forgot = forget_silent(&mut registry, Instant::now());
+        let agents = registry
+            .values()
+            .map(|registered| registered.announcement.clone())
+            .collect();
+        (agents, forgot)
+    };
+    if forgot {
+        save_state();
+    }
+    agents
+}
+
+// Agents restored from the state file that haven't announced themselves since the restart
+static AWAITING_RECONNECT: Mutex<BTreeMap<String, Announcement>> = Mutex::new(BTreeMap::new());
+
+// Where the cluster host keeps the registry across restarts; None keeps it in memory only
+static STATE_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set_state_file(path: Option<PathBuf>) {
+    *STATE_FILE.lock().unwrap_or_else(|e| e.into_inner()) = path;
+}
+
+// Record an announcement; returns true the first time a node registers or when it changed
+pub fn record(announcement: Announcement) -> bool {
+    let changed = {
+        let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = registry
+            .get(&announcement.name)
+            .is_none_or(|registered| registered.announcement != announcement);
+        registry.insert(
+            announcement.name.clone(),
+            Registered {
+                announcement: announcement.clone(),
+                seen: Instant::now(),
+            },
+        );
+        changed
+    };
+    let reconnected = AWAITING_RECONNECT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&announcement.name)
+        .is_some();
+    if reconnected {
+        println!(
+            "🧭 Agent from the last run reconnected: {}",
+            announcement.name
+        );
+    }
+    if changed {
+        save_state();
+    }
+    changed
+}
+
+// Record the payload of an agent_announce task, logging nodes that are new or changed
+pub fn record_payload(payload: &str) {
+    match Announcement::decode(payload) {
+        Some(announcement) => {
+            let summary = announcement.summary();
+            if record(announcement) {
+                println!("🧭 Agent registered: {}", summary);
+            }
+        }
+        None => println!("⚠️ Ignoring malformed agent announcement: {}", payload),
+    }
+}
+
+pub fn has_capability(capability: &str) -> bool {
+    registered()
+        .iter()
+        .any(|a| a.capabilities.iter().any(|c| c == capability))
+}
+
+// Last heartbeat per agent kind ("analysis", "camera"). Announcements only repeat every
+// minute, so heartbeats are what tells whether a capability is reachable right now. This is
+// the one liveness source: the doctor's tools, its redelivery and the GUI all read it.
+static LAST_HEARTBEAT: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+// When the doctor started listening for heartbeats; until one interval has passed, a kind
+// that hasn't sent one yet may just not have had its turn
+static LISTENING_SINCE: Mutex<Option<Instant>> = Mutex::new(None);
+
+pub fn start_listening() {
+    *LISTENING_SINCE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+}
+
+// Returns true when the kind came online: its first heartbeat, or the first after a gap
+pub fn record_heartbeat(kind: &str) -> bool {
+    let previous = LAST_HEARTBEAT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(kind.to_string(), Instant::now());
+    previous.is_none_or(|seen| seen.elapsed() >= OFFLINE_AFTER)
+}
+
+pub fn is_online(kind: &str) -> bool {
+    let last_heartbeat = LAST_HEARTBEAT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(kind)
+        .copied();
+    let listening_since = *LISTENING_SINCE.lock().unwrap_or_else(|e| e.into_inner());
+    online(last_heartbeat, listening_since, Instant::now())
+}
+
+fn online(last_heartbeat: Option<Instant>, listening_since: Option<Instant>, now: Instant) -> bool {
+    match last_heartbeat {
+        Some(seen) => now.duration_since(seen) < OFFLINE_AFTER,
+        // Room for one interval plus a late heartbeat
+        None => {
+            listening_since.is_some_and(|since| now.duration_since(since) < HEARTBEAT_INTERVAL * 2)
+        }
+    }
+}
+
+// Every known agent: registered ones plus those restored from the state file and still
+// expected, so a second restart before they reconnect doesn't forget them
+fn known_agents() -> Vec<Announcement> {
+    let mut agents = AWAITING_RECONNECT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    agents.extend(
+        REGISTRY
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, registered)| (name.clone(), registered.announcement.clone())),
+    );
+    agents.into_values().collect()
+}
+
+fn encode_state(agents: &[Announcement]) -> String {
+    serde_json::to_string_pretty(agents).unwrap_or_default()
+}
+
+fn decode_state(content: &str) -> Vec<Announcement> {
+    serde_json::from_str(content).unwrap_or_default()
+}
+
+fn save_state() {
+    let Some(path) = STATE_FILE.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return;
+    };
+    if let Err(e) = fs::write(&path, encode_state(&known_agents())) {
+        println!(
+            "⚠️ Could not save cluster state to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// Load the agents saved by the previous run; they are expected to reconnect. Returns their
+// names. A missing or unreadable file just means nothing is expected.
+pub fn restore_state() -> Vec<String> {
+    let Some(path) = STATE_FILE.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return Vec::new();
+    };
+    let saved = fs::read_to_string(&path)
+        .map(|content| decode_state(&content))
+        .unwrap_or_default();
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut awaiting = AWAITING_RECONNECT.lock().unwrap_or_else(|e| e.into_inner());
+    for announcement in saved {
+        if !registry.contains_key(&announcement.name) {
+            awaiting.insert(announcement.name.clone(), announcement);
+        }
+    }
+    awaiting.keys().cloned().collect()
+}
+
+pub fn awaiting_reconnect() -> Vec<String> {
+    AWAITING_RECONNECT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect()
+}
+
+// The grace period ran out: agents from the last run that still haven't reconnected are
+// forgotten, so the state file doesn't carry them into every later run. Returns their names.
+pub fn give_up_on_reconnects() -> Vec<String> {
+    let missing: Vec<String> =
+        std::mem::take(&mut *AWAITING_RECONNECT.lock().unwrap_or_else(|e| e.into_inner()))
+            .into_keys()
+            .collect();
+    if !missing.is_empty() {
+        save_state();
+    }
+    missing
+}
+
+// Registry as served at GET /cluster/state
+pub fn state_json() -> String {
+    let agents = registered();
+    serde_json::json!({
+        "agents": agents,
+        "awaiting_reconnect": awaiting_reconnect(),
+    })
+    .to_string()
+}
+
+pub fn summary() -> String {
+    let agents = registered();
+    if agents.is_empty() {
+        return "🧭 No agents registered yet".to_string();
+    }
+    let mut lines = vec!["🧭 Registered agents:".to_string()];
+    lines.extend(agents.iter().map(|a| format!("   {}", a.summary())));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_kinds_with_a_recent_heartbeat_are_online() {
+        assert!(record_heartbeat("registry_test_kind"));
+        assert!(!record_heartbeat("registry_test_kind"));
+        assert!(is_online("registry_test_kind"));
+
+        let now = Instant::now();
+        let ago = |secs| now - Duration::from_secs(secs);
+        assert!(online(Some(ago(5)), None, now));
+        assert!(!online(
+            Some(ago(OFFLINE_AFTER.as_secs())),
+            Some(ago(1)),
+            now
+        ));
+        // Not heard from yet: online until it had its chance to send a heartbeat
+        assert!(online(None, Some(ago(3)), now));
+        assert!(!online(
+            None,
+            Some(ago(HEARTBEAT_INTERVAL.as_secs() * 2)),
+            now
+        ));
+        assert!(!online(None, None, now));
+    }
+
+    #[test]
+    fn agents_that_stop_announcing_are_forgotten() {
+        let now = Instant::now();
+        let registered = |name: &str, seen| Registered {
+            announcement: Announcement {
+                name: name.to_string(),
+                address: String::new(),
+                capabilities: Vec::new(),
+                topics: Vec::new(),
+            },
+            seen,
+        };
+        let mut registry = BTreeMap::new();
+        registry.insert("camera".to_string(), registered("camera", now));
+        registry.insert(
+            "analysis".to_string(),
+            registered("analysis", now - FORGET_AFTER),
+        );
+        assert!(forget_silent(&mut registry, now));
+        assert_eq!(registry.keys().collect::<Vec<_>>(), vec!["camera"]);
+        assert!(!forget_silent(&mut registry, now));
+    }
+
+    #[test]
+    fn announcements_without_an_address_still_decode() {
+        let announcement =
+            Announcement::decode(r#"{"name":"camera","capabilities":["camera"],"topics":[]}"#)
+                .unwrap();
+        assert_eq!(announcement.address, "");
+        assert!(announcement.summary().contains("unknown address"));
+    }
+
+    #[test]
+    fn saved_state_round_trips_and_bad_files_mean_no_agents() {
+        let agents = vec![Announcement {
+            name: "camera".to_string(),
+            address: "10.0.0.13:9003".to_string(),
+            capabilities: vec!["camera".to_string()],
+            topics: vec!["camera_requests".to_string()],
+        }];
+        assert_eq!(decode_state(&encode_state(&agents)), agents);
+        assert!(decode_state("not json").is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+// Cheap local rhythm estimate from raw ECG samples: R-peak detection, R-R intervals, heart
+// rate and a coarse classification. No LLM involved; it is meant as a first look that the
+// doctor agent combines with the full analysis, not as a diagnosis.
+
+// Sampling rate assumed when the caller doesn't give one
+pub const DEFAULT_SAMPLE_RATE_HZ: f64 = 250.0;
+
+// Shortest plausible gap between two beats (300 bpm); peaks closer than this are one beat
+const REFRACTORY_SECS: f64 = 0.2;
+// A peak must rise this far from the mean towards the maximum
+const PEAK_THRESHOLD: f64 = 0.5;
+// R-R coefficient of variation above which the rhythm counts as irregular
+const IRREGULAR_RR_VARIATION: f64 = 0.15;
+
+const BRADYCARDIA_BELOW_BPM: f64 = 60.0;
+const TACHYCARDIA_ABOVE_BPM: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateClass {
+    Bradycardia,
+    Normal,
+    Tachycardia,
+}
+
+impl RateClass {
+    fn from_bpm(bpm: f64) -> Self {
+        if bpm < BRADYCARDIA_BELOW_BPM {
+            RateClass::Bradycardia
+        } else if bpm > TACHYCARDIA_ABOVE_BPM {
+            RateClass::Tachycardia
+        } else {
+            RateClass::Normal
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RateClass::Bradycardia => "bradycardia",
+            RateClass::Normal => "normal",
+            RateClass::Tachycardia => "tachycardia",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RhythmSummary {
+    pub beats: usize,
+    pub duration_secs: f64,
+    pub heart_rate_bpm: f64,
+    pub mean_rr_ms: f64,
+    pub rr_variation: f64, // Standard deviation of the R-R intervals divided by their mean
+    pub rate: RateClass,
+    pub regular: bool,
+}
+
+impl RhythmSummary {
+    pub fn describe(&self) -> String {
+        format!(
+            "Heart rate {:.0} bpm ({}), {} rhythm (mean R-R {:.0} ms, variation {:.0}%). {} beats detected over {:.1} s. Local heuristic estimate, not a diagnosis.",
+            self.heart_rate_bpm,
+            self.rate.label(),
+            if self.regular { "regular" } else { "irregular" },
+            self.mean_rr_ms,
+            self.rr_variation * 100.0,
+            self.beats,
+            self.duration_secs
+        )
+    }
+}
+
+// Samples as numbers separated by whitespace, commas or semicolons
+pub fn parse_samples(content: &str) -> Result<Vec<f64>, String> {
+    content
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a numeric sample", value))
+        })
+        .collect()
+}
+
+// Indices of the R peaks: the highest sample of each excursion above the threshold, with at
+// most one peak per refractory window
+fn detect_peaks(samples: &[f64], sample_rate_hz: f64) -> Vec<usize> {
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let max = samples.iter().copied().fold(f64::MIN, f64::max);
+    let threshold = mean + PEAK_THRESHOLD * (max - mean);
+    let refractory = ((REFRACTORY_SECS * sample_rate_hz) as usize).max(1);
+
+    let mut peaks = Vec::new();
+    let mut i = 0;
+    while i < samples.len() {
+        if samples[i] >= threshold && samples[i] > mean {
+            let end = (i + refractory).min(samples.len());
+            let peak = (i..end)
+                .max_by(|&a, &b| samples[a].total_cmp(&samples[b]))
+                .unwrap_or(i);
+            peaks.push(peak);
+            i = peak + refractory;
+        } else {
+            i += 1;
+        }
+    }
+    peaks
+}
+
+pub fn classify(samples: &[f64], sample_rate_hz: f64) -> Result<RhythmSummary, String> {
+    if sample_rate_hz.is_nan() || sample_rate_hz <= 0.0 {
+        return Err("the sampling rate must be positive".to_string());
+    }
+    if samples.is_empty() {
+        return Err("the file contains no samples".to_string());
+    }
+
+    let peaks = detect_peaks(samples, sample_rate_hz);
+    // Two intervals are the least that says anything about regularity
+    if peaks.len() < 3 {
+        return Err(format!(
+            "only {} beat(s) detected; need at least 3 to estimate the rhythm",
+            peaks.len()
+        ));
+    }
+
+    let rr_secs: Vec<f64> = peaks
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]) as f64 / sample_rate_hz)
+        .collect();
+    let mean_rr = rr_secs.iter().sum::<f64>() / rr_secs.len() as f64;
+    let variance =
+        rr_secs.iter().map(|rr| (rr - mean_rr).powi(2)).sum::<f64>() / rr_secs.len() as f64;
+    let rr_variation = variance.sqrt() / mean_rr;
+    let heart_rate_bpm = 60.0 / mean_rr;
+
+    Ok(RhythmSummary {
+        beats: peaks.len(),
+        duration_secs: samples.len() as f64 / sample_rate_hz,
+        heart_rate_bpm,
+        mean_rr_ms: mean_rr * 1000.0,
+        rr_variation,
+        rate: RateClass::from_bpm(heart_rate_bpm),
+        regular: rr_variation <= IRREGULAR_RR_VARIATION,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Flat baseline with a narrow R wave at each given sample index
+    fn synthetic_ecg(beats_at: &[usize], len: usize) -> Vec<f64> {
+        let mut samples = vec![0.05; len];
+        for &beat in beats_at {
+            samples[beat] = 1.0;
+            samples[beat + 1] = 0.6;
+            if beat > 0 {
+                samples[beat - 1] = 0.4;
+            }
+        }
+        samples
+    }
+
+    fn evenly_spaced(every: usize, len: usize) -> Vec<usize> {
+        (every / 2..len - 2).step_by(every).collect()
+    }
+
+    #[test]
+    fn steady_beats_classify_by_rate() {
+        let normal = synthetic_ecg(&evenly_spaced(200, 2500), 2500); // 0.8 s -> 75 bpm
+        let summary = classify(&normal, 250.0).unwrap();
+        assert_eq!(summary.rate, RateClass::Normal);
+        assert!(summary.regular);
+        assert!((summary.heart_rate_bpm - 75.0).abs() < 0.5);
+
+        let fast = synthetic_ecg(&evenly_spaced(100, 2500), 2500); // 150 bpm
+        assert_eq!(classify(&fast, 250.0).unwrap().rate, RateClass::Tachycardia);
+
+        let slow = synthetic_ecg(&evenly_spaced(375, 2500), 2500); // 40 bpm
+        assert_eq!(classify(&slow, 250.0).unwrap().rate, RateClass::Bradycardia);
+    }
+
+    #[test]
+    fn alternating_intervals_are_irregular() {
+        let beats = [50, 200, 500, 650, 950, 1100, 1400];
+        let summary = classify(&synthetic_ecg(&beats, 1500), 250.0).unwrap();
+        assert!(!summary.regular);
+        assert!(summary.describe().contains("irregular"));
+    }
+
+    #[test]
+    fn samples_parse_from_mixed_separators() {
+        assert_eq!(
+            parse_samples("0.1, 0.2;0.3\n-0.4 5").unwrap(),
+            vec![0.1, 0.2, 0.3, -0.4, 5.0]
+        );
+        assert!(parse_samples("0.1, abc").is_err());
+        assert!(classify(&[0.0; 100], 250.0).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+// Shared lifecycle for the cluster client nodes: create the runtime, start the shared
+// `handle_events` loop, run the environment, publish heartbeats and announcements, and stop
+// cleanly on Ctrl+C. It doesn't build agents: their types, memories and topics differ per node,
+// so each run_* function registers its own with AgentBuilder on `runtime()` between `new` and
+// `start`. Event routing is the same for every node except the analysis node, which switches it
+// with `analysis_routing`; there is no per-node event callback.
+use crate::agents::{AgentResponse, handle_events, spawn_announcer, spawn_heartbeat};
+use crate::registry::Announcement;
+use crate::usage;
+use autoagents::core::environment::Environment;
+use autoagents::core::error::Error;
+use autoagents::core::protocol::Event;
+use autoagents::core::runtime::ClusterClientRuntime;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+// Shared secret every node of one cluster presents; a node with another cookie can't join
+pub const DEFAULT_CLUSTER_COOKIE: &str = "cluster-cookie";
+static CLUSTER_COOKIE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_cluster_cookie(cookie: String) {
+    *CLUSTER_COOKIE.lock().unwrap_or_else(|e| e.into_inner()) = Some(cookie);
+}
+
+pub fn cluster_cookie() -> String {
+    CLUSTER_COOKIE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_CLUSTER_COOKIE.to_string())
+}
+
+// Where a client node listens and which cluster host it joins
+#[derive(Debug, Clone)]
+pub struct ClusterNode {
+    pub node_name: String,
+    pub port: u16,
+    pub host_addr: String,
+    pub bind: String, // Address the node listens on, e.g. 0.0.0.0 in a container
+}
+
+impl ClusterNode {
+    // Catch address typos here with a clear message rather than deep inside the runtime
+    pub fn validate(&self) -> Result<(), Error> {
+        parse_host_addr(&self.host_addr).map_err(Error::CustomError)?;
+        validate_host(&self.bind).map_err(Error::CustomError)?;
+        if self.port == 0 {
+            return Err(Error::CustomError(
+                "port must be between 1 and 65535, got 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Split "host:port" into its parts. IPv6 hosts must be bracketed, e.g. "[::1]:9000".
+pub fn parse_host_addr(value: &str) -> Result<(String, u16), String> {
+    let invalid = || format!("host-addr must be in host:port form, got '{}'", value);
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok((addr.ip().to_string(), addr.port()));
+    }
+    let (host, port) = value.rsplit_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || host.contains(':') || host.chars().any(char::is_whitespace) {
+        return Err(invalid());
+    }
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(invalid)?;
+    Ok((host.to_string(), port))
+}
+
+// --bind is a bare hostname or IP; the port comes from --port
+pub fn validate_host(host: &str) -> Result<(), String> {
+    let has_port = host.contains(':') && host.parse::<IpAddr>().is_err();
+    if host.is_empty() || host.chars().any(char::is_whitespace) || has_port {
+        return Err(format!(
+            "host must be a hostname or IP address without a port, got '{}'",
+            host
+        ));
+    }
+    Ok(())
+}
+
+pub struct AgentRunner {
+    label: &'static str, // Prefix for log lines, e.g. "📷 CameraAgent"
+    runtime: Arc<ClusterClientRuntime>,
+    host_addr: String,
+    heartbeat_kind: Option<&'static str>,
+    announcement: Announcement, // Published on agent_announce when the node starts
+    response_tx: Option<mpsc::UnboundedSender<AgentResponse>>,
+    reasoning_tx: Option<mpsc::UnboundedSender<String>>,
+    is_analysis_agent: bool,
+    started: AtomicBool,
+}
+
+// An environment hands out its event stream only once. A second take (a retried start, or two
+// handlers on one environment) gets a clear error instead of the bare environment error.
+pub(crate) async fn take_event_stream(
+    environment: &mut Environment,
+    label: &str,
+) -> Result<ReceiverStream<Event>, Error> {
+    environment.take_event_receiver(None).await.map_err(|e| {
+        Error::CustomError(format!(
+            "{} event receiver was already taken; events stay with the first handler ({})",
+            label, e
+        ))
+    })
+}
+
+impl AgentRunner {
+    pub fn new(label: &'static str, client_name: &str, node: ClusterNode) -> Result<Self, Error> {
+        node.validate()?;
+        let node_name = node.node_name.clone();
+        println!(
+            "{} initializing cluster client on port {}",
+            label, node.port
+        );
+
+        // The runtime connects to the dedicated cluster host on its own
+        let address = format!("{}:{}", node.bind, node.port);
+        let runtime = ClusterClientRuntime::new(
+            client_name.to_string(),
+            node.host_addr.clone(),
+            node.node_name,
+            cluster_cookie(),
+            node.port,
+            node.bind,
+        );
+
+        Ok(Self {
+            label,
+            runtime,
+            host_addr: node.host_addr,
+            heartbeat_kind: None,
+            announcement: Announcement {
+                name: node_name,
+                address,
+                capabilities: Vec::new(),
+                topics: Vec::new(),
+            },
+            response_tx: None,
+            reasoning_tx: None,
+            is_analysis_agent: false,
+            started: AtomicBool::new(false),
+        })
+    }
+
+    // Runtime to register this node's agents on before calling `start`
+    pub fn runtime(&self) -> Arc<ClusterClientRuntime> {
+        self.runtime.clone()
+    }
+
+    // Announce the node as `kind` on the heartbeat topic once started
+    pub fn heartbeat(mut self, kind: &'static str) -> Self {
+        self.heartbeat_kind = Some(kind);
+        self
+    }
+
+    // Advertise what this node can do on agent_announce once started
+    pub fn announce(mut self, capabilities: &[&str], topics: &[&str]) -> Self {
+        self.announcement.capabilities = capabilities.iter().map(|c| c.to_string()).collect();
+        self.announcement.topics = topics.iter().map(|t| crate::topics::name(t)).collect();
+        self
+    }
+
+    // Send results the event handler routes to the GUI on this channel
+    pub fn forward_responses(mut self, response_tx: mpsc::UnboundedSender<AgentResponse>) -> Self {
+        self.response_tx = Some(response_tx);
+        self
+    }
+
+    // Send ReAct tool steps on this channel
+    pub fn forward_reasoning(mut self, reasoning_tx: mpsc::UnboundedSender<String>) -> Self {
+        self.reasoning_tx = Some(reasoning_tx);
+        self
+    }
+
+    // Route events the way the analysis node needs (never forward its own results)
+    pub fn analysis_routing(mut self) -> Self {
+        self.is_analysis_agent = true;
+        self
+    }
+
+    // Register the runtime, start handling events and run the environment in the background.
+    // Calling it again is a no-op so a retry can't register the runtime or its handler twice.
+    pub async fn start(&self) -> Result<(), Error> {
+        if self.started.swap(true, Ordering::SeqCst) {
+            println!("⚠️ {} already started, ignoring repeated start", self.label);
+            return Ok(());
+        }
+
+        let mut environment = Environment::new(None);
+        let _ = environment.register_runtime(self.runtime.clone()).await;
+
+        let receiver = match take_event_stream(&mut environment, self.label).await {
+            Ok(receiver) => receiver,
+            Err(e) => {
+                self.started.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+        let response_tx = self
+            .response_tx
+            .clone()
+            .unwrap_or_else(|| mpsc::unbounded_channel::<AgentResponse>().0);
+        println!("{} setting up event handler...", self.label);
+        handle_events(
+            receiver,
+            response_tx,
+            self.runtime.clone(),
+            self.is_analysis_agent,
+            None,
+            self.reasoning_tx.clone(),
+        );
+
+        tokio::spawn(async move {
+            if let Err(e) = environment.run().await {
+                eprintln!("Environment error: {}", e);
+            }
+        });
+
+        println!(
+            "🌐 ClusterClientRuntime will connect to cluster host at {}",
+            self.host_addr
+        );
+
+        if let Some(kind) = self.heartbeat_kind {
+            spawn_heartbeat(self.runtime.clone(), kind);
+        }
+        spawn_announcer(self.runtime.clone(), self.announcement.clone());
+        usage::spawn_reporter();
+        Ok(())
+    }
+
+    // Block until Ctrl+C, then stop the runtime
+    pub async fn wait_for_shutdown(self) -> Result<(), Error> {
+        self.run_until(std::future::pending()).await
+    }
+
+    // Block until Ctrl+C or until `stop` completes (e.g. the GUI window closed), then stop
+    // the runtime
+    pub async fn run_until(self, stop: impl Future<Output = ()>) -> Result<(), Error> {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.expect("Failed to listen for Ctrl+C"),
+            _ = stop => {}
+        }
+        println!("{} shutting down...", self.label);
+        usage::print_final_summary();
+        if let Err(e) = self.runtime.stop().await {
+            eprintln!("Error stopping runtime: {}", e);
+        }
+        Ok(())
+    }
+}
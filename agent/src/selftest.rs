@@ -0,0 +1,179 @@
+// Pre-demo environment checks: LLM key and connectivity, a camera capture tool and free
+// cluster ports. Prints a checklist and reports whether every critical check passed.
+use crate::usage;
+use autoagents::llm::LLMProvider;
+use autoagents::llm::chat::{ChatMessage, ChatRole, MessageType};
+use colored::*;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::time::{Duration, timeout};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Capture tools tried by the camera agent, in the same order
+pub(crate) const CAMERA_TOOLS: [&str; 2] = ["imagesnap", "ffmpeg"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub critical: bool, // A failed critical check makes the self-test fail
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, critical: bool, result: Result<String, String>) -> Self {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        Check {
+            name: name.into(),
+            passed,
+            critical,
+            detail,
+        }
+    }
+
+    fn line(&self) -> String {
+        let text = format!("{}: {}", self.name, self.detail);
+        match (self.passed, self.critical) {
+            (true, _) => format!("✅ {}", text.green()),
+            (false, true) => format!("❌ {}", text.red()),
+            (false, false) => format!("⚠️ {}", text.yellow()),
+        }
+    }
+}
+
+// Run every check; `llm` carries the key validation error when no provider could be built
+pub async fn run(
+    llm: Result<Arc<dyn LLMProvider>, String>,
+    key_var: &str,
+    host: &str,
+    ports: &[u16],
+) -> bool {
+    println!("🩺 Running self-test");
+    let mut checks = Vec::new();
+
+    match llm {
+        Ok(llm) => {
+            checks.push(Check::new(key_var, true, Ok("present".to_string())));
+            checks.push(Check::new("LLM ping", true, ping_llm(&llm).await));
+        }
+        Err(e) => {
+            checks.push(Check::new(key_var, true, Err(e)));
+            checks.push(Check::new(
+                "LLM ping",
+                true,
+                Err("skipped, no usable API key".to_string()),
+            ));
+        }
+    }
+
+    // Either tool is enough for the camera agent, so only a missing pair is critical
+    let tools: Vec<(&str, Option<PathBuf>)> = CAMERA_TOOLS
+        .iter()
+        .map(|tool| (*tool, find_command(tool)))
+        .collect();
+    for (tool, path) in &tools {
+        checks.push(Check::new(
+            *tool,
+            false,
+            path.as_ref()
+                .map(|p| p.display().to_string())
+                .ok_or_else(|| "not found on PATH".to_string()),
+        ));
+    }
+    checks.push(Check::new(
+        "camera capture",
+        true,
+        if tools.iter().any(|(_, path)| path.is_some()) {
+            Ok("a capture tool is available".to_string())
+        } else {
+            Err(format!("install {}", CAMERA_TOOLS.join(" or ")))
+        },
+    ));
+
+    for port in ports {
+        checks.push(Check::new(
+            format!("port {}", port),
+            true,
+            check_port(host, *port),
+        ));
+    }
+
+    print_checklist(&checks)
+}
+
+// Print the checklist; returns true when no critical check failed
+pub fn print_checklist(checks: &[Check]) -> bool {
+    for check in checks {
+        println!("   {}", check.line());
+    }
+    let failed = checks.iter().filter(|c| c.critical && !c.passed).count();
+    if failed == 0 {
+        println!("{}", "✅ Self-test passed".green());
+    } else {
+        println!(
+            "{}",
+            format!("❌ Self-test failed: {} critical check(s)", failed).red()
+        );
+    }
+    failed == 0
+}
+
+async fn ping_llm(llm: &Arc<dyn LLMProvider>) -> Result<String, String> {
+    let messages = vec![ChatMessage {
+        role: ChatRole::User,
+        message_type: MessageType::Text,
+        content: "Reply with the single word OK.".to_string(),
+    }];
+    match timeout(PING_TIMEOUT, llm.chat(&messages, None, None)).await {
+        Ok(Ok(response)) => {
+            usage::record("self_test", response.as_ref());
+            Ok("the provider answered".to_string())
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no answer within {}s", PING_TIMEOUT.as_secs())),
+    }
+}
+
+// Look an executable up on PATH, the way Command::new would find it
+pub(crate) fn find_command(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+fn check_port(host: &str, port: u16) -> Result<String, String> {
+    TcpListener::bind((host, port))
+        .map(|_| format!("free on {}", host))
+        .map_err(|e| format!("cannot bind {}:{} ({})", host, port, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_port_in_use_is_reported() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(check_port("127.0.0.1", port).is_err());
+        drop(listener);
+        assert!(check_port("127.0.0.1", port).is_ok());
+    }
+
+    #[test]
+    fn only_critical_failures_fail_the_self_test() {
+        let warning = Check::new("imagesnap", false, Err("not found on PATH".to_string()));
+        let passed = Check::new("port 9000", true, Ok("free".to_string()));
+        assert!(print_checklist(&[warning.clone(), passed]));
+
+        let failed = Check::new("OPENAI_API_KEY", true, Err("not set".to_string()));
+        assert!(!print_checklist(&[warning, failed]));
+        assert!(find_command("definitely-not-an-installed-tool").is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::correlation::{self, CorrelationId};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable output (default)
+    Pretty,
+    /// One JSON object per line on stderr for log pipelines
+    Json,
+}
+
+static JSON_LOGS: AtomicBool = AtomicBool::new(false);
+static NODE_NAME: OnceLock<String> = OnceLock::new();
+
+// Log level for the number of -v flags: none = info, -v = debug, -vv (or more) = trace
+fn level_for_verbosity(verbose: u8) -> tracing::Level {
+    match verbose {
+        0 => tracing::Level::INFO,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    }
+}
+
+pub fn init(format: LogFormat, node: &str, verbose: u8) {
+    let _ = NODE_NAME.set(node.to_string());
+    let level = level_for_verbosity(verbose);
+
+    match format {
+        LogFormat::Pretty => {
+            // An explicit RUST_LOG still wins over the -v default
+            env_logger::Builder::from_env(
+                env_logger::Env::default().default_filter_or(level.as_str()),
+            )
+            .init();
+        }
+        LogFormat::Json => {
+            JSON_LOGS.store(true, Ordering::Relaxed);
+            tracing_subscriber::fmt()
+                .with_max_level(level)
+                .json()
+                .flatten_event(true)
+                .with_writer(std::io::stderr)
+                .init();
+        }
+    }
+}
+
+// Emit a key lifecycle event (task received, tool called, publish, analysis complete) as
+// structured fields. A no-op in pretty mode, where the existing console output is used instead.
+pub fn lifecycle(
+    event: &str,
+    topic: &str,
+    correlation_id: Option<CorrelationId>,
+    latency_ms: Option<u128>,
+) {
+    if !JSON_LOGS.load(Ordering::Relaxed) {
+        return;
+    }
+
+    tracing::info!(
+        event,
+        topic,
+        correlation_id = %correlation::display(correlation_id),
+        node = NODE_NAME.get().map(String::as_str).unwrap_or("-"),
+        latency_ms = latency_ms.map(|ms| ms as u64),
+        "lifecycle"
+    );
+}
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use autoagents::llm::LLMProvider;
+use autoagents::llm::ToolCall;
+use autoagents::llm::chat::{
+    ChatMessage, ChatProvider, ChatResponse, StructuredOutputFormat, Tool,
+};
+use autoagents::llm::completion::{CompletionProvider, CompletionRequest, CompletionResponse};
+use autoagents::llm::embedding::EmbeddingProvider;
+use autoagents::llm::error::LLMError;
+use autoagents::llm::models::ModelsProvider;
+use std::fmt;
+
+// LLM stand-in that answers every chat with a fixed reply, so agents run without an API key
+#[derive(Debug, Clone)]
+pub struct MockLLM {
+    reply: String,
+}
+
+impl MockLLM {
+    pub fn new(reply: &str) -> Self {
+        Self {
+            reply: reply.to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockResponse(String);
+
+impl fmt::Display for MockResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl ChatResponse for MockResponse {
+    fn text(&self) -> Option<String> {
+        Some(self.0.clone())
+    }
+
+    fn tool_calls(&self) -> Option<Vec<ToolCall>> {
+        None
+    }
+}
+
+#[async_trait]
+impl ChatProvider for MockLLM {
+    async fn chat(
+        &self,
+        _messages: &[ChatMessage],
+        _tools: Option<&[Tool]>,
+        _json_schema: Option<StructuredOutputFormat>,
+    ) -> Result<Box<dyn ChatResponse>, LLMError> {
+        Ok(Box::new(MockResponse(self.reply.clone())))
+    }
+}
+
+#[async_trait]
+impl CompletionProvider for MockLLM {
+    async fn complete(
+        &self,
+        _req: &CompletionRequest,
+        _json_schema: Option<StructuredOutputFormat>,
+    ) -> Result<CompletionResponse, LLMError> {
+        Ok(CompletionResponse {
+            text: self.reply.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MockLLM {
+    async fn embed(&self, input: Vec<String>) -> Result<Vec<Vec<f32>>, LLMError> {
+        Ok(input.iter().map(|_| Vec::new()).collect())
+    }
+}
+
+impl ModelsProvider for MockLLM {}
+
+impl LLMProvider for MockLLM {}
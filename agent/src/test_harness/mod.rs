@@ -0,0 +1,233 @@
+// In-process cluster harness: runs the host and agents on loopback ports with a mock LLM
+mod mock_llm;
+
+use crate::agents::{self, DoctorChannels, DoctorSettings};
+use crate::runner;
+use crate::topics;
+use autoagents::core::agent::task::Task;
+use autoagents::core::environment::Environment;
+use autoagents::core::error::Error;
+use autoagents::core::runtime::{ClusterClientRuntime, TypedRuntime};
+use mock_llm::MockLLM;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, sleep, timeout};
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MOCK_REPORT: &str = "### ECG Analysis Report\nKey Insights: normal sinus rhythm at 72 bpm.";
+
+// A running cluster; every node is aborted when the harness is dropped
+struct Cluster {
+    nodes: Vec<JoinHandle<()>>,
+    user_tx: mpsc::Sender<String>,
+    response_rx: mpsc::Receiver<agents::AgentResponse>,
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            node.abort();
+        }
+    }
+}
+
+// Start host, analysis and doctor nodes on consecutive ports starting at `base_port`
+async fn start_cluster(base_port: u16, doctor_reply: &str) -> Cluster {
+    let host = "127.0.0.1".to_string();
+    let host_addr = format!("{}:{}", host, base_port);
+    let mut nodes = Vec::new();
+
+    let host_clone = host.clone();
+    nodes.push(tokio::spawn(async move {
+        let _ =
+            agents::run_cluster_host("test_host".to_string(), base_port, host_clone, None).await;
+    }));
+    sleep(Duration::from_secs(1)).await;
+
+    let (host_clone, host_addr_clone) = (host.clone(), host_addr.clone());
+    nodes.push(tokio::spawn(async move {
+        let _ = agents::run_analysis_agent(
+            Arc::new(MockLLM::new(MOCK_REPORT)),
+            runner::ClusterNode {
+                node_name: "test_analysis".to_string(),
+                port: base_port + 1,
+                host_addr: host_addr_clone,
+                bind: host_clone,
+            },
+            10,
+            false,
+            1,
+            agents::DEFAULT_TASK_TIMEOUT,
+        )
+        .await;
+    }));
+
+    let (user_tx, user_rx) = mpsc::channel::<String>(256);
+    let (response_tx, response_rx) = mpsc::channel::<agents::AgentResponse>(256);
+    let (ack_tx, _ack_rx) = mpsc::unbounded_channel::<String>();
+    let (_cancel_tx, cancel_rx) = mpsc::unbounded_channel::<()>();
+    let (queue_tx, _queue_rx) = mpsc::unbounded_channel::<agents::QueueStatus>();
+    let (reasoning_tx, _reasoning_rx) = mpsc::unbounded_channel::<String>();
+    let (_upload_tx, upload_rx) = mpsc::unbounded_channel::<agents::UploadedImage>();
+    let doctor_llm = Arc::new(MockLLM::new(doctor_reply));
+    nodes.push(tokio::spawn(async move {
+        let _ = agents::run_doctor_agent(
+            doctor_llm,
+            runner::ClusterNode {
+                node_name: "test_doctor".to_string(),
+                port: base_port + 2,
+                host_addr,
+                bind: host,
+            },
+            DoctorChannels {
+                user_rx,
+                response_tx,
+                ack_tx,
+                cancel_rx,
+                camera_confirm_tx: None,
+                queue_tx,
+                reasoning_tx,
+                upload_rx,
+            },
+            DoctorSettings {
+                memory_window: 50,
+                confirm_camera: false,
+                rate_limit_per_min: 20,
+                max_query_chars: 4000,
+                channel_capacity: 256,
+                max_tool_calls: 4,
+                max_turns: 10,
+                query_timeout: agents::DEFAULT_QUERY_TIMEOUT,
+                history: Vec::new(),
+            },
+        )
+        .await;
+    }));
+
+    // Give the clients time to connect and subscribe
+    sleep(Duration::from_secs(3)).await;
+
+    Cluster {
+        nodes,
+        user_tx,
+        response_rx,
+    }
+}
+
+// Join the cluster as a bare client node and publish a single task on `topic`
+async fn publish_probe(
+    host_addr: String,
+    port: u16,
+    topic: &str,
+    prompt: &str,
+) -> Result<(), Error> {
+    let runtime = ClusterClientRuntime::new(
+        "probe_client".to_string(),
+        host_addr,
+        "test_probe".to_string(),
+        runner::cluster_cookie(),
+        port,
+        "127.0.0.1".to_string(),
+    );
+
+    let mut environment = Environment::new(None);
+    let _ = environment.register_runtime(runtime.clone()).await;
+    tokio::spawn(async move {
+        let _ = environment.run().await;
+    });
+    sleep(Duration::from_secs(2)).await;
+
+    runtime
+        .publish(&topics::topic(topic), Task::new(prompt.to_string()))
+        .await?;
+    Ok(())
+}
+
+async fn next_response(cluster: &mut Cluster) -> Option<String> {
+    timeout(RESPONSE_TIMEOUT, cluster.response_rx.recv())
+        .await
+        .ok()
+        .flatten()
+        .map(|response| response.content)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_start_and_second_receiver_take_do_not_abort() {
+    let host_task = tokio::spawn(async move {
+        let _ = agents::run_cluster_host(
+            "test_host".to_string(),
+            19120,
+            "127.0.0.1".to_string(),
+            None,
+        )
+        .await;
+    });
+    sleep(Duration::from_secs(1)).await;
+
+    let node = runner::AgentRunner::new(
+        "🧪 TestRunner",
+        "double_start_client",
+        runner::ClusterNode {
+            node_name: "test_double_start".to_string(),
+            port: 19121,
+            host_addr: "127.0.0.1:19120".to_string(),
+            bind: "127.0.0.1".to_string(),
+        },
+    )
+    .unwrap();
+    assert!(node.start().await.is_ok());
+    assert!(node.start().await.is_ok(), "second start should be a no-op");
+
+    // Taking the receiver twice from one environment is an error, not a panic
+    let mut environment = Environment::new(None);
+    let _ = environment.register_runtime(node.runtime()).await;
+    let _first = runner::take_event_stream(&mut environment, "🧪 TestRunner").await;
+    let second = runner::take_event_stream(&mut environment, "🧪 TestRunner").await;
+    assert!(second.is_err());
+
+    host_task.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn user_query_reaches_doctor_and_reply_returns_to_gui() {
+    let mut cluster = start_cluster(19100, "The ECG looks normal.").await;
+
+    cluster
+        .user_tx
+        .send("USER_SEND:How is the patient's heart?".to_string())
+        .await
+        .unwrap();
+
+    let response = next_response(&mut cluster).await;
+    assert_eq!(response.as_deref(), Some("The ECG looks normal."));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn analysis_request_produces_report_on_analysis_response() {
+    let mut cluster = start_cluster(19110, "Forwarding to analysis.").await;
+
+    // Publish to analysis_agent the way the doctor's ecg_analysis_tool does
+    let probe = publish_probe(
+        "127.0.0.1:19110".to_string(),
+        19113,
+        topics::ANALYSIS_REQUESTS,
+        "Analyze the latest ECG",
+    )
+    .await;
+    assert!(probe.is_ok(), "probe publish failed: {:?}", probe.err());
+
+    // The doctor forwards analysis reports arriving on analysis_response straight to the GUI
+    let mut saw_report = false;
+    while let Some(response) = next_response(&mut cluster).await {
+        if response == MOCK_REPORT {
+            saw_report = true;
+            break;
+        }
+    }
+    assert!(
+        saw_report,
+        "no analysis report arrived on analysis_response"
+    );
+}
@@ -0,0 +1,102 @@
+// Operator overrides for the LLM-facing tool descriptions, loaded at startup from a JSON file
+// keyed by tool name, e.g. {"ecg_analysis_tool": "..."}. Tools without an entry keep their
+// compiled description.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+// Replacement descriptions by tool name
+#[derive(Debug, Default)]
+pub struct Overrides(BTreeMap<String, &'static str>);
+
+impl Overrides {
+    pub const fn new() -> Self {
+        Overrides(BTreeMap::new())
+    }
+
+    pub fn description(&self, tool: &str, compiled: &'static str) -> &'static str {
+        self.0.get(tool).copied().unwrap_or(compiled)
+    }
+}
+
+// The overrides in use, from load
+static OVERRIDES: Mutex<Overrides> = Mutex::new(Overrides::new());
+
+// Read overrides from `path`; names not in `known` are reported since they would never apply
+pub fn read(path: &Path, known: &[&str]) -> Result<Overrides, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read tool descriptions {}: {}", path.display(), e))?;
+    let parsed = parse(&content)
+        .map_err(|e| format!("invalid tool descriptions in {}: {}", path.display(), e))?;
+
+    let mut overrides = Overrides::new();
+    for (tool, description) in parsed {
+        if !known.contains(&tool.as_str()) {
+            eprintln!(
+                "⚠️ Ignoring description for unknown tool '{}' (known: {})",
+                tool,
+                known.join(", ")
+            );
+            continue;
+        }
+        println!(
+            "📝 Using the description from {} for {}",
+            path.display(),
+            tool
+        );
+        // Leaked since tools hand out &'static str; read once at startup
+        overrides
+            .0
+            .insert(tool, Box::leak(description.into_boxed_str()));
+    }
+    Ok(overrides)
+}
+
+// Read overrides from `path` and use them for the rest of the process
+pub fn load(path: &Path, known: &[&str]) -> Result<(), String> {
+    let overrides = read(path, known)?;
+    *current() = overrides;
+    Ok(())
+}
+
+fn parse(content: &str) -> Result<BTreeMap<String, String>, String> {
+    let overrides: BTreeMap<String, String> =
+        serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if let Some((tool, _)) = overrides.iter().find(|(_, d)| d.trim().is_empty()) {
+        return Err(format!("the description of {} is empty", tool));
+    }
+    Ok(overrides)
+}
+
+pub fn current() -> MutexGuard<'static, Overrides> {
+    OVERRIDES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_only_known_tools() {
+        let path = std::env::temp_dir().join(format!(
+            "liquidos-tool-descriptions-{}.json",
+            std::process::id()
+        ));
+        fs::write(
+            &path,
+            r#"{"tool_docs_test": "Operator text", "not_a_tool": "Never applied"}"#,
+        )
+        .unwrap();
+        let overrides = read(&path, &["tool_docs_test"]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            overrides.description("tool_docs_test", "Compiled"),
+            "Operator text"
+        );
+        assert_eq!(overrides.description("not_a_tool", "Compiled"), "Compiled");
+        assert!(parse(r#"{"tool_docs_test": "  "}"#).is_err());
+        assert!(parse("not json").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+// Every cluster topic name in one place. Publishers and subscribers must agree on these
+// exactly - a typo doesn't fail anywhere, the messages just never arrive.
+
+use autoagents::core::actor::Topic;
+use autoagents::core::agent::task::Task;
+use std::sync::Mutex;
+
+// Prefix applied to every topic on the wire (--cluster-namespace), so separate clusters on
+// one network don't see each other's messages. Empty means no prefix.
+static NAMESPACE: Mutex<String> = Mutex::new(String::new());
+
+pub fn set_namespace(namespace: &str) {
+    *NAMESPACE.lock().unwrap_or_else(|e| e.into_inner()) = namespace.to_string();
+}
+
+// Wire name of a topic, e.g. "ns1/analysis_agent" in namespace ns1
+pub fn name(topic: &str) -> String {
+    namespaced(&NAMESPACE.lock().unwrap_or_else(|e| e.into_inner()), topic)
+}
+
+fn namespaced(namespace: &str, topic: &str) -> String {
+    if namespace.is_empty() {
+        topic.to_string()
+    } else {
+        format!("{}/{}", namespace, topic)
+    }
+}
+
+// Every publish and subscription goes through here so the namespace is never missed
+pub fn topic(topic: &str) -> Topic<Task> {
+    Topic::<Task>::new(&name(topic))
+}
+
+// Doctor -> AnalysisAgent: ECG analysis requests
+pub const ANALYSIS_REQUESTS: &str = "analysis_agent";
+// AnalysisAgent -> Doctor: finished analysis reports
+pub const ANALYSIS_RESPONSE: &str = "analysis_response";
+// Doctor -> CameraAgent: camera capture requests
+pub const CAMERA_REQUESTS: &str = "camera_requests";
+// CameraAgent -> Doctor: camera analysis results
+pub const CAMERA_RESPONSE: &str = "camera_response";
+// GUI (via the doctor node) -> Doctor: user queries
+pub const USER_MESSAGES: &str = "user_messages";
+// Analysis/Camera -> Doctor roster: liveness heartbeats
+pub const HEARTBEAT: &str = "agent_heartbeat";
+
+// Every client node -> cluster host and doctor: node name, capabilities and topics on startup
+pub const AGENT_ANNOUNCE: &str = "agent_announce";
+
+// AnalysisAgent -> external integrations: reports as JSON (--emit-raw-json)
+pub const ANALYSIS_RESPONSE_JSON: &str = "analysis_response_json";
+
+// Doctor -> GUI (via the doctor node): the query the doctor just started working on
+pub const USER_ACK: &str = "user_ack";
+
+// GUI (via the doctor node) -> CameraAgent node: images uploaded by the doctor
+pub const UPLOADED_IMAGE: &str = "uploaded_image";
+
+// AnalysisAgent -> doctor GUI: PNG plot of the analyzed ECG samples (--render-waveform)
+pub const ANALYSIS_WAVEFORM: &str = "analysis_waveform";
+
+// AnalysisAgent -> Doctor: a re-sent request is still being analyzed, so it wasn't lost
+pub const ANALYSIS_PROGRESS: &str = "analysis_progress";
+
+// CameraAnalysisTool -> doctor GUI (via the doctor node): a capture waiting for the user's
+// approval (--confirm-camera)
+pub const CAMERA_CONFIRM: &str = "camera_confirm";
+
+// Topics some node publishes to
+pub const PUBLISHED: [&str; 13] = [
+    ANALYSIS_REQUESTS,
+    ANALYSIS_RESPONSE,
+    ANALYSIS_RESPONSE_JSON,
+    ANALYSIS_WAVEFORM,
+    ANALYSIS_PROGRESS,
+    CAMERA_REQUESTS,
+    CAMERA_RESPONSE,
+    CAMERA_CONFIRM,
+    USER_MESSAGES,
+    USER_ACK,
+    UPLOADED_IMAGE,
+    HEARTBEAT,
+    AGENT_ANNOUNCE,
+];
+
+// Topics some agent subscribes to
+pub const SUBSCRIBED: [&str; 12] = [
+    ANALYSIS_REQUESTS,
+    CAMERA_REQUESTS,
+    CAMERA_CONFIRM,
+    USER_MESSAGES,
+    USER_ACK,
+    ANALYSIS_RESPONSE,
+    CAMERA_RESPONSE,
+    ANALYSIS_WAVEFORM,
+    ANALYSIS_PROGRESS,
+    UPLOADED_IMAGE,
+    HEARTBEAT,
+    AGENT_ANNOUNCE,
+];
+
+// What the DoctorAgent itself subscribes to. run_doctor_agent subscribes, logs and announces
+// from this list, so they can't drift apart. Everything else on the doctor node (heartbeats,
+// announcements, acks, waveforms, analysis progress, camera confirmations) is handled by its
+// helper agents.
+pub const DOCTOR_AGENT_TOPICS: [&str; 3] = [USER_MESSAGES, ANALYSIS_RESPONSE, CAMERA_RESPONSE];
+
+// Topics only consumed by integrations outside this binary
+pub const EXTERNAL: [&str; 1] = [ANALYSIS_RESPONSE_JSON];
+
+// Fail the build if a topic is published without a subscriber or subscribed without a publisher
+const _: () = {
+    assert!(
+        first_unmatched(&PUBLISHED, &[&SUBSCRIBED, &EXTERNAL]).is_none(),
+        "published topic has no subscriber"
+    );
+    assert!(
+        first_unmatched(&SUBSCRIBED, &[&PUBLISHED]).is_none(),
+        "subscribed topic has no publisher"
+    );
+    assert!(
+        first_unmatched(&DOCTOR_AGENT_TOPICS, &[&SUBSCRIBED]).is_none(),
+        "doctor topic missing from SUBSCRIBED"
+    );
+};
+
+// The first of `topics` that appears in none of `sets`, e.g. a misspelled subscription
+const fn first_unmatched(topics: &[&'static str], sets: &[&[&str]]) -> Option<&'static str> {
+    let mut i = 0;
+    while i < topics.len() {
+        let mut found = false;
+        let mut j = 0;
+        while j < sets.len() {
+            found = found || contains(sets[j], topics[i]);
+            j += 1;
+        }
+        if !found {
+            return Some(topics[i]);
+        }
+        i += 1;
+    }
+    None
+}
+
+// True when `name` appears in `set`
+const fn contains(set: &[&str], name: &str) -> bool {
+    let mut i = 0;
+    while i < set.len() {
+        if str_eq(set[i], name) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_prefix_topic_names() {
+        assert_eq!(namespaced("", ANALYSIS_REQUESTS), "analysis_agent");
+        assert_eq!(namespaced("ns1", ANALYSIS_REQUESTS), "ns1/analysis_agent");
+        assert_eq!(namespaced("ward-3", HEARTBEAT), "ward-3/agent_heartbeat");
+    }
+
+    #[test]
+    fn a_topic_without_a_counterpart_is_reported() {
+        let published = ["analysis_agent", "camera_requests"];
+        let subscribed = ["analysis_agnet", "camera_requests"];
+        assert_eq!(
+            first_unmatched(&published, &[&subscribed]),
+            Some("analysis_agent")
+        );
+        assert_eq!(
+            first_unmatched(&subscribed, &[&published]),
+            Some("analysis_agnet")
+        );
+        // A topic consumed outside this binary counts as subscribed
+        assert_eq!(
+            first_unmatched(&published, &[&subscribed, &["analysis_agent"]]),
+            None
+        );
+    }
+}
@@ -0,0 +1,104 @@
+// Transport security policy for the cluster connections. Queries carry patient data, so a
+// cluster reachable from other hosts must not run in plaintext by accident.
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub struct TransportSecurity {
+    pub tls_cert: Option<PathBuf>, // Host certificate (PEM)
+    pub tls_key: Option<PathBuf>,  // Host private key (PEM)
+    pub tls_ca: Option<PathBuf>,   // CA bundle clients use to verify the host
+    pub insecure: bool,            // Allow plaintext across hosts
+}
+
+impl TransportSecurity {
+    fn tls_requested(&self) -> bool {
+        self.tls_cert.is_some() || self.tls_key.is_some() || self.tls_ca.is_some()
+    }
+
+    // Decide whether a node may start with the given addresses (bind host and, for clients,
+    // the cluster host address). Loopback-only demos are always allowed.
+    pub fn check(&self, addresses: &[&str]) -> Result<(), String> {
+        if self.tls_requested() {
+            for path in [&self.tls_cert, &self.tls_key, &self.tls_ca]
+                .into_iter()
+                .flatten()
+            {
+                require_file(path)?;
+            }
+            // ClusterHostRuntime/ClusterClientRuntime take no TLS configuration yet
+            return Err(
+                "TLS was requested, but the cluster runtime does not support TLS yet. Put the cluster port behind a TLS tunnel (e.g. stunnel or WireGuard) and start with --insecure"
+                    .to_string(),
+            );
+        }
+
+        let remote: Vec<&str> = addresses
+            .iter()
+            .copied()
+            .filter(|addr| !is_loopback(addr))
+            .collect();
+        if remote.is_empty() || self.insecure {
+            if !remote.is_empty() {
+                println!(
+                    "⚠️ Cluster traffic to {} is unencrypted (--insecure)",
+                    remote.join(", ")
+                );
+            }
+            return Ok(());
+        }
+
+        Err(format!(
+            "refusing to use {} without TLS: cluster traffic carries patient data. Pass --insecure to allow plaintext, e.g. inside a VPN",
+            remote.join(", ")
+        ))
+    }
+}
+
+fn require_file(path: &Path) -> Result<(), String> {
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(format!("TLS file {} does not exist", path.display()))
+    }
+}
+
+// True for "localhost" and loopback IPs, with or without a port
+fn is_loopback(addr: &str) -> bool {
+    let host = match addr.rsplit_once(':') {
+        // Bare IPv6 addresses contain colons too; only strip a trailing numeric port
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => host,
+        _ => addr,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_addresses_are_recognised() {
+        assert!(is_loopback("localhost"));
+        assert!(is_loopback("localhost:9000"));
+        assert!(is_loopback("127.0.0.1:9000"));
+        assert!(is_loopback("[::1]:9000"));
+        assert!(!is_loopback("0.0.0.0"));
+        assert!(!is_loopback("10.0.0.5:9000"));
+    }
+
+    #[test]
+    fn cross_host_needs_insecure_and_localhost_is_unaffected() {
+        let default = TransportSecurity::default();
+        assert!(default.check(&["localhost", "localhost:9000"]).is_ok());
+        assert!(default.check(&["10.0.0.2", "10.0.0.5:9000"]).is_err());
+
+        let insecure = TransportSecurity {
+            insecure: true,
+            ..Default::default()
+        };
+        assert!(insecure.check(&["10.0.0.2", "10.0.0.5:9000"]).is_ok());
+    }
+}
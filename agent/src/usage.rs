@@ -0,0 +1,203 @@
+// Token accounting for the agents' chat calls, the doctor's ReAct loop included. Usage is
+// accumulated per agent for the whole process (so `all` mode reports every node) and turned
+// into an estimated cost with the rate given by `--usd-per-1k-tokens`.
+use autoagents::llm::chat::ChatResponse;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio::time::{Duration, interval};
+
+pub const DEFAULT_USD_PER_1K_TOKENS: f64 = 0.002;
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AgentUsage {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl AgentUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn estimated_cost(&self, usd_per_1k_tokens: f64) -> f64 {
+        self.total_tokens() as f64 / 1000.0 * usd_per_1k_tokens
+    }
+}
+
+struct Ledger {
+    usd_per_1k_tokens: f64,
+    per_agent: BTreeMap<String, AgentUsage>,
+}
+
+fn ledger() -> &'static Mutex<Ledger> {
+    static LEDGER: OnceLock<Mutex<Ledger>> = OnceLock::new();
+    LEDGER.get_or_init(|| {
+        Mutex::new(Ledger {
+            usd_per_1k_tokens: DEFAULT_USD_PER_1K_TOKENS,
+            per_agent: BTreeMap::new(),
+        })
+    })
+}
+
+pub fn set_rate(usd_per_1k_tokens: f64) {
+    ledger()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .usd_per_1k_tokens = usd_per_1k_tokens;
+}
+
+// Add the usage reported by one chat response. Providers that don't report usage still count
+// as a call so the summary shows how many requests went out.
+pub fn record(agent: &str, response: &dyn ChatResponse) {
+    let (prompt, completion) = response
+        .usage()
+        .map(|u| (u.prompt_tokens as u64, u.completion_tokens as u64))
+        .unwrap_or((0, 0));
+    record_tokens(agent, prompt, completion);
+}
+
+pub fn record_tokens(agent: &str, prompt_tokens: u64, completion_tokens: u64) {
+    let mut ledger = ledger().lock().unwrap_or_else(|e| e.into_inner());
+    let usage = ledger.per_agent.entry(agent.to_string()).or_default();
+    usage.calls += 1;
+    usage.prompt_tokens += prompt_tokens;
+    usage.completion_tokens += completion_tokens;
+}
+
+pub fn session_total() -> AgentUsage {
+    let ledger = ledger().lock().unwrap_or_else(|e| e.into_inner());
+    ledger
+        .per_agent
+        .values()
+        .fold(AgentUsage::default(), |mut total, usage| {
+            total.calls += usage.calls;
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total
+        })
+}
+
+pub fn summary_table() -> String {
+    let (rate, per_agent) = {
+        let ledger = ledger().lock().unwrap_or_else(|e| e.into_inner());
+        (ledger.usd_per_1k_tokens, ledger.per_agent.clone())
+    };
+    format_summary(&per_agent, rate)
+}
+
+fn format_summary(per_agent: &BTreeMap<String, AgentUsage>, usd_per_1k_tokens: f64) -> String {
+    if per_agent.is_empty() {
+        return "💰 No LLM calls made yet".to_string();
+    }
+
+    let width = per_agent.keys().map(|a| a.len()).max().unwrap_or(0).max(5);
+    let mut table = format!(
+        "💰 {:<width$}  {:>6}  {:>10}  {:>10}  {:>10}\n",
+        "AGENT",
+        "CALLS",
+        "PROMPT",
+        "COMPLETION",
+        "EST. USD",
+        width = width
+    );
+    let mut total = AgentUsage::default();
+    for (agent, usage) in per_agent {
+        table.push_str(&format_row(agent, usage, usd_per_1k_tokens, width));
+        total.calls += usage.calls;
+        total.prompt_tokens += usage.prompt_tokens;
+        total.completion_tokens += usage.completion_tokens;
+    }
+    table.push_str(format_row("TOTAL", &total, usd_per_1k_tokens, width).trim_end());
+    table
+}
+
+fn format_row(agent: &str, usage: &AgentUsage, usd_per_1k_tokens: f64, width: usize) -> String {
+    format!(
+        "   {:<width$}  {:>6}  {:>10}  {:>10}  {:>10.4}\n",
+        agent,
+        usage.calls,
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.estimated_cost(usd_per_1k_tokens),
+        width = width
+    )
+}
+
+// Print the running totals every minute while new calls keep coming in. Only the first call
+// starts a reporter, so `all` mode doesn't print the same table once per node.
+pub fn spawn_reporter() {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = interval(REPORT_INTERVAL);
+        ticker.tick().await;
+        let mut last_calls = 0;
+        loop {
+            ticker.tick().await;
+            let calls = session_total().calls;
+            if calls != last_calls {
+                last_calls = calls;
+                println!("{}", summary_table());
+            }
+        }
+    });
+}
+
+// Final summary on shutdown; printed once even when several nodes stop in the same process
+pub fn print_final_summary() {
+    static PRINTED: AtomicBool = AtomicBool::new(false);
+    if !PRINTED.swap(true, Ordering::SeqCst) {
+        println!("{}", summary_table());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cost_is_estimated_from_total_tokens() {
+        let usage = AgentUsage {
+            calls: 2,
+            prompt_tokens: 1500,
+            completion_tokens: 500,
+        };
+        assert_eq!(usage.total_tokens(), 2000);
+        assert!((usage.estimated_cost(0.002) - 0.004).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn summary_lists_each_agent_and_the_total() {
+        let mut per_agent = BTreeMap::new();
+        per_agent.insert(
+            "analysis".to_string(),
+            AgentUsage {
+                calls: 1,
+                prompt_tokens: 1000,
+                completion_tokens: 0,
+            },
+        );
+        per_agent.insert(
+            "camera".to_string(),
+            AgentUsage {
+                calls: 3,
+                prompt_tokens: 0,
+                completion_tokens: 1000,
+            },
+        );
+
+        let table = format_summary(&per_agent, 1.0);
+        let total = table.lines().last().unwrap();
+        assert!(table.contains("analysis"));
+        assert!(table.contains("camera"));
+        assert!(total.contains("TOTAL"));
+        assert!(total.contains('4'));
+        assert!(total.ends_with("2.0000"));
+    }
+}
@@ -0,0 +1,289 @@
+// Dictation for the GUI: record from the default microphone and turn the audio into text,
+// either with OpenAI Whisper or with a local transcription command. Built only with the
+// `voice` cargo feature so the default build doesn't pull in audio dependencies.
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex, mpsc as std_mpsc};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+// Recording stops on its own after this long, in case Stop is never pressed
+const MAX_RECORDING: Duration = Duration::from_secs(120);
+const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(60);
+// Used without --openai-base-url / OPENAI_BASE_URL
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1/";
+const WHISPER_MODEL: &str = "whisper-1";
+
+#[derive(Debug, Clone)]
+pub enum Transcriber {
+    // Whisper through the OpenAI API, or the configured OpenAI-compatible endpoint
+    OpenAi {
+        api_key: String,
+        base_url: Option<String>, // Normalized with a trailing slash
+        org: Option<String>,
+    },
+    Command(String), // Local program; gets the WAV file path as last argument and prints the text
+}
+
+// None when neither an OpenAI key nor a local command is available
+static TRANSCRIBER: Mutex<Option<Transcriber>> = Mutex::new(None);
+
+pub fn configure(transcriber: Option<Transcriber>) {
+    *TRANSCRIBER.lock().unwrap() = transcriber;
+}
+
+// A recording in progress. The input stream lives on its own thread since audio streams
+// can't move between threads.
+pub struct Recorder {
+    stop_tx: std_mpsc::Sender<()>,
+    wav_rx: oneshot::Receiver<Result<Vec<u8>, String>>,
+}
+
+impl Recorder {
+    pub fn start() -> Result<Self, String> {
+        let (ready_tx, ready_rx) = std_mpsc::channel::<Result<(), String>>();
+        let (stop_tx, stop_rx) = std_mpsc::channel::<()>();
+        let (wav_tx, wav_rx) = oneshot::channel();
+
+        std::thread::spawn(move || {
+            let samples = Arc::new(Mutex::new(Vec::<f32>::new()));
+            let (stream, sample_rate) = match open_input(samples.clone()) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            if let Err(e) = stream.play() {
+                let _ = ready_tx.send(Err(e.to_string()));
+                return;
+            }
+            let _ = ready_tx.send(Ok(()));
+
+            // Stop on request, when the recorder was dropped, or at the length cap
+            let _ = stop_rx.recv_timeout(MAX_RECORDING);
+            drop(stream);
+            let samples = std::mem::take(&mut *samples.lock().unwrap());
+            let _ = wav_tx.send(encode_wav(&samples, sample_rate));
+        });
+
+        ready_rx
+            .recv()
+            .map_err(|_| "the recording thread exited".to_string())??;
+        println!("🎤 Recording started");
+        Ok(Recorder { stop_tx, wav_rx })
+    }
+
+    // Stop recording and return the audio as a mono 16-bit WAV file
+    pub async fn finish(self) -> Result<Vec<u8>, String> {
+        let _ = self.stop_tx.send(());
+        let wav = self
+            .wav_rx
+            .await
+            .map_err(|_| "the recording thread exited".to_string())?;
+        println!("🎤 Recording stopped");
+        wav
+    }
+}
+
+// Open the default microphone, appending mono samples in -1.0..=1.0 to `samples`
+fn open_input(samples: Arc<Mutex<Vec<f32>>>) -> Result<(cpal::Stream, u32), String> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("no microphone was found")?;
+    let config = device.default_input_config().map_err(|e| e.to_string())?;
+    let sample_rate = config.sample_rate().0;
+    let channels = usize::from(config.channels()).max(1);
+    let on_error = |e: cpal::StreamError| eprintln!("❌ Microphone error: {}", e);
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _: &_| push_mono(&samples, data, channels, |s| s),
+            on_error,
+            None,
+        ),
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[i16], _: &_| {
+                push_mono(&samples, data, channels, |s| {
+                    f32::from(s) / f32::from(i16::MAX)
+                })
+            },
+            on_error,
+            None,
+        ),
+        format => return Err(format!("unsupported microphone sample format {:?}", format)),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok((stream, sample_rate))
+}
+
+// Average interleaved frames down to one channel
+fn push_mono<T: Copy>(
+    samples: &Mutex<Vec<f32>>,
+    data: &[T],
+    channels: usize,
+    to_f32: impl Fn(T) -> f32,
+) {
+    let mut samples = samples.lock().unwrap();
+    samples.extend(
+        data.chunks(channels)
+            .map(|frame| frame.iter().map(|s| to_f32(*s)).sum::<f32>() / frame.len() as f32),
+    );
+}
+
+fn encode_wav(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, String> {
+    if samples.is_empty() {
+        return Err("no audio was recorded".to_string());
+    }
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut wav, spec).map_err(|e| e.to_string())?;
+    for sample in samples {
+        writer
+            .write_sample((sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)
+            .map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(wav.into_inner())
+}
+
+// Transcribe a WAV recording with the configured backend. Runs on the GUI's tokio executor.
+pub async fn transcribe(wav: Vec<u8>) -> Result<String, String> {
+    let transcriber = TRANSCRIBER.lock().unwrap().clone().ok_or(
+        "voice input needs OPENAI_API_KEY or --transcribe-command for a local transcriber",
+    )?;
+    let text = tokio::time::timeout(TRANSCRIBE_TIMEOUT, run_transcriber(&transcriber, wav))
+        .await
+        .map_err(|_| format!("no transcript within {}s", TRANSCRIBE_TIMEOUT.as_secs()))??;
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err("no speech was recognized".to_string());
+    }
+    println!("🎤 Transcribed {} characters", text.chars().count());
+    Ok(text)
+}
+
+async fn run_transcriber(transcriber: &Transcriber, wav: Vec<u8>) -> Result<String, String> {
+    match transcriber {
+        Transcriber::OpenAi {
+            api_key,
+            base_url,
+            org,
+        } => transcribe_with_whisper(api_key, base_url.as_deref(), org.as_deref(), wav).await,
+        Transcriber::Command(command) => transcribe_with_command(command, wav).await,
+    }
+}
+
+#[derive(Deserialize)]
+struct WhisperResponse {
+    text: String,
+}
+
+fn transcriptions_url(base_url: Option<&str>) -> String {
+    format!(
+        "{}audio/transcriptions",
+        base_url.unwrap_or(OPENAI_BASE_URL)
+    )
+}
+
+async fn transcribe_with_whisper(
+    api_key: &str,
+    base_url: Option<&str>,
+    org: Option<&str>,
+    wav: Vec<u8>,
+) -> Result<String, String> {
+    let file = reqwest::multipart::Part::bytes(wav)
+        .file_name("dictation.wav")
+        .mime_str("audio/wav")
+        .map_err(|e| e.to_string())?;
+    let form = reqwest::multipart::Form::new()
+        .text("model", WHISPER_MODEL)
+        .part("file", file);
+    let mut request = reqwest::Client::new()
+        .post(transcriptions_url(base_url))
+        .bearer_auth(api_key)
+        .multipart(form);
+    if let Some(org) = org {
+        request = request.header("OpenAI-Organization", org);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("Whisper returned {}: {}", status, body));
+    }
+    response
+        .json::<WhisperResponse>()
+        .await
+        .map(|whisper| whisper.text)
+        .map_err(|e| e.to_string())
+}
+
+async fn transcribe_with_command(command: &str, wav: Vec<u8>) -> Result<String, String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or("the transcription command is empty")?;
+    let path =
+        std::env::temp_dir().join(format!("liquidos-dictation-{}.wav", uuid::Uuid::new_v4()));
+    tokio::fs::write(&path, wav)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let output = Command::new(program).args(parts).arg(&path).output().await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let output = output.map_err(|e| format!("could not run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_encode_as_mono_16_bit_wav() {
+        let wav = encode_wav(&[0.0, 0.5, -1.0, 2.0], 16_000).unwrap();
+        let reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.spec().sample_rate, 16_000);
+        let samples: Vec<i16> = reader.into_samples().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, vec![0, 16383, -32767, 32767]);
+        assert!(encode_wav(&[], 16_000).is_err());
+    }
+
+    #[test]
+    fn whisper_follows_the_configured_openai_endpoint() {
+        assert_eq!(
+            transcriptions_url(None),
+            "https://api.openai.com/v1/audio/transcriptions"
+        );
+        assert_eq!(
+            transcriptions_url(Some("https://gateway.example.com/openai/v1/")),
+            "https://gateway.example.com/openai/v1/audio/transcriptions"
+        );
+    }
+
+    #[test]
+    fn stereo_frames_are_averaged_to_mono() {
+        let samples = Mutex::new(Vec::new());
+        push_mono(&samples, &[0.2f32, 0.4, -1.0, 1.0], 2, |s| s);
+        let mono = samples.into_inner().unwrap();
+        assert_eq!(mono.len(), 2);
+        assert!((mono[0] - 0.3).abs() < 1e-6);
+        assert_eq!(mono[1], 0.0);
+    }
+}
@@ -0,0 +1,92 @@
+// Small PNG plot of the ECG samples an analysis was about (--render-waveform), sent to the
+// doctor GUI next to the report. Only built with the `waveform` feature.
+use plotters::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+const WIDTH: u32 = 480;
+const HEIGHT: u32 = 140;
+// Longest stretch drawn, about 10 seconds at 250 Hz; more would blur into a solid band
+const MAX_SAMPLES: usize = 2500;
+
+// First word of the query naming an existing file, e.g. "Analyze ecg/bed4.csv please"
+pub fn sample_file_in(query: &str) -> Option<PathBuf> {
+    query
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| "'\"`,;:()".contains(c)))
+        .map(Path::new)
+        .find(|path| path.is_file())
+        .map(Path::to_path_buf)
+}
+
+pub fn render_png(samples: &[f64]) -> Result<Vec<u8>, String> {
+    let samples = &samples[..samples.len().min(MAX_SAMPLES)];
+    if samples.len() < 2 {
+        return Err("at least two samples are needed to draw a waveform".to_string());
+    }
+    let (low, high) = samples
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &v| {
+            (low.min(v), high.max(v))
+        });
+    let padding = ((high - low) * 0.1).max(1e-6);
+
+    let mut rgb = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut rgb, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).map_err(|e| e.to_string())?;
+        let mut chart = ChartBuilder::on(&root)
+            .margin(6)
+            .build_cartesian_2d(0..samples.len(), (low - padding)..(high + padding))
+            .map_err(|e| e.to_string())?;
+        chart
+            .draw_series(LineSeries::new(
+                samples.iter().enumerate().map(|(i, v)| (i, *v)),
+                &RED,
+            ))
+            .map_err(|e| e.to_string())?;
+        root.present().map_err(|e| e.to_string())?;
+    }
+
+    let plot = image::RgbImage::from_raw(WIDTH, HEIGHT, rgb)
+        .ok_or_else(|| "plot buffer has the wrong size".to_string())?;
+    let mut png = Vec::new();
+    plot.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_render_to_a_png_of_the_thumbnail_size() {
+        let samples: Vec<f64> = (0..3000).map(|i| (i as f64 / 20.0).sin()).collect();
+        let png = render_png(&samples).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (WIDTH, HEIGHT));
+
+        assert!(render_png(&[0.5]).is_err());
+    }
+
+    #[test]
+    fn only_existing_files_count_as_sample_files() {
+        let path = std::env::temp_dir().join("waveform_test_samples.csv");
+        std::fs::write(&path, "0.1, 0.2, 0.3").unwrap();
+        let query = format!("Analyze '{}' for arrhythmia", path.display());
+        assert_eq!(sample_file_in(&query), Some(path.clone()));
+        assert_eq!(sample_file_in("Analyze the latest ECG"), None);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,161 @@
+// Delivery of finished analyses to an external system (e.g. an EHR) with --webhook-url. Built
+// only with the `webhook` cargo feature. Delivery is best-effort: it runs detached from the
+// analysis, so a slow or failing endpoint never holds up the report going back to the doctor.
+use crate::agents::AnalysisReport;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MAX_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1); // Doubled after every failed attempt
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static WEBHOOK_URL: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn configure(url: Option<String>) {
+    if let Some(url) = &url {
+        println!("🪝 Posting finished analyses to {}", url);
+    }
+    *WEBHOOK_URL.lock().unwrap() = url;
+}
+
+// Reports carry patient data, so only https is accepted, except plain http to this machine
+pub fn parse_url(value: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(value)
+        .map_err(|e| format!("invalid webhook URL '{}': {}", value, e))?;
+    let Some(host) = url.host_str() else {
+        return Err(format!(
+            "invalid webhook URL '{}': expected an https:// URL with a host",
+            value
+        ));
+    };
+    let local = matches!(host, "localhost" | "127.0.0.1" | "[::1]");
+    match url.scheme() {
+        "https" => Ok(url.to_string()),
+        "http" if local => Ok(url.to_string()),
+        "http" => Err(format!(
+            "invalid webhook URL '{}': plain http is only allowed to localhost; use https://",
+            value
+        )),
+        _ => Err(format!(
+            "invalid webhook URL '{}': expected an https:// URL",
+            value
+        )),
+    }
+}
+
+// POST the report to the configured URL in the background; does nothing without --webhook-url
+pub fn deliver(report: &AnalysisReport) {
+    let Some(url) = WEBHOOK_URL.lock().unwrap().clone() else {
+        return;
+    };
+    let body = match serde_json::to_string(report) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("❌ [Webhook] Failed to serialize analysis report: {}", e);
+            return;
+        }
+    };
+    let correlation_id = report.correlation_id.clone().unwrap_or_else(|| "-".into());
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!(
+                    "❌ [Webhook] [{}] Could not create HTTP client: {}",
+                    correlation_id, e
+                );
+                return;
+            }
+        };
+        let mut delay = FIRST_RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            match post(&client, &url, &correlation_id, &body).await {
+                Ok(()) => {
+                    println!(
+                        "🪝 [Webhook] [{}] Delivered analysis (attempt {})",
+                        correlation_id, attempt
+                    );
+                    return;
+                }
+                Err(Failure { reason, retryable }) => {
+                    eprintln!(
+                        "⚠️ [Webhook] [{}] Attempt {}/{} failed: {}",
+                        correlation_id, attempt, MAX_ATTEMPTS, reason
+                    );
+                    if !retryable {
+                        break;
+                    }
+                }
+            }
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+        eprintln!(
+            "❌ [Webhook] [{}] Gave up delivering analysis to {}",
+            correlation_id, url
+        );
+    });
+}
+
+struct Failure {
+    reason: String,
+    retryable: bool,
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    correlation_id: &str,
+    body: &str,
+) -> Result<(), Failure> {
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Correlation-Id", correlation_id)
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| Failure {
+            reason: e.to_string(),
+            retryable: true,
+        })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    Err(Failure {
+        reason: format!("endpoint returned {}", status),
+        retryable: is_retryable(status.as_u16()),
+    })
+}
+
+// Server errors, timeouts and throttling may pass on a retry; other client errors won't
+fn is_retryable(status: u16) -> bool {
+    status >= 500 || status == 408 || status == 429
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_https_urls_or_local_http_urls_are_accepted() {
+        assert!(parse_url("https://ehr.example.com/hooks/ecg").is_ok());
+        assert!(parse_url("http://localhost:8080/ecg").is_ok());
+        assert!(parse_url("http://127.0.0.1:8080/ecg").is_ok());
+        assert!(parse_url("http://[::1]:8080/ecg").is_ok());
+        assert!(parse_url("http://ehr.example.com/hooks/ecg").is_err());
+        assert!(parse_url("http://10.0.0.5/ecg").is_err());
+        assert!(parse_url("ftp://ehr.example.com/ecg").is_err());
+        assert!(parse_url("ehr.example.com/ecg").is_err());
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        assert!(is_retryable(500) && is_retryable(503));
+        assert!(is_retryable(408) && is_retryable(429));
+        assert!(!is_retryable(400) && !is_retryable(401) && !is_retryable(404));
+    }
+}